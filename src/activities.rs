@@ -0,0 +1,770 @@
+//! Mapping from configured activities to concrete agent requests.
+
+use std::collections::BTreeMap;
+
+use crate::cfgparse::{Activity, LaunchMode, PollFormat};
+use crate::connection::{ConnError, ConnectionOps, FgResult};
+use crate::proto::{ActivityId, Request, Response};
+
+/// Allocates the stable activity ids of one stage: `<stage>.<name>`,
+/// with a numeric suffix only when the same name repeats within the
+/// stage. Ids derive from the config rather than from agent-side
+/// sequence numbers, so output files keep their names when chains are
+/// edited.
+pub struct IdAlloc {
+    prefix: String,
+    used: BTreeMap<String, u32>,
+}
+
+impl IdAlloc {
+    pub fn new(stage: &str) -> IdAlloc {
+        // Stage names are free-form YAML; ids are file names.
+        let prefix = stage
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        IdAlloc {
+            prefix,
+            used: BTreeMap::new(),
+        }
+    }
+
+    fn next(&mut self, name: &str) -> ActivityId {
+        let n = self.used.entry(name.to_string()).or_insert(0);
+        *n += 1;
+        match *n {
+            1 => format!("{}.{name}", self.prefix),
+            n => format!("{}.{name}-{n}", self.prefix),
+        }
+    }
+}
+
+/// What starting a chain entry produced on the agent.
+pub enum Started {
+    /// A background activity that must be stopped at the end of the stage.
+    Bg(ActivityId),
+    /// A foreground command that already ran to completion.
+    Fg(ActivityId, FgResult),
+}
+
+fn bg(ids: &mut IdAlloc, name: &str, cmd: Vec<String>) -> Request {
+    Request::SpawnBg {
+        id: ids.next(name),
+        name: name.to_string(),
+        cmd,
+    }
+}
+
+fn fg(ids: &mut IdAlloc, name: &str, cmd: Vec<String>) -> Request {
+    Request::SpawnFg {
+        id: ids.next(name),
+        name: name.to_string(),
+        cmd,
+    }
+}
+
+fn poll(
+    ids: &mut IdAlloc,
+    name: &str,
+    period_ms: u64,
+    paths: Vec<String>,
+    format: PollFormat,
+) -> Request {
+    Request::Poll {
+        id: ids.next(name),
+        name: name.to_string(),
+        period_ms,
+        paths,
+        binary: format == PollFormat::Binary,
+    }
+}
+
+/// Build the agent request implementing one activity.
+///
+/// Marks and parallel blocks have no single request and are handled by
+/// the caller.
+fn to_request(
+    activity: &Activity,
+    ids: &mut IdAlloc,
+    seed: u64,
+    poll_format: PollFormat,
+) -> Request {
+    match activity {
+        Activity::Mpstat { period } => {
+            bg(ids, "mpstat", strvec(&["mpstat", "-P", "ALL", &period.to_string()]))
+        }
+        // S_TIME_FORMAT pins the timestamp format regardless of the agent
+        // locale, so the parser sees deterministic input.
+        Activity::Iostat { period } => bg(
+            ids,
+            "iostat",
+            strvec(&[
+                "env",
+                "S_TIME_FORMAT=ISO",
+                "iostat",
+                "-x",
+                "-t",
+                "-y",
+                &period.to_string(),
+            ]),
+        ),
+        Activity::Sar { period } => bg(ids, "sar", strvec(&["sar", "-A", &period.to_string()])),
+        Activity::Pidstat { period } => bg(
+            ids,
+            "pidstat",
+            strvec(&["pidstat", "-H", "-h", "-u", "-r", "-d", &period.to_string()]),
+        ),
+        Activity::Vmstat { period } => {
+            bg(ids, "vmstat", strvec(&["vmstat", "-t", "-n", &period.to_string()]))
+        }
+        Activity::Meminfo { period_ms } => {
+            poll(ids, "meminfo", *period_ms, strvec(&["/proc/meminfo"]), poll_format)
+        }
+        Activity::Netdev { period_ms } => {
+            poll(ids, "netdev", *period_ms, strvec(&["/proc/net/dev"]), poll_format)
+        }
+        // Like ethtool below, the database counters come from a shell loop
+        // emitting the poll-log format; `psql -Atx` prints name|value
+        // lines the dbstat parser reads directly.
+        Activity::Pgstat { period, dbname } => {
+            let db = dbname
+                .as_ref()
+                .map(|db| format!("-d '{db}' "))
+                .unwrap_or_default();
+            bg(
+                ids,
+                "pgstat",
+                strvec(&[
+                    "sh",
+                    "-c",
+                    &format!(
+                        "while :; do echo \"=== $(($(date +%s%N) / 1000000))\"; \
+                         echo '--- pgstat:database'; \
+                         psql {db}-X -Atx -c 'SELECT sum(xact_commit) AS xact_commit, \
+                         sum(xact_rollback) AS xact_rollback, sum(blks_read) AS blks_read, \
+                         sum(blks_hit) AS blks_hit, sum(tup_inserted) AS tup_inserted, \
+                         sum(tup_updated) AS tup_updated, sum(tup_deleted) AS tup_deleted, \
+                         sum(deadlocks) AS deadlocks FROM pg_stat_database'; \
+                         echo '--- pgstat:bgwriter'; \
+                         psql {db}-X -Atx -c 'SELECT * FROM pg_stat_bgwriter'; \
+                         sleep {period}; done"
+                    ),
+                ]),
+            )
+        }
+        Activity::Mysqlstat { period } => bg(
+            ids,
+            "mysqlstat",
+            strvec(&[
+                "sh",
+                "-c",
+                &format!(
+                    "while :; do echo \"=== $(($(date +%s%N) / 1000000))\"; \
+                     echo '--- mysql:status'; mysql -N -B -e 'SHOW GLOBAL STATUS'; \
+                     sleep {period}; done"
+                ),
+            ]),
+        ),
+        Activity::Redis { period, url } => {
+            let url = url
+                .as_ref()
+                .map(|url| format!("-u '{url}' "))
+                .unwrap_or_default();
+            bg(
+                ids,
+                "redis",
+                strvec(&[
+                    "sh",
+                    "-c",
+                    &format!(
+                        "while :; do echo \"=== $(($(date +%s%N) / 1000000))\"; \
+                         echo '--- redis:info'; redis-cli {url}INFO; \
+                         sleep {period}; done"
+                    ),
+                ]),
+            )
+        }
+        Activity::Memcached { period, addr } => {
+            let (host, port) = addr.rsplit_once(':').unwrap_or((addr.as_str(), "11211"));
+            bg(
+                ids,
+                "memcached",
+                strvec(&[
+                    "sh",
+                    "-c",
+                    &format!(
+                        "while :; do echo \"=== $(($(date +%s%N) / 1000000))\"; \
+                         echo '--- memcached:stats'; \
+                         printf 'stats\\r\\nquit\\r\\n' | nc '{host}' '{port}'; \
+                         sleep {period}; done"
+                    ),
+                ]),
+            )
+        }
+        Activity::Jvm {
+            pattern,
+            period,
+            jfr,
+        } => {
+            // `pgrep -n` picks the newest match, so restarting the target
+            // between stages keeps the pattern valid.
+            let mut script = format!(
+                "pid=$(pgrep -n -f '{pattern}')\n\
+                 [ -n \"$pid\" ]\n"
+            );
+            if *jfr {
+                script.push_str("jcmd \"$pid\" JFR.start name=pmppt\n");
+            }
+            script.push_str(&format!(
+                "while :; do echo \"=== $(($(date +%s%N) / 1000000))\"; \
+                 echo '--- jstat'; jstat -gcutil \"$pid\"; sleep {period}; done\n"
+            ));
+            bg(ids, "jvm", strvec(&["sh", "-c", &script]))
+        }
+        // There is no file to poll for ethtool counters: a shell loop
+        // emits the poll-log format the plotter already understands.
+        Activity::Ethtool { iface, period_ms } => bg(
+            ids,
+            "ethtool",
+            strvec(&[
+                "sh",
+                "-c",
+                &format!(
+                    "while :; do echo \"=== $(($(date +%s%N) / 1000000))\"; \
+                     echo '--- ethtool:{iface}'; ethtool -S '{iface}'; \
+                     sleep {}; done",
+                    *period_ms as f64 / 1000.0
+                ),
+            ]),
+        ),
+        Activity::Interrupts { period_ms } => {
+            poll(ids, "interrupts", *period_ms, strvec(&["/proc/interrupts"]), poll_format)
+        }
+        Activity::Pressure { period_ms } => poll(
+            ids,
+            "pressure",
+            *period_ms,
+            strvec(&[
+                "/proc/pressure/cpu",
+                "/proc/pressure/io",
+                "/proc/pressure/memory",
+            ]),
+            poll_format,
+        ),
+        Activity::PrepareFs {
+            drop_caches,
+            create,
+            trim,
+            confirm_trim: _,
+        } => {
+            let mut script = String::from("set -e\n");
+            for (path, size) in create {
+                script.push_str(&format!("fallocate -l '{size}' '{path}'\n"));
+            }
+            if *drop_caches {
+                script.push_str("sync\necho 3 > /proc/sys/vm/drop_caches\n");
+            }
+            if let Some(mnt) = trim {
+                script.push_str(&format!("fstrim -v '{mnt}'\n"));
+            }
+            fg(ids, "prepare_fs", strvec(&["sh", "-c", &script]))
+        }
+        // Machine-state changes run as shell scripts in the session
+        // directory: the apply script saves the previous state into
+        // `*.prev` files that the restore script (built by
+        // [`cleanup_requests`]) reads back at the end of the stage.
+        Activity::Cpufreq { governor, no_turbo } => {
+            let mut script = format!(
+                "set -e\n\
+                 cat /sys/devices/system/cpu/cpu0/cpufreq/scaling_governor \
+                 > cpufreq-governor.prev\n\
+                 for g in /sys/devices/system/cpu/cpu*/cpufreq/scaling_governor; do\n\
+                 echo '{governor}' > \"$g\"\n\
+                 done\n"
+            );
+            if *no_turbo {
+                // Intel pstate and the generic cpufreq boost knob spell
+                // "turbo off" differently; the prev file records which
+                // knob was found along with its old value.
+                script.push_str(
+                    "t=/sys/devices/system/cpu/intel_pstate/no_turbo; off=1\n\
+                     [ -e \"$t\" ] || { t=/sys/devices/system/cpu/cpufreq/boost; off=0; }\n\
+                     if [ -e \"$t\" ]; then\n\
+                     echo \"$t $(cat \"$t\")\" > cpufreq-turbo.prev\n\
+                     echo \"$off\" > \"$t\"\n\
+                     fi\n",
+                );
+            }
+            fg(ids, "cpufreq", strvec(&["sh", "-c", &script]))
+        }
+        Activity::Sysctl { settings } => {
+            let mut script = String::from("set -e\n: > sysctl.prev\n");
+            for (knob, value) in settings {
+                // `sysctl -n` keeps the prev file free of the key that
+                // `sysctl <knob>` would print along with the value.
+                script.push_str(&format!(
+                    "echo \"{knob}=$(sysctl -n '{knob}')\" >> sysctl.prev\n\
+                     sysctl -w '{knob}={value}'\n"
+                ));
+            }
+            fg(ids, "sysctl", strvec(&["sh", "-c", &script]))
+        }
+        // Without nftables counters this is plain file polling; with them
+        // a command has to run, so a shell loop emits the same poll-log
+        // format to stdout instead.
+        Activity::Conntrack { period_ms, nft } => {
+            let paths = [
+                "/proc/sys/net/netfilter/nf_conntrack_count",
+                "/proc/sys/net/netfilter/nf_conntrack_max",
+                "/proc/net/stat/nf_conntrack",
+            ];
+            if !nft {
+                poll(ids, "conntrack", *period_ms, strvec(&paths), poll_format)
+            } else {
+                let cats: String = paths
+                    .iter()
+                    .map(|p| format!("echo '--- {p}'; cat '{p}'; "))
+                    .collect();
+                bg(
+                    ids,
+                    "conntrack",
+                    strvec(&[
+                        "sh",
+                        "-c",
+                        &format!(
+                            "while :; do echo \"=== $(($(date +%s%N) / 1000000))\"; \
+                             {cats}echo '--- conntrack:nft'; nft list counters; \
+                             sleep {}; done",
+                            *period_ms as f64 / 1000.0
+                        ),
+                    ]),
+                )
+            }
+        }
+        Activity::Nfs { period_ms } => poll(
+            ids,
+            "nfs",
+            *period_ms,
+            strvec(&[
+                "/proc/net/rpc/nfs",
+                "/proc/net/rpc/nfsd",
+                "/proc/self/mountstats",
+            ]),
+            poll_format,
+        ),
+        Activity::Cyclictest { duration, args } => {
+            let mut cmd = strvec(&["cyclictest", "-q", "-m", "-h", "1000"]);
+            cmd.push(format!("-D{duration}"));
+            cmd.extend(args.iter().cloned());
+            bg(ids, "cyclictest", cmd)
+        }
+        Activity::Schbench { duration, args } => {
+            let mut cmd = strvec(&["schbench", "-r", &duration.to_string()]);
+            cmd.extend(args.iter().cloned());
+            bg(ids, "schbench", cmd)
+        }
+        Activity::Smart { devices } => fg(
+            ids,
+            "smart",
+            strvec(&["sh", "-c", &smart_script(devices, "smart-before")]),
+        ),
+        Activity::Fixture { tmpfs, loopdev } => {
+            let mut script = String::from("set -e\n: > fixture-loops\n");
+            for (mnt, size) in tmpfs {
+                script.push_str(&format!(
+                    "mkdir -p '{mnt}'\nmount -t tmpfs -o size={size} tmpfs '{mnt}'\n"
+                ));
+            }
+            for (file, size) in loopdev {
+                // `losetup -f --show` picks the first free device and
+                // prints its name; the fixture-loops file maps it back to
+                // the backing file for teardown.
+                script.push_str(&format!(
+                    "fallocate -l '{size}' '{file}'\n\
+                     dev=$(losetup -f --show '{file}')\n\
+                     echo \"$dev {file}\" >> fixture-loops\n\
+                     echo \"loop {file} $dev\"\n"
+                ));
+            }
+            fg(ids, "fixture", strvec(&["sh", "-c", &script]))
+        }
+        Activity::IrqAffinity { irqs } => {
+            let mut script = String::from("set -e\n: > irq-affinity.prev\n");
+            for (irq, mask) in irqs {
+                if irq.chars().all(|c| c.is_ascii_digit()) {
+                    script.push_str(&format!("irqs={irq}\n"));
+                } else {
+                    // Non-numeric keys select IRQs by device name, so a
+                    // whole NIC queue family pins with one entry.
+                    script.push_str(&format!(
+                        "irqs=$(awk -F: -v pat='{irq}' \
+                         '$2 ~ pat {{ gsub(/ /, \"\", $1); print $1 }}' /proc/interrupts)\n"
+                    ));
+                }
+                script.push_str(&format!(
+                    "[ -n \"$irqs\" ]\n\
+                     for irq in $irqs; do\n\
+                     echo \"$irq $(cat /proc/irq/$irq/smp_affinity)\" >> irq-affinity.prev\n\
+                     echo '{mask}' > /proc/irq/$irq/smp_affinity\n\
+                     done\n"
+                ));
+            }
+            fg(ids, "irq_affinity", strvec(&["sh", "-c", &script]))
+        }
+        Activity::Fio { args } => {
+            // The logs land in the agent session directory and are picked
+            // up by the fio plotter via the "fio" prefix.
+            let mut cmd = strvec(&["fio"]);
+            cmd.extend(args.iter().cloned());
+            // The run seed pins the random I/O pattern unless the config
+            // insists on its own.
+            if !args.iter().any(|a| a.starts_with("--randseed")) {
+                cmd.push(format!("--randseed={seed}"));
+            }
+            cmd.push("--write_bw_log=fio".to_string());
+            cmd.push("--write_hist_log=fio".to_string());
+            cmd.push("--output-format=json".to_string());
+            cmd.push("--output=fio.json".to_string());
+            fg(ids, "fio", cmd)
+        }
+        Activity::Launch { cmd, mode, .. } => match mode {
+            LaunchMode::Bg => bg(ids, "launch", cmd.clone()),
+            LaunchMode::Fg => fg(ids, "launch", cmd.clone()),
+        },
+        Activity::Mark { .. } | Activity::Barrier {} | Activity::Parallel(_) => {
+            unreachable!("handled by the controller")
+        }
+        Activity::Poll { period_ms, paths } => {
+            poll(ids, "poll", *period_ms, paths.clone(), poll_format)
+        }
+        // The command runs inside one shell loop rather than being
+        // re-spawned from the controller, so a slow link costs nothing
+        // per sample.
+        Activity::Snapshot { cmd, period } => bg(
+            ids,
+            "snapshot",
+            strvec(&[
+                "sh",
+                "-c",
+                &format!(
+                    "while :; do echo \"=== $(($(date +%s%N) / 1000000))\"; \
+                     echo '--- snapshot'; {cmd}; \
+                     sleep {period}; done"
+                ),
+            ]),
+        ),
+    }
+}
+
+/// Paths a fixture activity creates on the agent: tmpfs mount points and
+/// loop backing files. The controller publishes them as stage artifacts
+/// so later stages and the report can refer to them.
+pub fn fixture_paths(activity: &Activity) -> Vec<String> {
+    match activity {
+        Activity::Fixture { tmpfs, loopdev } => {
+            tmpfs.keys().chain(loopdev.keys()).cloned().collect()
+        }
+        Activity::Parallel(entries) => entries.iter().flat_map(fixture_paths).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Storage artifacts an entry publishes, one slot per started request
+/// so the caller can zip them with the [`Started`] results: the artifact
+/// name and optional extraction regex of a foreground launch, `None`
+/// for everything else.
+pub fn artifact_specs(activity: &Activity) -> Vec<Option<(String, Option<String>)>> {
+    match activity {
+        Activity::Parallel(entries) => entries.iter().flat_map(artifact_specs).collect(),
+        Activity::Launch {
+            artifact: Some(name),
+            extract,
+            ..
+        } => vec![Some((name.clone(), extract.clone()))],
+        _ => vec![None],
+    }
+}
+
+/// Requests undoing the machine-state changes of an activity, run as
+/// foreground commands after everything else in the stage has stopped.
+/// Empty for activities that change nothing.
+pub fn cleanup_requests(activity: &Activity, ids: &mut IdAlloc) -> Vec<Request> {
+    match activity {
+        Activity::Cpufreq { .. } => {
+            let script = "set -e\n\
+                 prev=$(cat cpufreq-governor.prev)\n\
+                 for g in /sys/devices/system/cpu/cpu*/cpufreq/scaling_governor; do\n\
+                 echo \"$prev\" > \"$g\"\n\
+                 done\n\
+                 if [ -e cpufreq-turbo.prev ]; then\n\
+                 read -r t v < cpufreq-turbo.prev\n\
+                 echo \"$v\" > \"$t\"\n\
+                 fi\n";
+            vec![fg(ids, "cpufreq-restore", strvec(&["sh", "-c", script]))]
+        }
+        Activity::Sysctl { .. } => {
+            let script = "set -e\n\
+                 while read -r setting; do\n\
+                 sysctl -w \"$setting\"\n\
+                 done < sysctl.prev\n";
+            vec![fg(ids, "sysctl-restore", strvec(&["sh", "-c", script]))]
+        }
+        Activity::Smart { devices } => vec![fg(
+            ids,
+            "smart-after",
+            strvec(&["sh", "-c", &smart_script(devices, "smart-after")]),
+        )],
+        Activity::Fixture { tmpfs, loopdev } => {
+            // Loop devices may back files inside the tmpfs, so they
+            // detach before the mounts go away.
+            let mut script = String::from("set -e\n");
+            if !loopdev.is_empty() {
+                script.push_str(
+                    "while read -r dev file; do\n\
+                     losetup -d \"$dev\"\n\
+                     rm -f \"$file\"\n\
+                     done < fixture-loops\n",
+                );
+            }
+            for mnt in tmpfs.keys() {
+                script.push_str(&format!("umount '{mnt}'\n"));
+            }
+            vec![fg(ids, "fixture-teardown", strvec(&["sh", "-c", &script]))]
+        }
+        // The JFR recording only hits the disk on an explicit dump; the
+        // jstat loop itself needs no cleanup, it is killed with the stage.
+        Activity::Jvm {
+            pattern, jfr: true, ..
+        } => {
+            let script = format!(
+                "set -e\n\
+                 pid=$(pgrep -n -f '{pattern}')\n\
+                 jcmd \"$pid\" JFR.dump name=pmppt filename=\"$PWD/jvm.jfr\"\n\
+                 jcmd \"$pid\" JFR.stop name=pmppt\n"
+            );
+            vec![fg(ids, "jvm-jfr-dump", strvec(&["sh", "-c", &script]))]
+        }
+        Activity::IrqAffinity { .. } => {
+            let script = "set -e\n\
+                 while read -r irq mask; do\n\
+                 echo \"$mask\" > /proc/irq/$irq/smp_affinity\n\
+                 done < irq-affinity.prev\n";
+            vec![fg(ids, "irq_affinity-restore", strvec(&["sh", "-c", script]))]
+        }
+        Activity::Parallel(entries) => entries
+            .iter()
+            .flat_map(|e| cleanup_requests(e, ids))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn interpret(resp: Response, requested: &ActivityId) -> Result<Started, ConnError> {
+    match resp {
+        Response::Started { id } => Ok(Started::Bg(id)),
+        Response::Finished {
+            status,
+            stdout,
+            stderr,
+        } => Ok(Started::Fg(
+            requested.clone(),
+            FgResult {
+                status,
+                stdout,
+                stderr,
+            },
+        )),
+        other => Err(ConnError::Unexpected(format!("{other:?}"))),
+    }
+}
+
+/// Start one activity chain entry on an agent, with ids drawn from the
+/// stage allocator.
+///
+/// A plain entry yields one [`Started`]; a parallel block yields one per
+/// grouped entry, started with pipelined requests.
+pub fn start(
+    conn: &mut dyn ConnectionOps,
+    activity: &Activity,
+    ids: &mut IdAlloc,
+    seed: u64,
+    poll_format: PollFormat,
+) -> Result<Vec<Started>, ConnError> {
+    let mut results = start_all(conn, &[activity], ids, seed, poll_format, None)?;
+    Ok(results.pop().expect("one entry yields one result list"))
+}
+
+/// Start several consecutive chain entries with one pipelined exchange,
+/// returning one result list per entry in entry order.
+///
+/// The controller batches the runs of plain entries between marks and
+/// barriers through here, so a chain of N entries costs one round trip
+/// instead of N — on a fleet of slow links this is what keeps stage
+/// start skew down.
+///
+/// With `resume_at` a [`Request::WaitUntil`] for that agent-local
+/// timestamp rides in front of the batch: the agent sleeps it out and
+/// then runs the spawns already sitting in its socket buffer, so the
+/// entries start at the agreed instant regardless of request latency.
+pub fn start_all(
+    conn: &mut dyn ConnectionOps,
+    entries: &[&Activity],
+    ids: &mut IdAlloc,
+    seed: u64,
+    poll_format: PollFormat,
+    resume_at: Option<u64>,
+) -> Result<Vec<Vec<Started>>, ConnError> {
+    let mut reqs = Vec::new();
+    if let Some(millis) = resume_at {
+        reqs.push(Request::WaitUntil { millis });
+    }
+    let mut counts = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let before = reqs.len();
+        match entry {
+            Activity::Parallel(grouped) => {
+                reqs.extend(grouped.iter().map(|e| to_request(e, ids, seed, poll_format)));
+            }
+            single => reqs.push(to_request(single, ids, seed, poll_format)),
+        }
+        counts.push(reqs.len() - before);
+    }
+    let mut resps = conn.transact_many(&reqs)?.into_iter().zip(&reqs);
+    if resume_at.is_some() {
+        match resps.next() {
+            Some((Response::Resumed, _)) => {}
+            other => {
+                return Err(ConnError::Unexpected(format!("{:?}", other.map(|(r, _)| r))));
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .map(|count| {
+            resps
+                .by_ref()
+                .take(count)
+                .map(|(resp, req)| interpret(resp, request_id(req)))
+                .collect()
+        })
+        .collect()
+}
+
+/// The activity id a spawn request was issued with; responses arrive in
+/// request order, so fg results can be tied back to their activity.
+fn request_id(req: &Request) -> &ActivityId {
+    match req {
+        Request::Poll { id, .. } | Request::SpawnBg { id, .. } | Request::SpawnFg { id, .. } => id,
+        other => unreachable!("activities only spawn and poll: {other:?}"),
+    }
+}
+
+/// External tools an activity needs on the agent, for pre-flight checks.
+pub fn required_tools(activity: &Activity) -> Vec<String> {
+    match activity {
+        Activity::Mpstat { .. } => vec!["mpstat".to_string()],
+        Activity::Iostat { .. } => vec!["iostat".to_string()],
+        Activity::Sar { .. } => vec!["sar".to_string()],
+        Activity::Pidstat { .. } => vec!["pidstat".to_string()],
+        Activity::Vmstat { .. } => vec!["vmstat".to_string()],
+        Activity::Pgstat { .. } => vec!["psql".to_string()],
+        Activity::Mysqlstat { .. } => vec!["mysql".to_string()],
+        Activity::Redis { .. } => vec!["redis-cli".to_string()],
+        Activity::Memcached { .. } => vec!["nc".to_string()],
+        Activity::Conntrack { nft, .. } => {
+            if *nft {
+                vec!["nft".to_string()]
+            } else {
+                Vec::new()
+            }
+        }
+        Activity::Cyclictest { .. } => vec!["cyclictest".to_string()],
+        Activity::Schbench { .. } => vec!["schbench".to_string()],
+        Activity::Smart { devices } => {
+            let mut tools = Vec::new();
+            if devices.iter().any(|d| d.contains("nvme")) {
+                tools.push("nvme".to_string());
+            }
+            if devices.iter().any(|d| !d.contains("nvme")) {
+                tools.push("smartctl".to_string());
+            }
+            tools
+        }
+        Activity::Jvm { jfr, .. } => {
+            let mut tools = vec!["jstat".to_string(), "pgrep".to_string()];
+            if *jfr {
+                tools.push("jcmd".to_string());
+            }
+            tools
+        }
+        Activity::Ethtool { .. } => vec!["ethtool".to_string()],
+        Activity::Sysctl { .. } => vec!["sysctl".to_string()],
+        Activity::Fio { .. } => vec!["fio".to_string()],
+        Activity::Launch { cmd, .. } => cmd.first().cloned().into_iter().collect(),
+        Activity::Snapshot { cmd, .. } => cmd
+            .split_whitespace()
+            .next()
+            .map(str::to_string)
+            .into_iter()
+            .collect(),
+        Activity::Fixture { tmpfs, loopdev } => {
+            let mut tools = Vec::new();
+            if !tmpfs.is_empty() {
+                tools.push("mount".to_string());
+            }
+            if !loopdev.is_empty() {
+                tools.extend(["fallocate".to_string(), "losetup".to_string()]);
+            }
+            tools
+        }
+        Activity::PrepareFs { create, trim, .. } => {
+            let mut tools = Vec::new();
+            if !create.is_empty() {
+                tools.push("fallocate".to_string());
+            }
+            if trim.is_some() {
+                tools.push("fstrim".to_string());
+            }
+            tools
+        }
+        Activity::Parallel(entries) => entries.iter().flat_map(required_tools).collect(),
+        Activity::Meminfo { .. }
+        | Activity::Netdev { .. }
+        | Activity::Interrupts { .. }
+        | Activity::Pressure { .. }
+        | Activity::Cpufreq { .. }
+        | Activity::Nfs { .. }
+        | Activity::IrqAffinity { .. }
+        | Activity::Mark { .. }
+        | Activity::Barrier {}
+        | Activity::Poll { .. } => Vec::new(),
+    }
+}
+
+/// Script dumping the health of the given devices into `out`, with the
+/// `--- smart:<device>` section markers the smart plotter reads.
+/// smartctl encodes attribute states in its exit status, so a nonzero
+/// exit must not abort the snapshot.
+fn smart_script(devices: &[String], out: &str) -> String {
+    let mut script = format!("set -e\n: > {out}\n");
+    for dev in devices {
+        script.push_str(&format!(
+            "echo '--- smart:{dev}' >> {out}\n\
+             case '{dev}' in\n\
+             *nvme*) nvme smart-log '{dev}' >> {out} || true ;;\n\
+             *) smartctl -a '{dev}' >> {out} || true ;;\n\
+             esac\n"
+        ));
+    }
+    script
+}
+
+fn strvec(parts: &[&str]) -> Vec<String> {
+    parts.iter().map(|s| s.to_string()).collect()
+}
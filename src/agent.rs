@@ -0,0 +1,679 @@
+//! The agent: executes controller requests on the machine under test.
+//!
+//! Every connection gets its own numbered session directory under the agent
+//! output root. Activity output files are named by activity id, and the
+//! `out.map` file maps ids back to activity names for the plotter.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use regex::Regex;
+
+use crate::common::{create_next_numeric_dir_in, now_millis, readfile};
+use crate::proto::{self, ActivityId, Request, Response, PROTO_VERSION};
+
+/// Request execution interface, implemented by the real [`Agent`].
+pub trait AgentOps {
+    fn handle(&mut self, req: Request) -> Response;
+}
+
+/// Format the agent converts recognized outputs into at collect time,
+/// replacing the raw logs (`--pre-export`).
+#[cfg(feature = "plotter")]
+static PRE_EXPORT: std::sync::Mutex<Option<crate::export::Format>> = std::sync::Mutex::new(None);
+
+/// Make every collect parse recognized outputs into export tables and
+/// drop the converted raw logs, process-wide. Shrinks the collected
+/// archives and spares the controller from parsing huge captures; only
+/// agents built with the plotter feature carry the parsers.
+#[cfg(feature = "plotter")]
+pub fn set_pre_export(format: crate::export::Format) {
+    *PRE_EXPORT.lock().unwrap() = Some(format);
+}
+
+struct PollHandle {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+enum Running {
+    Poll(PollHandle),
+    Child(Child),
+}
+
+/// One agent session bound to a numbered output directory.
+pub struct Agent {
+    root: PathBuf,
+    outdir: PathBuf,
+    used_ids: HashSet<ActivityId>,
+    running: HashMap<ActivityId, Running>,
+}
+
+impl Agent {
+    /// Create a fresh session under `root`.
+    pub fn new(root: &Path) -> io::Result<Agent> {
+        let outdir = create_next_numeric_dir_in(root)?;
+        Ok(Agent {
+            root: root.to_path_buf(),
+            outdir,
+            used_ids: HashSet::new(),
+            running: HashMap::new(),
+        })
+    }
+
+    /// The session output directory.
+    pub fn outdir(&self) -> &Path {
+        &self.outdir
+    }
+
+    /// Validate a controller-proposed activity id and record it in
+    /// `out.map` and the journal. Ids name output files, so only a safe
+    /// character set is accepted, and reuse within a session is an error.
+    fn register_id(&mut self, id: &str, name: &str) -> io::Result<()> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid activity id '{id}'"),
+            ));
+        }
+        if !self.used_ids.insert(id.to_string()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("activity id '{id}' already used in this session"),
+            ));
+        }
+
+        let mut map = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.outdir.join("out.map"))?;
+        writeln!(map, "{id} {name}")?;
+        self.journal(&format!("start {id} {name}"))
+    }
+
+    fn journal(&self, line: &str) -> io::Result<()> {
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.outdir.join("journal.log"))?;
+        writeln!(journal, "{} {line}", now_millis())
+    }
+
+    fn check(&self, tools: &[String]) -> Response {
+        let missing_tools = tools
+            .iter()
+            .filter(|tool| !tool_available(tool))
+            .cloned()
+            .collect();
+
+        let probe = self.outdir.join(".writable");
+        let outdir_writable = File::create(&probe).is_ok();
+        let _ = std::fs::remove_file(&probe);
+
+        let uname = Command::new("uname")
+            .arg("-srm")
+            .output()
+            .ok()
+            .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+            .unwrap_or_default();
+        let mem_total_kb = readfile(Path::new("/proc/meminfo"))
+            .ok()
+            .and_then(|text| {
+                let total = text.lines().find_map(|l| l.strip_prefix("MemTotal:"))?;
+                total.split_whitespace().next()?.parse().ok()
+            })
+            .unwrap_or(0);
+
+        Response::Checked {
+            missing_tools,
+            outdir_writable,
+            agent_millis: now_millis(),
+            uname,
+            cpus: std::thread::available_parallelism().map_or(0, |n| n.get()),
+            mem_total_kb,
+        }
+    }
+
+    fn start_poll(
+        &mut self,
+        id: &str,
+        name: &str,
+        period_ms: u64,
+        paths: Vec<String>,
+        binary: bool,
+    ) -> io::Result<()> {
+        self.register_id(id, name)?;
+        let mut log = File::create(self.outdir.join(format!("{id}-poll.log")))?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+
+        let thread = if binary {
+            let mut writer = crate::polllog::Writer::new(log)?;
+            std::thread::spawn(move || {
+                while !stop_flag.load(Ordering::Relaxed) {
+                    let files: Vec<(String, String)> = paths
+                        .iter()
+                        .map(|path| {
+                            let content = readfile(Path::new(path)).unwrap_or_default();
+                            (path.clone(), content)
+                        })
+                        .collect();
+                    let _ = writer.write_sample(now_millis(), &files);
+                    std::thread::sleep(Duration::from_millis(period_ms));
+                }
+            })
+        } else {
+            std::thread::spawn(move || {
+                while !stop_flag.load(Ordering::Relaxed) {
+                    let _ = writeln!(log, "=== {}", now_millis());
+                    for path in &paths {
+                        let content = readfile(Path::new(path)).unwrap_or_default();
+                        let _ = writeln!(log, "--- {path}");
+                        let _ = log.write_all(content.as_bytes());
+                    }
+                    std::thread::sleep(Duration::from_millis(period_ms));
+                }
+            })
+        };
+
+        self.running
+            .insert(id.to_string(), Running::Poll(PollHandle { stop, thread }));
+        Ok(())
+    }
+
+    fn spawn_bg(&mut self, id: &str, name: &str, cmd: &[String]) -> io::Result<()> {
+        self.register_id(id, name)?;
+        // The parser rejects empty launch commands, but the agent must
+        // not trust the controller: an empty cmd would panic here.
+        let exe = cmd
+            .first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+        let stdout = File::create(self.outdir.join(format!("{id}-out.log")))?;
+        let stderr = File::create(self.outdir.join(format!("{id}-err.log")))?;
+        let child = Command::new(exe)
+            .args(&cmd[1..])
+            .current_dir(&self.outdir)
+            .stdin(Stdio::null())
+            .stdout(stdout)
+            .stderr(stderr)
+            .spawn()?;
+        self.running.insert(id.to_string(), Running::Child(child));
+        Ok(())
+    }
+
+    fn spawn_fg(&mut self, id: &str, name: &str, cmd: &[String]) -> io::Result<(i32, Vec<u8>, Vec<u8>)> {
+        self.register_id(id, name)?;
+        let exe = cmd
+            .first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+        let output = Command::new(exe)
+            .args(&cmd[1..])
+            .current_dir(&self.outdir)
+            .stdin(Stdio::null())
+            .output()?;
+        let status = output.status.code().unwrap_or(-1);
+        self.journal(&format!("stop {id} {status}"))?;
+        Ok((status, output.stdout, output.stderr))
+    }
+
+    fn stop_one(&mut self, id: &str, running: Running) -> io::Result<()> {
+        let status = match running {
+            Running::Poll(handle) => {
+                handle.stop.store(true, Ordering::Relaxed);
+                let _ = handle.thread.join();
+                0
+            }
+            Running::Child(mut child) => {
+                child.kill()?;
+                child.wait()?.code().unwrap_or(-1)
+            }
+        };
+        self.journal(&format!("stop {id} {status}"))
+    }
+
+    fn stop(&mut self, id: &str) -> io::Result<()> {
+        match self.running.remove(id) {
+            Some(running) => self.stop_one(id, running),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no running activity '{id}'"),
+            )),
+        }
+    }
+
+    fn stop_all(&mut self) -> io::Result<()> {
+        let ids: Vec<ActivityId> = self.running.keys().cloned().collect();
+        for id in ids {
+            self.stop(&id)?;
+        }
+        Ok(())
+    }
+
+    /// Stop everything and pack the session output directory, returning
+    /// the archive path. The bytes are not read here: the session loop
+    /// streams the file in bounded chunks, so agent memory stays flat
+    /// however large the collected artifacts are.
+    pub fn collect(&mut self) -> io::Result<PathBuf> {
+        self.stop_all()?;
+
+        #[cfg(feature = "plotter")]
+        if let Some(format) = *PRE_EXPORT.lock().unwrap() {
+            let converted = crate::export::pre_export_dir(&self.outdir, format)?;
+            if converted > 0 {
+                eprintln!("agent: pre-exported {converted} activities");
+            }
+        }
+
+        // Archive relative to the parent of the output root so the entries
+        // carry a stable two-component prefix.
+        let parent = self.root.parent().unwrap_or(Path::new("/"));
+        let rel = self
+            .outdir
+            .strip_prefix(parent)
+            .expect("outdir under root")
+            .to_path_buf();
+        let archive = self.outdir.join("out.tgz");
+        let status = Command::new("tar")
+            .arg("czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(parent)
+            .arg("--exclude=out.tgz")
+            .arg(&rel)
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("tar failed: {status}")));
+        }
+        Ok(archive)
+    }
+}
+
+impl AgentOps for Agent {
+    fn handle(&mut self, req: Request) -> Response {
+        let result = match req {
+            Request::Version => {
+                return Response::Version {
+                    version: PROTO_VERSION,
+                }
+            }
+            Request::Check { tools } => return self.check(&tools),
+            Request::Poll {
+                id,
+                name,
+                period_ms,
+                paths,
+                binary,
+            } => self
+                .start_poll(&id, &name, period_ms, paths, binary)
+                .map(|()| Response::Started { id }),
+            Request::SpawnBg { id, name, cmd } => self
+                .spawn_bg(&id, &name, &cmd)
+                .map(|()| Response::Started { id }),
+            Request::SpawnFg { id, name, cmd } => {
+                self.spawn_fg(&id, &name, &cmd)
+                    .map(|(status, stdout, stderr)| Response::Finished {
+                        status,
+                        stdout,
+                        stderr,
+                    })
+            }
+            Request::Stop { id } => self.stop(&id).map(|()| Response::Stopped { id }),
+            Request::StopAll => self.stop_all().map(|()| Response::AllStopped),
+            // The deadline is on this agent's clock: the controller folds
+            // the offset measured at pre-flight into the timestamp.
+            Request::WaitUntil { millis } => {
+                let now = now_millis();
+                if millis > now {
+                    std::thread::sleep(Duration::from_millis(millis - now));
+                }
+                Ok(Response::Resumed)
+            }
+            // Transfer replies are chunk streams and cannot fit the
+            // one-response shape of this method; every session loop
+            // handles them before getting here.
+            Request::Fetch { .. } | Request::Collect => {
+                Err(io::Error::other("transfer requests are streamed by the session loop"))
+            }
+        };
+        result.unwrap_or_else(|e| Response::Error {
+            message: e.to_string(),
+        })
+    }
+}
+
+/// Largest chunk of a streamed Fetch/Collect reply: the agent holds at
+/// most this much file data in memory at a time.
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+/// Chunk responses streaming one file, read lazily so only the chunk
+/// being sent is resident. A file that cannot be opened or read yields
+/// a single [`Response::Error`], which the controller surfaces as an
+/// agent error instead of silently truncated data.
+pub struct FileChunks {
+    file: Option<io::Result<File>>,
+}
+
+impl FileChunks {
+    pub fn new(path: &Path) -> FileChunks {
+        FileChunks {
+            file: Some(File::open(path)),
+        }
+    }
+}
+
+impl Iterator for FileChunks {
+    type Item = Response;
+
+    fn next(&mut self) -> Option<Response> {
+        use std::io::Read;
+
+        let file = match self.file.as_mut()? {
+            Ok(file) => file,
+            Err(e) => {
+                let message = e.to_string();
+                self.file = None;
+                return Some(Response::Error { message });
+            }
+        };
+        let mut data = vec![0u8; CHUNK_SIZE];
+        match file.read(&mut data) {
+            Ok(0) => {
+                self.file = None;
+                Some(Response::Chunk {
+                    data: Vec::new(),
+                    last: true,
+                })
+            }
+            Ok(n) => {
+                data.truncate(n);
+                Some(Response::Chunk { data, last: false })
+            }
+            Err(e) => {
+                self.file = None;
+                Some(Response::Error {
+                    message: e.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Check whether an executable is reachable through PATH.
+fn tool_available(tool: &str) -> bool {
+    if tool.contains('/') {
+        return Path::new(tool).exists();
+    }
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| dir.join(tool).exists())
+}
+
+/// TCP transport serving msgpack-encoded requests, one session per
+/// connection.
+pub struct TcpMsgpackProtocol {
+    listener: TcpListener,
+}
+
+impl TcpMsgpackProtocol {
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        Ok(TcpMsgpackProtocol {
+            listener: TcpListener::bind(addr)?,
+        })
+    }
+
+    /// The actually bound address (relevant when binding to port 0).
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept connections forever, running each session to completion.
+    pub fn serve(&self, root: &Path) -> io::Result<()> {
+        self.serve_with(root, None, None)
+    }
+
+    /// Like [`Self::serve`], but optionally exit after `max_sessions`
+    /// sessions and reject spawned commands whose executable does not
+    /// match `allow`.
+    pub fn serve_with(
+        &self,
+        root: &Path,
+        max_sessions: Option<u64>,
+        allow: Option<&Regex>,
+    ) -> io::Result<()> {
+        let mut served = 0;
+        while max_sessions.is_none_or(|max| served < max) {
+            let (stream, peer) = self.listener.accept()?;
+            eprintln!("agent: session from {peer}");
+            if let Err(e) = serve_session(stream, root, allow) {
+                eprintln!("agent: session from {peer} failed: {e}");
+            }
+            served += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Check a request against the command allow policy. Only spawned
+/// commands are policed: the builtin poll/stop/collect machinery does not
+/// execute arbitrary executables.
+fn allowed(req: &Request, allow: Option<&Regex>) -> bool {
+    let Some(allow) = allow else { return true };
+    match req {
+        Request::SpawnBg { cmd, .. } | Request::SpawnFg { cmd, .. } => {
+            cmd.first().is_some_and(|exe| allow.is_match(exe))
+        }
+        _ => true,
+    }
+}
+
+fn serve_session(mut stream: TcpStream, root: &Path, allow: Option<&Regex>) -> io::Result<()> {
+    let mut agent = Agent::new(root)?;
+    // Whatever ends the session — a clean controller hangup or a
+    // protocol error — running children must not outlive it.
+    let result = session_requests(&mut stream, &mut agent, allow);
+    let _ = agent.stop_all();
+    result
+}
+
+fn session_requests(
+    stream: &mut TcpStream,
+    agent: &mut Agent,
+    allow: Option<&Regex>,
+) -> io::Result<()> {
+    loop {
+        let req: Request = match proto::recv_msg(stream) {
+            Ok(req) => req,
+            // Controller hung up: session is over.
+            Err(proto::ProtoError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Ok(());
+            }
+            Err(e) => return Err(io::Error::other(e.to_string())),
+        };
+        let resp = if !allowed(&req, allow) {
+            Response::Error {
+                message: "command rejected by the --allow policy".to_string(),
+            }
+        } else {
+            match req {
+                // Transfers are streamed straight from disk to socket in
+                // bounded chunks instead of passing through one message.
+                Request::Fetch { path } => {
+                    for resp in FileChunks::new(Path::new(&path)) {
+                        proto::send_msg(stream, &resp)
+                            .map_err(|e| io::Error::other(e.to_string()))?;
+                    }
+                    continue;
+                }
+                Request::Collect => match agent.collect() {
+                    Ok(archive) => {
+                        for resp in FileChunks::new(&archive) {
+                            proto::send_msg(stream, &resp)
+                                .map_err(|e| io::Error::other(e.to_string()))?;
+                        }
+                        continue;
+                    }
+                    Err(e) => Response::Error {
+                        message: e.to_string(),
+                    },
+                },
+                req => agent.handle(req),
+            }
+        };
+        proto::send_msg(stream, &resp).map_err(|e| io::Error::other(e.to_string()))?;
+    }
+}
+
+/// Retention policy for the numbered session directories under the agent
+/// output root.
+pub struct Retention {
+    /// Keep at most this many sessions.
+    pub max_sessions: Option<usize>,
+    /// Keep at most this many bytes of session data in total.
+    pub max_bytes: Option<u64>,
+}
+
+/// Prune the oldest numbered session directories under `root` until the
+/// remaining ones fit the policy, returning how many were removed.
+/// Long-lived agents on small root filesystems otherwise accumulate
+/// session data until the disk fills; this runs once at agent startup.
+pub fn prune_sessions(root: &Path, retention: &Retention) -> io::Result<usize> {
+    if retention.max_sessions.is_none() && retention.max_bytes.is_none() {
+        return Ok(0);
+    }
+    let mut sessions = Vec::new();
+    match std::fs::read_dir(root) {
+        Ok(entries) => {
+            for entry in entries {
+                let entry = entry?;
+                let Some(number) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok())
+                else {
+                    continue;
+                };
+                if entry.file_type()?.is_dir() {
+                    sessions.push((number, entry.path()));
+                }
+            }
+        }
+        // A root not created yet holds nothing to prune.
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    }
+    sessions.sort_by_key(|(number, _)| *number);
+
+    let sizes: Vec<u64> = sessions
+        .iter()
+        .map(|(_, dir)| dir_size(dir))
+        .collect::<io::Result<_>>()?;
+    let mut total: u64 = sizes.iter().sum();
+    let mut kept = sessions.len();
+    let mut removed = 0;
+    for ((_, dir), size) in sessions.iter().zip(&sizes) {
+        let over_count = retention.max_sessions.is_some_and(|max| kept > max);
+        let over_bytes = retention.max_bytes.is_some_and(|max| total > max);
+        if !over_count && !over_bytes {
+            break;
+        }
+        std::fs::remove_dir_all(dir)?;
+        eprintln!("agent: pruned old session {}", dir.display());
+        kept -= 1;
+        total -= size;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Total size of the files under a directory, recursively.
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            total += dir_size(&entry.path())?;
+        } else {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+/// Self-hosted mode: execute JSON requests from stdin locally, one per
+/// line, printing JSON responses to stdout. Collect leaves the archive in
+/// the session directory instead of sending it anywhere.
+pub fn selfhosted(root: &Path) -> io::Result<()> {
+    let mut agent = Agent::new(root)?;
+    eprintln!("agent: selfhosted session in {}", agent.outdir().display());
+
+    for line in io::stdin().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let resp = match serde_json::from_str::<Request>(&line) {
+            // Do not dump archive bytes to the terminal: the archive
+            // stays in the session directory anyway.
+            Ok(Request::Collect) => match agent.collect() {
+                Ok(_) => Response::Chunk {
+                    data: vec![],
+                    last: true,
+                },
+                Err(e) => Response::Error {
+                    message: e.to_string(),
+                },
+            },
+            Ok(Request::Fetch { path }) => {
+                for resp in FileChunks::new(Path::new(&path)) {
+                    println!("{}", serde_json::to_string(&resp)?);
+                }
+                continue;
+            }
+            Ok(req) => agent.handle(req),
+            Err(e) => Response::Error {
+                message: format!("bad request: {e}"),
+            },
+        };
+        println!("{}", serde_json::to_string(&resp)?);
+    }
+    agent.stop_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_sessions_are_pruned_to_the_quota() {
+        let root = std::env::temp_dir().join(format!("pmppt-prune-{}", std::process::id()));
+        for session in ["000", "001", "002"] {
+            std::fs::create_dir_all(root.join(session)).unwrap();
+            std::fs::write(root.join(session).join("out.log"), [0u8; 100]).unwrap();
+        }
+
+        let unlimited = Retention { max_sessions: None, max_bytes: None };
+        assert_eq!(prune_sessions(&root, &unlimited).unwrap(), 0);
+
+        let keep_two = Retention { max_sessions: Some(2), max_bytes: None };
+        assert_eq!(prune_sessions(&root, &keep_two).unwrap(), 1);
+        assert!(!root.join("000").exists());
+        assert!(root.join("001").exists());
+
+        let fit_bytes = Retention { max_sessions: None, max_bytes: Some(150) };
+        assert_eq!(prune_sessions(&root, &fit_bytes).unwrap(), 1);
+        assert!(!root.join("001").exists());
+        assert!(root.join("002").exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
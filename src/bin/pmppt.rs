@@ -0,0 +1,147 @@
+//! The unified PMPPT binary.
+//!
+//! One file to deploy everywhere: agent, controller and plotter commands
+//! are mounted as subcommands, so scripting is uniform across hosts.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{CommandFactory, Parser, Subcommand};
+use pmppt::cli;
+
+/// Poor Man's Performance Profiler Tool.
+#[derive(Parser)]
+#[command(name = "pmppt", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    #[command(flatten)]
+    options: cli::plotter::Options,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Agent-side commands.
+    #[command(subcommand)]
+    Agent(cli::agent::Command),
+    /// Execute a scenario as the controller.
+    Run {
+        /// Scenario configuration file.
+        config: PathBuf,
+        /// Root directory for the numbered run directories.
+        #[arg(long, value_name = "DIR", default_value = "pmppt-out")]
+        output_dir: PathBuf,
+        /// Render the HTML report right after the run.
+        #[arg(long)]
+        plot: bool,
+        /// Open the rendered report in the browser (implies --plot).
+        #[arg(long)]
+        open: bool,
+        /// Serve a live metrics dashboard on this address during the run.
+        #[arg(long, value_name = "ADDR")]
+        live: Option<String>,
+    },
+    /// Run every scenario in a directory, with a combined report index.
+    Batch {
+        /// Directory with .yaml scenario configurations.
+        configs: PathBuf,
+        /// Root directory for the numbered run directories.
+        #[arg(long, value_name = "DIR", default_value = "pmppt-out")]
+        output_dir: PathBuf,
+        /// Run up to N configs with disjoint agent sets concurrently.
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        jobs: usize,
+    },
+    /// Parse and validate a scenario configuration without running it.
+    Validate {
+        /// Scenario configuration file.
+        config: PathBuf,
+    },
+    /// Open an interactive shell on one agent.
+    Shell {
+        /// Agent address (host:port).
+        agent: String,
+    },
+    /// Gate a candidate run against a baseline with a rules file.
+    Check {
+        /// Baseline run directory.
+        #[arg(long, value_name = "RUN")]
+        baseline: PathBuf,
+        /// Candidate run directory.
+        #[arg(long, value_name = "RUN")]
+        candidate: PathBuf,
+        /// YAML rules file with the thresholds.
+        #[arg(long, value_name = "FILE")]
+        rules: PathBuf,
+    },
+    /// Prune old numbered run or session directories, keeping metadata.
+    Gc {
+        /// Directory with the numbered output directories.
+        root: PathBuf,
+        /// Never prune the newest N directories.
+        #[arg(long, value_name = "N")]
+        keep_last: Option<usize>,
+        /// Only prune directories older than this (e.g. 30d, 12h).
+        #[arg(long, value_name = "AGE", value_parser = cli::gc::parse_age)]
+        older_than: Option<std::time::Duration>,
+    },
+    /// Serve the controller HTTP API for programmatic run submission.
+    Serve {
+        /// Address to listen on.
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+        listen: String,
+        /// Root directory for the numbered run directories.
+        #[arg(long, value_name = "DIR", default_value = "pmppt-out")]
+        output_dir: PathBuf,
+    },
+    /// Plotter commands (plot, report, summary, timeline, compare, ...).
+    #[command(flatten)]
+    Plotter(cli::plotter::Mode),
+}
+
+fn main() -> ExitCode {
+    let parsed = Cli::parse();
+    match parsed.command {
+        Command::Agent(command) => cli::agent::run(command, Cli::command()),
+        Command::Run {
+            config,
+            output_dir,
+            plot,
+            open,
+            live,
+        } => {
+            if let Some(addr) = live {
+                pmppt::live::set_addr(addr);
+            }
+            cli::controller::run_scenario(&config, &output_dir, plot || open, open)
+        }
+        Command::Batch {
+            configs,
+            output_dir,
+            jobs,
+        } => cli::controller::batch(&configs, &output_dir, jobs),
+        Command::Validate { config } => cli::controller::validate(&config),
+        Command::Shell { agent } => cli::shell::run(&agent),
+        Command::Check {
+            baseline,
+            candidate,
+            rules,
+        } => cli::check::run(&baseline, &candidate, &rules),
+        Command::Gc {
+            root,
+            keep_last,
+            older_than,
+        } => cli::gc::run(&root, keep_last, older_than),
+        Command::Serve { listen, output_dir } => {
+            match pmppt::serve::serve(&listen, &output_dir) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("serve: {e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Plotter(mode) => cli::plotter::run(mode, parsed.options, Cli::command()),
+    }
+}
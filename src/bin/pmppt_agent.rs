@@ -0,0 +1,19 @@
+//! PMPPT agent binary, a thin wrapper over [`pmppt::cli::agent`].
+
+use std::process::ExitCode;
+
+use clap::{CommandFactory, Parser};
+use pmppt::cli;
+
+/// Executes controller requests on the machine under test.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: cli::agent::Command,
+}
+
+fn main() -> ExitCode {
+    let parsed = Cli::parse();
+    cli::agent::run(parsed.command, Cli::command())
+}
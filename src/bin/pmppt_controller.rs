@@ -0,0 +1,60 @@
+//! PMPPT controller binary, a thin wrapper over [`pmppt::cli::controller`].
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use pmppt::cli;
+
+/// Drives a profiling run over the configured agents.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Scenario configuration file.
+    #[arg(required_unless_present = "completions")]
+    config: Option<PathBuf>,
+    /// Root directory for the numbered run directories.
+    #[arg(long, value_name = "DIR", default_value = "pmppt-out")]
+    output_dir: PathBuf,
+    /// Render the HTML report right after the run.
+    #[arg(long)]
+    plot: bool,
+    /// Open the rendered report in the browser (implies --plot).
+    #[arg(long)]
+    open: bool,
+    /// Serve a live metrics dashboard on this address during the run.
+    #[arg(long, value_name = "ADDR")]
+    live: Option<String>,
+    /// Only parse and validate the configuration, without running.
+    #[arg(long)]
+    validate: bool,
+    /// Generate a shell completion script to stdout instead of running.
+    #[arg(long, value_name = "SHELL", conflicts_with = "config")]
+    completions: Option<Shell>,
+}
+
+fn main() -> ExitCode {
+    let parsed = Cli::parse();
+    if let Some(shell) = parsed.completions {
+        let mut cmd = Cli::command();
+        let name = cmd.get_name().to_string();
+        clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
+        return ExitCode::SUCCESS;
+    }
+
+    let config = parsed.config.expect("required by clap");
+    if let Some(addr) = parsed.live {
+        pmppt::live::set_addr(addr);
+    }
+    if parsed.validate {
+        cli::controller::validate(&config)
+    } else {
+        cli::controller::run_scenario(
+            &config,
+            &parsed.output_dir,
+            parsed.plot || parsed.open,
+            parsed.open,
+        )
+    }
+}
@@ -0,0 +1,22 @@
+//! PMPPT plotter binary, a thin wrapper over [`pmppt::cli::plotter`].
+
+use std::process::ExitCode;
+
+use clap::{CommandFactory, Parser};
+use pmppt::cli::plotter;
+
+/// Render HTML reports from collected pmppt output.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    mode: plotter::Mode,
+
+    #[command(flatten)]
+    options: plotter::Options,
+}
+
+fn main() -> ExitCode {
+    let parsed = Cli::parse();
+    plotter::run(parsed.mode, parsed.options, Cli::command())
+}
@@ -0,0 +1,862 @@
+//! YAML scenario configuration parsing.
+//!
+//! A scenario consists of a `setup` section describing the agents and a
+//! list of `stages`, each holding per-agent activity chains:
+//!
+//! ```yaml
+//! setup:
+//!   agents:
+//!     - name: server
+//!       addr: "192.168.1.5:50051"
+//! stages:
+//!   - name: load
+//!     duration: 60
+//!     chains:
+//!       server:
+//!         - mpstat: {}
+//!         - meminfo: {}
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub setup: Setup,
+    pub stages: Vec<Stage>,
+    /// The original YAML text, embedded into the run metadata so a report
+    /// always shows exactly what was run.
+    #[serde(skip)]
+    pub raw: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Setup {
+    pub agents: Vec<AgentCfg>,
+    /// Random seed handed to the load generators (fio `--randseed`).
+    /// Generated per run when absent; recorded in the run metadata
+    /// either way, so a run can be replayed with the same I/O pattern.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Where to announce run completion, if anywhere.
+    #[serde(default)]
+    pub notify: Option<NotifyCfg>,
+    /// Where to upload the results after the run, if anywhere.
+    #[serde(default)]
+    pub upload: Option<UploadCfg>,
+    /// On-disk format of the agent poll logs. `binary` selects the
+    /// compact length-prefixed format, cutting log size and parse time
+    /// for high-frequency runs; the plotters read both.
+    #[serde(default)]
+    pub poll_format: PollFormat,
+    /// Upper bound on chain workers running at once during a stage.
+    /// Defaults to one worker per chain, the minimum start skew; lower
+    /// it when dozens of agents would mean as many controller threads.
+    /// Stages with barriers need every chain running at once and reject
+    /// a smaller bound.
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    /// Label identifying this run in the output naming templates below,
+    /// expanded as `{run_label}`. Defaults to the output directory name.
+    #[serde(default)]
+    pub run_label: Option<String>,
+    /// Template of the per-agent output directory, relative to the run
+    /// output directory; `{agent}`, `{run_label}` and `{seed}` expand.
+    /// Defaults to `{agent}`, the layout the report pipeline discovers
+    /// on its own — custom layouts fit existing lab conventions but are
+    /// plotted per directory.
+    #[serde(default)]
+    pub agent_dir: Option<String>,
+    /// Template of the collected archive file name inside the agent
+    /// directory, same placeholders. Defaults to `out.tgz`.
+    #[serde(default)]
+    pub archive_name: Option<String>,
+}
+
+/// Format the agents write their poll logs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PollFormat {
+    /// Human-readable `===`/`---` marker lines, the default.
+    #[default]
+    Text,
+    /// The compact framed format of the `polllog` module.
+    Binary,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UploadCfg {
+    /// Destination bucket.
+    pub bucket: String,
+    /// Key prefix inside the bucket; the run name is appended to it.
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Endpoint URL for S3-compatible stores other than AWS itself.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotifyCfg {
+    /// URL the notification is POSTed to (a Slack or generic webhook).
+    pub url: String,
+    /// Message template. `{status}`, `{run}` and `{summary}` are
+    /// substituted, JSON-escaped so they can sit inside a JSON payload.
+    /// Defaults to a Slack-style `{"text": ...}` body.
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentCfg {
+    pub name: String,
+    /// Address of an externally started agent. Not needed for local agents.
+    #[serde(default)]
+    pub addr: Option<String>,
+    /// Run an in-process agent on the controller host automatically.
+    /// Useful for single-machine profiling.
+    #[serde(default)]
+    pub local: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stage {
+    pub name: String,
+    /// How long to keep the stage running after the chains are started, in
+    /// seconds. Without it the stage length is defined by its foreground
+    /// activities.
+    #[serde(default)]
+    pub duration: Option<u64>,
+    /// Shell commands run on the controller host before the chains start.
+    #[serde(default)]
+    pub pre: Vec<String>,
+    /// Shell commands run on the controller host after the stage ends.
+    #[serde(default)]
+    pub post: Vec<String>,
+    /// Per-agent activity chains, keyed by agent name.
+    #[serde(default, with = "serde_yaml::with::singleton_map_recursive")]
+    pub chains: BTreeMap<String, Vec<Activity>>,
+}
+
+fn default_period() -> u64 {
+    1
+}
+
+fn default_period_ms() -> u64 {
+    1000
+}
+
+fn default_memcached_addr() -> String {
+    "127.0.0.1:11211".to_string()
+}
+
+/// One entry of an activity chain.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Activity {
+    /// Per-CPU statistics via `mpstat -P ALL`.
+    Mpstat {
+        #[serde(default = "default_period")]
+        period: u64,
+    },
+    /// Extended device statistics via `iostat -x`.
+    Iostat {
+        #[serde(default = "default_period")]
+        period: u64,
+    },
+    /// Combined system activity report via `sar -A`.
+    Sar {
+        #[serde(default = "default_period")]
+        period: u64,
+    },
+    /// Per-process CPU/memory/IO statistics via `pidstat`.
+    Pidstat {
+        #[serde(default = "default_period")]
+        period: u64,
+    },
+    /// Scheduler/VM statistics via `vmstat -t`.
+    Vmstat {
+        #[serde(default = "default_period")]
+        period: u64,
+    },
+    /// Poll `/proc/meminfo`.
+    Meminfo {
+        #[serde(default = "default_period_ms")]
+        period_ms: u64,
+    },
+    /// Poll `/proc/net/dev`.
+    Netdev {
+        #[serde(default = "default_period_ms")]
+        period_ms: u64,
+    },
+    /// Sample PostgreSQL statistics views (`pg_stat_database`,
+    /// `pg_stat_bgwriter`) via `psql` over the local socket.
+    Pgstat {
+        #[serde(default = "default_period")]
+        period: u64,
+        /// Database to connect to; the psql default when absent.
+        #[serde(default)]
+        dbname: Option<String>,
+    },
+    /// Sample MySQL `SHOW GLOBAL STATUS` via the `mysql` client.
+    Mysqlstat {
+        #[serde(default = "default_period")]
+        period: u64,
+    },
+    /// Sample `redis-cli INFO`.
+    Redis {
+        #[serde(default = "default_period")]
+        period: u64,
+        /// Connection URL (`redis://...`); the redis-cli default when absent.
+        #[serde(default)]
+        url: Option<String>,
+    },
+    /// Sample memcached `stats` over a TCP connection.
+    Memcached {
+        #[serde(default = "default_period")]
+        period: u64,
+        #[serde(default = "default_memcached_addr")]
+        addr: String,
+    },
+    /// Sample GC statistics of a JVM via `jstat -gcutil`, resolving the
+    /// target pid with `pgrep -f <pattern>`. With `jfr: true` a JFR
+    /// recording runs for the stage and is dumped into the session
+    /// directory as `jvm.jfr` when the stage ends.
+    Jvm {
+        pattern: String,
+        #[serde(default = "default_period")]
+        period: u64,
+        #[serde(default)]
+        jfr: bool,
+    },
+    /// Sample per-queue NIC counters via `ethtool -S`.
+    Ethtool {
+        iface: String,
+        #[serde(default = "default_period_ms")]
+        period_ms: u64,
+    },
+    /// Poll `/proc/interrupts`.
+    Interrupts {
+        #[serde(default = "default_period_ms")]
+        period_ms: u64,
+    },
+    /// Poll pressure stall information via `/proc/pressure`.
+    Pressure {
+        #[serde(default = "default_period_ms")]
+        period_ms: u64,
+    },
+    /// Storage benchmark hygiene before the measurement starts: pre-create
+    /// test files, `sync` and drop the page cache, `fstrim` a mount point.
+    PrepareFs {
+        /// Drop the page cache after syncing (`echo 3 > drop_caches`).
+        #[serde(default)]
+        drop_caches: bool,
+        /// Files to pre-allocate, path to `fallocate` size (e.g. "10G").
+        #[serde(default)]
+        create: BTreeMap<String, String>,
+        /// Mount point to `fstrim`. Discards unused blocks, so it must be
+        /// confirmed explicitly with `confirm_trim: true`.
+        #[serde(default)]
+        trim: Option<String>,
+        #[serde(default)]
+        confirm_trim: bool,
+    },
+    /// Pin the CPU scaling governor for the stage, optionally disabling
+    /// turbo/boost, restoring the previous state when the stage ends.
+    Cpufreq {
+        governor: String,
+        #[serde(default)]
+        no_turbo: bool,
+    },
+    /// Set kernel tunables for the stage (`net.core.rmem_max: 8388608`),
+    /// restoring the previous values when the stage ends — made for
+    /// parameter sweeps over matrix-expanded configs.
+    Sysctl { settings: BTreeMap<String, String> },
+    /// Poll connection tracking statistics: table occupancy and limit
+    /// plus the per-CPU event counters. With `nft: true` the sampling
+    /// additionally captures `nft list counters`.
+    Conntrack {
+        #[serde(default = "default_period_ms")]
+        period_ms: u64,
+        #[serde(default)]
+        nft: bool,
+    },
+    /// Poll NFS client/server RPC statistics (`/proc/net/rpc/nfs`,
+    /// `/proc/net/rpc/nfsd`) plus the raw per-mount `mountstats`.
+    Nfs {
+        #[serde(default = "default_period_ms")]
+        period_ms: u64,
+    },
+    /// Run cyclictest for `duration` seconds with a latency histogram.
+    /// Runs in the background like a monitor, so give the stage a
+    /// duration at least as long.
+    Cyclictest {
+        duration: u64,
+        /// Extra cyclictest arguments (threads, priority, affinity).
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// Run schbench for `duration` seconds; the percentile table it
+    /// prints on exit is parsed into a dedicated page. Background like
+    /// cyclictest.
+    Schbench {
+        duration: u64,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// Snapshot device health (`nvme smart-log` for NVMe devices,
+    /// `smartctl -a` otherwise) at stage start and end; the report diffs
+    /// the two and flags devices that errored or throttled meanwhile.
+    Smart { devices: Vec<String> },
+    /// Scratch fixtures for the stage, torn down when it ends: tmpfs
+    /// mounts (mount point to size) and loop devices (backing file path
+    /// to size). The configured paths are published as stage artifacts;
+    /// the allocated `/dev/loopN` names are recorded in the
+    /// `fixture-loops` file of the agent session.
+    Fixture {
+        #[serde(default)]
+        tmpfs: BTreeMap<String, String>,
+        #[serde(default)]
+        loopdev: BTreeMap<String, String>,
+    },
+    /// Pin IRQ affinities for the stage. Each key is an IRQ number or a
+    /// device-name pattern matched against `/proc/interrupts`, the value
+    /// is the hex `smp_affinity` mask to apply. Previous masks are
+    /// restored when the stage ends.
+    IrqAffinity { irqs: BTreeMap<String, String> },
+    /// Run fio with the given job arguments, logging bandwidth and
+    /// latency histograms.
+    Fio { args: Vec<String> },
+    /// Launch an arbitrary command.
+    Launch {
+        cmd: Vec<String>,
+        #[serde(default)]
+        mode: LaunchMode,
+        /// Store the command's stdout as a run artifact under this name
+        /// (`fg` mode only), so a printed port, token or device name can
+        /// feed later activities.
+        #[serde(default)]
+        artifact: Option<String>,
+        /// Narrow the stored artifact to the first capture group of this
+        /// regex instead of the whole stdout.
+        #[serde(default)]
+        extract: Option<String>,
+    },
+    /// Record a named timestamp on the controller, e.g. to delimit the
+    /// measurement window inside a stage.
+    Mark { name: String },
+    /// Wait until every agent chain of the stage has reached its barrier,
+    /// then resume all of them at one agreed absolute timestamp, slept
+    /// out on each agent's own clock using the offsets measured at
+    /// pre-flight, so neither per-agent request latency nor clock skew
+    /// shifts the activities that follow. Every chain of the stage must
+    /// contain the same number of barriers.
+    Barrier {},
+    /// Start all grouped entries at once: the start requests are
+    /// pipelined so a high-latency link pays one round trip instead of
+    /// one per entry.
+    Parallel(Vec<Activity>),
+    /// Poll arbitrary files.
+    Poll {
+        #[serde(default = "default_period_ms")]
+        period_ms: u64,
+        paths: Vec<String>,
+    },
+    /// Run an arbitrary command every period, appending its timestamped
+    /// output in the poll-log format — the command counterpart of
+    /// [`Activity::Poll`] for data only a tool can produce (`nvidia-smi`,
+    /// `zpool iostat`, a custom CLI).
+    Snapshot {
+        cmd: String,
+        #[serde(default = "default_period")]
+        period: u64,
+    },
+}
+
+impl Activity {
+    /// Short name of the activity used for output file mapping.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Activity::Mpstat { .. } => "mpstat",
+            Activity::Iostat { .. } => "iostat",
+            Activity::Sar { .. } => "sar",
+            Activity::Pidstat { .. } => "pidstat",
+            Activity::Vmstat { .. } => "vmstat",
+            Activity::Meminfo { .. } => "meminfo",
+            Activity::Netdev { .. } => "netdev",
+            Activity::Pgstat { .. } => "pgstat",
+            Activity::Mysqlstat { .. } => "mysqlstat",
+            Activity::Redis { .. } => "redis",
+            Activity::Memcached { .. } => "memcached",
+            Activity::Jvm { .. } => "jvm",
+            Activity::Ethtool { .. } => "ethtool",
+            Activity::Interrupts { .. } => "interrupts",
+            Activity::Pressure { .. } => "pressure",
+            Activity::PrepareFs { .. } => "prepare_fs",
+            Activity::Cpufreq { .. } => "cpufreq",
+            Activity::Sysctl { .. } => "sysctl",
+            Activity::Conntrack { .. } => "conntrack",
+            Activity::Nfs { .. } => "nfs",
+            Activity::Cyclictest { .. } => "cyclictest",
+            Activity::Schbench { .. } => "schbench",
+            Activity::Smart { .. } => "smart",
+            Activity::Fixture { .. } => "fixture",
+            Activity::IrqAffinity { .. } => "irq_affinity",
+            Activity::Fio { .. } => "fio",
+            Activity::Launch { .. } => "launch",
+            Activity::Mark { .. } => "mark",
+            Activity::Barrier {} => "barrier",
+            Activity::Parallel(_) => "parallel",
+            Activity::Poll { .. } => "poll",
+            Activity::Snapshot { .. } => "snapshot",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchMode {
+    /// Run in background until the stage ends.
+    #[default]
+    Bg,
+    /// Run to completion before the next chain entry starts.
+    Fg,
+}
+
+/// Parse a scenario from YAML text.
+pub fn parse(text: &str) -> Result<Config, serde_yaml::Error> {
+    let mut config: Config = serde_yaml::from_str(text)?;
+    config.raw = text.to_string();
+    for agent in &config.setup.agents {
+        if agent.addr.is_none() && !agent.local {
+            return Err(serde::de::Error::custom(format!(
+                "agent '{}' needs either an addr or local: true",
+                agent.name
+            )));
+        }
+    }
+    // Stage names prefix the activity ids proposed to the agents, so a
+    // duplicate would collide there (and overwrite stage metadata too).
+    let mut stage_names = std::collections::BTreeSet::new();
+    for stage in &config.stages {
+        if !stage_names.insert(&stage.name) {
+            return Err(serde::de::Error::custom(format!(
+                "duplicate stage name '{}'",
+                stage.name
+            )));
+        }
+    }
+    for stage in &config.stages {
+        for chain in stage.chains.values() {
+            for activity in chain {
+                if let Activity::Parallel(entries) = activity {
+                    if entries.iter().any(|e| {
+                        matches!(
+                            e,
+                            Activity::Parallel(_) | Activity::Mark { .. } | Activity::Barrier {}
+                        )
+                    }) {
+                        return Err(serde::de::Error::custom(format!(
+                            "stage '{}': parallel blocks cannot contain marks, barriers or \
+                             nested parallel blocks",
+                            stage.name
+                        )));
+                    }
+                }
+                if let Err(msg) = check_confirmations(&stage.name, activity) {
+                    return Err(serde::de::Error::custom(msg));
+                }
+                if let Err(msg) = check_artifacts(&stage.name, activity) {
+                    return Err(serde::de::Error::custom(msg));
+                }
+                if let Err(msg) = check_commands(&stage.name, activity) {
+                    return Err(serde::de::Error::custom(msg));
+                }
+            }
+        }
+        // A chain missing a barrier would leave the others waiting
+        // forever, so the counts must agree across the whole stage.
+        let mut counts = stage.chains.values().map(|chain| {
+            chain
+                .iter()
+                .filter(|a| matches!(a, Activity::Barrier {}))
+                .count()
+        });
+        if let Some(first) = counts.next() {
+            if counts.any(|count| count != first) {
+                return Err(serde::de::Error::custom(format!(
+                    "stage '{}': every chain must contain the same number of barriers",
+                    stage.name
+                )));
+            }
+        }
+    }
+    if let Some(template) = &config.setup.agent_dir {
+        if config.setup.agents.len() > 1 && !template.contains("{agent}") {
+            return Err(serde::de::Error::custom(
+                "agent_dir template must contain {agent} with multiple agents",
+            ));
+        }
+        if template.starts_with('/') {
+            return Err(serde::de::Error::custom(
+                "agent_dir template must be relative to the output directory",
+            ));
+        }
+    }
+    if let Some(name) = &config.setup.archive_name {
+        if name.contains('/') {
+            return Err(serde::de::Error::custom("archive_name must not contain '/'"));
+        }
+    }
+    if let Some(concurrency) = config.setup.concurrency {
+        if concurrency == 0 {
+            return Err(serde::de::Error::custom("concurrency must be at least 1"));
+        }
+        for stage in &config.stages {
+            let has_barriers = stage
+                .chains
+                .values()
+                .flatten()
+                .any(|a| matches!(a, Activity::Barrier {}));
+            if has_barriers && concurrency < stage.chains.len() {
+                return Err(serde::de::Error::custom(format!(
+                    "stage '{}': barriers need all {} chains running at once, \
+                     but concurrency is {concurrency}",
+                    stage.name,
+                    stage.chains.len()
+                )));
+            }
+        }
+    }
+    Ok(config)
+}
+
+/// Destructive activity options need an explicit confirmation flag next
+/// to them, so a copy-pasted scenario cannot discard data by accident.
+fn check_confirmations(stage: &str, activity: &Activity) -> Result<(), String> {
+    match activity {
+        Activity::PrepareFs {
+            trim: Some(_),
+            confirm_trim: false,
+            ..
+        } => Err(format!(
+            "stage '{stage}': prepare_fs trim discards unused blocks, \
+             set confirm_trim: true to confirm"
+        )),
+        Activity::Parallel(entries) => entries
+            .iter()
+            .try_for_each(|e| check_confirmations(stage, e)),
+        _ => Ok(()),
+    }
+}
+
+/// Artifact options on launch entries are easy to get subtly wrong, so
+/// they are validated up front: only a completed foreground command has
+/// a stdout to store, and a broken regex should fail the parse rather
+/// than the run.
+fn check_artifacts(stage: &str, activity: &Activity) -> Result<(), String> {
+    match activity {
+        Activity::Launch {
+            artifact, extract, ..
+        } if artifact.is_none() && extract.is_some() => Err(format!(
+            "stage '{stage}': launch extract needs an artifact name to store under"
+        )),
+        Activity::Launch {
+            artifact: Some(_),
+            mode: LaunchMode::Bg,
+            ..
+        } => Err(format!(
+            "stage '{stage}': launch artifacts need mode: fg, \
+             background commands have no stdout to store"
+        )),
+        Activity::Launch {
+            extract: Some(re), ..
+        } => regex::Regex::new(re)
+            .map(|_| ())
+            .map_err(|e| format!("stage '{stage}': bad launch extract regex: {e}")),
+        Activity::Parallel(entries) => entries.iter().try_for_each(|e| check_artifacts(stage, e)),
+        _ => Ok(()),
+    }
+}
+
+/// An empty launch command parses cleanly (`cmd: []` is a valid YAML
+/// list) but there is nothing to execute, so it is rejected up front
+/// rather than failing on the agent mid-run.
+fn check_commands(stage: &str, activity: &Activity) -> Result<(), String> {
+    match activity {
+        Activity::Launch { cmd, .. } if cmd.is_empty() => {
+            Err(format!("stage '{stage}': launch cmd must not be empty"))
+        }
+        Activity::Parallel(entries) => entries.iter().try_for_each(|e| check_commands(stage, e)),
+        _ => Ok(()),
+    }
+}
+
+/// Load and parse a scenario file.
+pub fn load(path: &Path) -> Result<Config, String> {
+    let text = crate::common::readfile(path).map_err(|e| format!("cannot read config: {e}"))?;
+    parse(&text).map_err(|e| format!("cannot parse config: {e}"))
+}
+
+/// Rewrite a scenario for selfhosted execution: the single agent runs
+/// in-process regardless of its configured address, so distributed
+/// scenarios can be developed and debugged offline unchanged.
+/// Multi-agent scenarios cannot collapse onto one host.
+pub fn selfhost(mut config: Config) -> Result<Config, String> {
+    match config.setup.agents.as_mut_slice() {
+        [agent] => {
+            agent.addr = None;
+            agent.local = true;
+            Ok(config)
+        }
+        agents => Err(format!(
+            "selfhosted mode needs exactly one agent, config has {}",
+            agents.len()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+setup:
+  agents:
+    - name: client
+      addr: "10.0.0.1:50051"
+    - name: server
+      addr: "10.0.0.2:50051"
+stages:
+  - name: warmup
+    duration: 10
+    chains:
+      server:
+        - mpstat: {}
+        - meminfo: { period_ms: 500 }
+  - name: load
+    chains:
+      server:
+        - iostat: { period: 2 }
+        - netdev: {}
+      client:
+        - fio: { args: ["--name=rw", "--rw=randrw"] }
+"#;
+
+    #[test]
+    fn sample_config_parses() {
+        let config = parse(SAMPLE).unwrap();
+        assert_eq!(config.setup.agents.len(), 2);
+        assert_eq!(config.stages.len(), 2);
+
+        let warmup = &config.stages[0];
+        assert_eq!(warmup.name, "warmup");
+        assert_eq!(warmup.duration, Some(10));
+        assert_eq!(warmup.chains["server"].len(), 2);
+        assert!(matches!(
+            warmup.chains["server"][1],
+            Activity::Meminfo { period_ms: 500 }
+        ));
+
+        let load = &config.stages[1];
+        assert!(load.duration.is_none());
+        assert!(matches!(load.chains["client"][0], Activity::Fio { .. }));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let bad = SAMPLE.replace("duration", "duratoin");
+        assert!(parse(&bad).is_err());
+    }
+
+    #[test]
+    fn prepare_fs_trim_needs_confirmation() {
+        let text = r#"
+setup:
+  agents:
+    - name: box
+      local: true
+stages:
+  - name: prep
+    chains:
+      box:
+        - prepare_fs: { trim: /mnt/test }
+"#;
+        assert!(parse(text).unwrap_err().to_string().contains("confirm_trim"));
+
+        let confirmed = text.replace("{ trim: /mnt/test }", "{ trim: /mnt/test, confirm_trim: true }");
+        assert!(parse(&confirmed).is_ok());
+    }
+
+    #[test]
+    fn launch_artifact_needs_fg_mode() {
+        let text = r#"
+setup:
+  agents:
+    - name: box
+      local: true
+stages:
+  - name: prep
+    chains:
+      box:
+        - launch: { cmd: [mktemp, -d], artifact: scratch }
+"#;
+        assert!(parse(text).unwrap_err().to_string().contains("mode: fg"));
+
+        let fg = text.replace("artifact: scratch", "artifact: scratch, mode: fg");
+        assert!(parse(&fg).is_ok());
+
+        let bad = fg.replace("mode: fg", "mode: fg, extract: '(['");
+        assert!(parse(&bad).unwrap_err().to_string().contains("regex"));
+    }
+
+    #[test]
+    fn launch_cmd_must_not_be_empty() {
+        let text = r#"
+setup:
+  agents:
+    - name: box
+      local: true
+stages:
+  - name: prep
+    chains:
+      box:
+        - launch: { cmd: [] }
+"#;
+        assert!(parse(text)
+            .unwrap_err()
+            .to_string()
+            .contains("cmd must not be empty"));
+    }
+
+    #[test]
+    fn barrier_counts_must_match_across_chains() {
+        let text = r#"
+setup:
+  agents:
+    - name: client
+      local: true
+    - name: server
+      local: true
+stages:
+  - name: load
+    chains:
+      client:
+        - barrier: {}
+        - vmstat: { period: 1 }
+      server:
+        - vmstat: { period: 1 }
+"#;
+        let error = parse(text).unwrap_err().to_string();
+        assert!(error.contains("same number of barriers"));
+
+        let balanced = text.replace(
+            "      server:\n        - vmstat",
+            "      server:\n        - barrier: {}\n        - vmstat",
+        );
+        assert!(parse(&balanced).is_ok());
+    }
+
+    #[test]
+    fn output_templates_are_validated() {
+        let text = r#"
+setup:
+  agents:
+    - name: client
+      local: true
+    - name: server
+      local: true
+  agent_dir: "{run_label}/{agent}"
+stages: []
+"#;
+        assert!(parse(text).is_ok());
+
+        let error = parse(&text.replace("{run_label}/{agent}", "{run_label}"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("must contain {agent}"));
+
+        let error = parse(&text.replace("{run_label}/{agent}", "/lab/{agent}"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("must be relative"));
+
+        let bad_archive = text.replace(
+            "  agent_dir: \"{run_label}/{agent}\"",
+            "  archive_name: \"sub/out.tgz\"",
+        );
+        let error = parse(&bad_archive).unwrap_err().to_string();
+        assert!(error.contains("archive_name"));
+    }
+
+    #[test]
+    fn concurrency_must_cover_barrier_stages() {
+        let text = r#"
+setup:
+  agents:
+    - name: client
+      local: true
+    - name: server
+      local: true
+  concurrency: 1
+stages:
+  - name: load
+    chains:
+      client:
+        - barrier: {}
+        - vmstat: { period: 1 }
+      server:
+        - barrier: {}
+        - vmstat: { period: 1 }
+"#;
+        let error = parse(text).unwrap_err().to_string();
+        assert!(error.contains("barriers need all 2 chains"));
+
+        assert!(parse(&text.replace("concurrency: 1", "concurrency: 2")).is_ok());
+        let error = parse(&text.replace("concurrency: 1", "concurrency: 0"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("at least 1"));
+    }
+
+    #[test]
+    fn selfhost_localizes_a_single_remote_agent() {
+        let text = r#"
+setup:
+  agents:
+    - name: box
+      addr: "10.0.0.1:50051"
+stages: []
+"#;
+        let config = selfhost(parse(text).unwrap()).unwrap();
+        assert!(config.setup.agents[0].local);
+        assert!(config.setup.agents[0].addr.is_none());
+
+        assert!(selfhost(parse(SAMPLE).unwrap()).is_err());
+    }
+
+    #[test]
+    fn local_agents_need_no_addr() {
+        let text = r#"
+setup:
+  agents:
+    - name: here
+      local: true
+stages: []
+"#;
+        let config = parse(text).unwrap();
+        assert!(config.setup.agents[0].local);
+        assert!(config.setup.agents[0].addr.is_none());
+
+        let bad = text.replace("local: true", "local: false");
+        assert!(parse(&bad).is_err());
+    }
+}
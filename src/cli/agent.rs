@@ -0,0 +1,187 @@
+//! The agent command line.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Subcommand;
+use clap_complete::Shell;
+use regex::Regex;
+
+use crate::agent;
+
+/// Agent-side commands.
+#[derive(Subcommand)]
+pub enum Command {
+    /// Serve controller sessions over TCP, one session per connection.
+    Tcp {
+        /// Address to listen on.
+        #[arg(long, value_name = "ADDR", default_value = "0.0.0.0:50051")]
+        listen: String,
+        /// Root directory for the numbered session directories.
+        #[arg(long, value_name = "DIR")]
+        outdir: PathBuf,
+        /// Exit after serving this many sessions.
+        #[arg(long, value_name = "N")]
+        max_sessions: Option<u64>,
+        /// Reject spawned commands whose executable does not match REGEX.
+        #[arg(long, value_name = "REGEX", value_parser = parse_regex)]
+        allow: Option<Regex>,
+        /// Prune the oldest session directories at startup, keeping at
+        /// most this many.
+        #[arg(long, value_name = "N")]
+        keep_sessions: Option<usize>,
+        /// Prune the oldest session directories at startup until the
+        /// remaining ones fit this total size (e.g. `500M`, `8G`).
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_outdir_size: Option<u64>,
+        /// Convert recognized outputs (mpstat, iostat, vmstat, meminfo,
+        /// netdev) into tidy export tables at collect time, replacing
+        /// the raw logs in the archive.
+        #[cfg(feature = "plotter")]
+        #[arg(long, value_name = "csv|json|openmetrics|influx", value_parser = parse_format)]
+        pre_export: Option<crate::export::Format>,
+    },
+    /// Execute JSON requests from stdin locally, one per line, without a
+    /// controller. Collected archives stay in the session directory.
+    Selfhosted {
+        /// Root directory for the numbered session directories.
+        outdir: PathBuf,
+        /// Run a full YAML scenario locally instead of reading JSON
+        /// requests: the single configured agent runs in-process,
+        /// whatever its configured address.
+        #[cfg(feature = "controller")]
+        #[arg(long, value_name = "FILE")]
+        config: Option<PathBuf>,
+        /// Convert recognized outputs into tidy export tables at collect
+        /// time, replacing the raw logs (see `tcp --pre-export`).
+        #[cfg(feature = "plotter")]
+        #[arg(long, value_name = "csv|json|openmetrics|influx", value_parser = parse_format)]
+        pre_export: Option<crate::export::Format>,
+    },
+    /// Generate a shell completion script to stdout.
+    Completions { shell: Shell },
+}
+
+/// Execute an agent command. `cmd` is the full command of the calling
+/// binary, used by the completions mode.
+pub fn run(command: Command, mut cmd: clap::Command) -> ExitCode {
+    #[cfg(feature = "plotter")]
+    match &command {
+        Command::Tcp {
+            pre_export: Some(format),
+            ..
+        }
+        | Command::Selfhosted {
+            pre_export: Some(format),
+            ..
+        } => agent::set_pre_export(*format),
+        _ => {}
+    }
+
+    let result = match command {
+        Command::Tcp {
+            listen,
+            outdir,
+            max_sessions,
+            allow,
+            keep_sessions,
+            max_outdir_size,
+            ..
+        } => {
+            let retention = agent::Retention {
+                max_sessions: keep_sessions,
+                max_bytes: max_outdir_size,
+            };
+            agent::prune_sessions(&outdir, &retention).and_then(|_| {
+                let proto = agent::TcpMsgpackProtocol::bind(&listen)?;
+                eprintln!("agent: listening on {listen}");
+                proto.serve_with(&outdir, max_sessions, allow.as_ref())
+            })
+        }
+        #[cfg(feature = "controller")]
+        Command::Selfhosted {
+            outdir,
+            config: Some(config),
+            ..
+        } => return run_scenario(&config, &outdir),
+        Command::Selfhosted { outdir, .. } => agent::selfhosted(&outdir),
+        Command::Completions { shell } => {
+            let name = cmd.get_name().to_string();
+            clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
+            return ExitCode::SUCCESS;
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("agent: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_regex(s: &str) -> Result<Regex, String> {
+    Regex::new(s).map_err(|e| e.to_string())
+}
+
+/// Parse a size like `500M`, `8G`, `32K` or plain bytes.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let (number, multiplier) = match s.chars().last() {
+        Some('K') => (&s[..s.len() - 1], 1u64 << 10),
+        Some('M') => (&s[..s.len() - 1], 1 << 20),
+        Some('G') => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    let number: u64 = number.parse().map_err(|_| format!("bad size '{s}'"))?;
+    Ok(number * multiplier)
+}
+
+#[cfg(feature = "plotter")]
+fn parse_format(s: &str) -> Result<crate::export::Format, String> {
+    s.parse()
+}
+
+/// Drive a YAML scenario through the full run loop with the agent
+/// in-process, writing run output into a numbered directory under
+/// `outdir`.
+#[cfg(feature = "controller")]
+fn run_scenario(config_path: &std::path::Path, outdir: &std::path::Path) -> ExitCode {
+    use crate::controller::exit_code;
+
+    let config = match crate::cfgparse::load(config_path).and_then(crate::cfgparse::selfhost) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("agent: {e}");
+            return ExitCode::from(exit_code::CONFIG);
+        }
+    };
+    let rundir = match crate::common::create_next_numeric_dir_in(outdir) {
+        Ok(rundir) => rundir,
+        Err(e) => {
+            eprintln!("agent: {e}");
+            return ExitCode::from(exit_code::IO);
+        }
+    };
+    eprintln!("agent: selfhosted run in {}", rundir.display());
+    match crate::controller::run(&config, &rundir) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("agent: {e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_units_parse() {
+        assert_eq!(parse_size("100"), Ok(100));
+        assert_eq!(parse_size("32K"), Ok(32 << 10));
+        assert_eq!(parse_size("8G"), Ok(8 << 30));
+        assert!(parse_size("8T").is_err());
+        assert!(parse_size("G").is_err());
+    }
+}
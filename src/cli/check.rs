@@ -0,0 +1,41 @@
+//! The regression gate command line.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::gate;
+
+/// Check a candidate run against a baseline with a rules file, exiting
+/// non-zero on any violation so CI can gate on the result.
+pub fn run(baseline: &Path, candidate: &Path, rules_path: &Path) -> ExitCode {
+    let rules = match gate::load_rules(rules_path) {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("check: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    for run in [baseline, candidate] {
+        if let Err(e) = crate::cli::plotter::unpack_run(run) {
+            eprintln!("check: cannot unpack {}: {e}", run.display());
+            return ExitCode::FAILURE;
+        }
+    }
+
+    match gate::check(baseline, candidate, &rules) {
+        Ok(violations) if violations.is_empty() => {
+            println!("OK ({} rules)", rules.rules.len());
+            ExitCode::SUCCESS
+        }
+        Ok(violations) => {
+            for violation in &violations {
+                println!("FAIL {violation}");
+            }
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("check: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
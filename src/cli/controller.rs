@@ -0,0 +1,287 @@
+//! The controller command line.
+
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc;
+
+use crate::cfgparse::Config;
+use crate::common::create_next_numeric_dir_in;
+use crate::controller::RunError;
+use crate::{cfgparse, controller};
+
+/// Load a scenario and execute the full run in a fresh numbered run
+/// directory under `output_root`, writing `failure.json` into it when the
+/// run fails. With `plot` the HTML report is rendered right after the
+/// run, and `open` hands it to the desktop browser.
+pub fn run_scenario(config_path: &Path, output_root: &Path, plot: bool, open: bool) -> ExitCode {
+    let config = match cfgparse::load(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("controller: {e}");
+            return ExitCode::from(controller::exit_code::CONFIG);
+        }
+    };
+
+    let outdir = match std::fs::create_dir_all(output_root)
+        .and_then(|()| create_next_numeric_dir_in(output_root))
+    {
+        Ok(outdir) => outdir,
+        Err(e) => {
+            eprintln!(
+                "controller: cannot create run directory in {}: {e}",
+                output_root.display()
+            );
+            return ExitCode::from(controller::exit_code::IO);
+        }
+    };
+    eprintln!("controller: run directory {}", outdir.display());
+
+    match controller::run(&config, &outdir) {
+        Ok(()) => {
+            announce(&config, "completed", &outdir);
+            if plot {
+                if let Err(e) = crate::cli::plotter::report_run(&outdir) {
+                    eprintln!("controller: cannot render report: {e}");
+                    return ExitCode::from(controller::exit_code::IO);
+                }
+                let report = outdir.join("report.html");
+                eprintln!("controller: report at {}", report.display());
+                if open {
+                    // Fire and forget: the browser outliving us is fine.
+                    if let Err(e) = std::process::Command::new("xdg-open").arg(&report).spawn() {
+                        eprintln!("controller: cannot open {}: {e}", report.display());
+                    }
+                }
+            }
+            if let Some(upload) = &config.setup.upload {
+                if let Err(e) = crate::upload::run(upload, &outdir) {
+                    eprintln!("controller: upload failed: {e}");
+                    return ExitCode::from(controller::exit_code::IO);
+                }
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("controller: {e}");
+            announce(&config, "failed", &outdir);
+            let report =
+                serde_json::to_string_pretty(&e.failure_report()).expect("serializable");
+            if let Err(write_err) = std::fs::write(outdir.join("failure.json"), report) {
+                eprintln!("controller: cannot write failure.json: {write_err}");
+            }
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// Fire the configured completion notification; a failure to deliver it
+/// never changes the run outcome.
+fn announce(config: &Config, status: &str, outdir: &Path) {
+    if let Some(notify) = &config.setup.notify {
+        if let Err(e) = crate::notify::send(notify, status, outdir) {
+            eprintln!("controller: notification failed: {e}");
+        }
+    }
+}
+
+/// Parse and validate a scenario configuration without running it.
+pub fn validate(config_path: &Path) -> ExitCode {
+    match cfgparse::load(config_path) {
+        Ok(config) => {
+            println!(
+                "{}: OK ({} agents, {} stages)",
+                config_path.display(),
+                config.setup.agents.len(),
+                config.stages.len()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("controller: {e}");
+            ExitCode::from(controller::exit_code::CONFIG)
+        }
+    }
+}
+
+/// Run every scenario found in a directory and tie the reports together
+/// with an `index.html` in the output root.
+///
+/// Configs run sequentially by default. With `jobs > 1`, configs whose
+/// remote agent sets are disjoint may overlap; configs sharing an agent
+/// never do. All configs are parsed up front so a nightly suite fails
+/// before the first run rather than halfway through.
+pub fn batch(configs_dir: &Path, output_root: &Path, jobs: usize) -> ExitCode {
+    let paths = match config_paths(configs_dir) {
+        Ok(paths) if paths.is_empty() => {
+            eprintln!("controller: no .yaml configs in {}", configs_dir.display());
+            return ExitCode::from(controller::exit_code::CONFIG);
+        }
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("controller: cannot read {}: {e}", configs_dir.display());
+            return ExitCode::from(controller::exit_code::CONFIG);
+        }
+    };
+
+    let mut configs = Vec::new();
+    for path in &paths {
+        match cfgparse::load(path) {
+            Ok(config) => configs.push(config),
+            Err(e) => {
+                eprintln!("controller: {e}");
+                return ExitCode::from(controller::exit_code::CONFIG);
+            }
+        }
+    }
+
+    // Create the run directories up front, in config order, so the run
+    // numbering stays stable regardless of the execution order.
+    let mut runs = Vec::new();
+    for path in &paths {
+        let label = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        match std::fs::create_dir_all(output_root)
+            .and_then(|()| create_next_numeric_dir_in(output_root))
+        {
+            Ok(outdir) => {
+                eprintln!("controller: [{label}] run directory {}", outdir.display());
+                runs.push((label, outdir));
+            }
+            Err(e) => {
+                eprintln!(
+                    "controller: cannot create run directory in {}: {e}",
+                    output_root.display()
+                );
+                return ExitCode::from(controller::exit_code::IO);
+            }
+        }
+    }
+
+    let results = run_batch(&configs, &runs, jobs.max(1));
+
+    let mut exit = ExitCode::SUCCESS;
+    for (((label, outdir), result), config) in runs.iter().zip(&results).zip(&configs) {
+        let status = if result.is_ok() { "completed" } else { "failed" };
+        announce(config, status, outdir);
+        if let Err(e) = result {
+            eprintln!("controller: [{label}] {e}");
+            let report = serde_json::to_string_pretty(&e.failure_report()).expect("serializable");
+            if let Err(write_err) = std::fs::write(outdir.join("failure.json"), report) {
+                eprintln!("controller: cannot write failure.json: {write_err}");
+            }
+            exit = ExitCode::from(e.exit_code());
+            continue;
+        }
+        if let Err(e) = crate::cli::plotter::report_run(outdir) {
+            eprintln!("controller: [{label}] cannot render report: {e}");
+            exit = ExitCode::from(controller::exit_code::IO);
+        }
+        if let Some(upload) = &config.setup.upload {
+            if let Err(e) = crate::upload::run(upload, outdir) {
+                eprintln!("controller: [{label}] upload failed: {e}");
+                exit = ExitCode::from(controller::exit_code::IO);
+            }
+        }
+    }
+
+    if let Err(e) = write_index(output_root, &runs, &results) {
+        eprintln!("controller: cannot write index.html: {e}");
+        return ExitCode::from(controller::exit_code::IO);
+    }
+    eprintln!("controller: index at {}", output_root.join("index.html").display());
+    exit
+}
+
+fn config_paths(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let ext = path.extension().and_then(|e| e.to_str());
+        if matches!(ext, Some("yaml" | "yml")) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Remote agents this config talks to: two configs may run concurrently
+/// only when these sets are disjoint. Local agents are spawned per run
+/// and never conflict.
+fn remote_agents(config: &Config) -> BTreeSet<String> {
+    config.setup.agents.iter().filter_map(|a| a.addr.clone()).collect()
+}
+
+fn run_batch(
+    configs: &[Config],
+    runs: &[(String, PathBuf)],
+    jobs: usize,
+) -> Vec<Result<(), RunError>> {
+    std::thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        let mut results: Vec<Option<Result<(), RunError>>> = configs.iter().map(|_| None).collect();
+        let mut pending: Vec<usize> = (0..configs.len()).collect();
+        let mut busy: Vec<(usize, BTreeSet<String>)> = Vec::new();
+        let mut done = 0;
+
+        while done < configs.len() {
+            let mut i = 0;
+            while busy.len() < jobs && i < pending.len() {
+                let index = pending[i];
+                let agents = remote_agents(&configs[index]);
+                if busy.iter().any(|(_, taken)| !taken.is_disjoint(&agents)) {
+                    i += 1;
+                    continue;
+                }
+                pending.remove(i);
+                busy.push((index, agents));
+                let tx = tx.clone();
+                let config = &configs[index];
+                let outdir = &runs[index].1;
+                scope.spawn(move || {
+                    let _ = tx.send((index, controller::run(config, outdir)));
+                });
+            }
+
+            let (index, result) = rx.recv().expect("a worker is running");
+            busy.retain(|(busy_index, _)| *busy_index != index);
+            results[index] = Some(result);
+            done += 1;
+        }
+        results.into_iter().map(|r| r.expect("all collected")).collect()
+    })
+}
+
+/// Write the combined `index.html` linking every run's report.
+fn write_index(
+    output_root: &Path,
+    runs: &[(String, PathBuf)],
+    results: &[Result<(), RunError>],
+) -> std::io::Result<()> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(output_root.join("index.html"))?);
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>pmppt batch</title></head><body>")?;
+    writeln!(out, "<h1>pmppt batch</h1>")?;
+    writeln!(out, "<table border=\"1\" cellpadding=\"4\">")?;
+    writeln!(out, "<tr><th>config</th><th>run</th><th>status</th><th>report</th></tr>")?;
+    for ((label, outdir), result) in runs.iter().zip(results) {
+        let run = outdir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let status = match result {
+            Ok(()) => "ok".to_string(),
+            Err(e) => format!("<span style=\"color: #b00\">{e}</span>"),
+        };
+        let report = if result.is_ok() {
+            format!("<a href=\"{run}/report.html\">report</a>")
+        } else {
+            format!("<a href=\"{run}/failure.json\">failure</a>")
+        };
+        writeln!(
+            out,
+            "<tr><td>{label}</td><td>{run}</td><td>{status}</td><td>{report}</td></tr>"
+        )?;
+    }
+    writeln!(out, "</table></body></html>")?;
+    Ok(())
+}
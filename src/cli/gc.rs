@@ -0,0 +1,126 @@
+//! Result retention: pruning of old numbered output directories.
+//!
+//! Both the controller (run directories) and the agents (session
+//! directories) accumulate numbered directories forever, in the layout
+//! `create_next_numeric_dir_in` produces. `pmppt gc` strips old ones
+//! down to their metadata — manifests, run descriptions, failure
+//! reports stay, so history remains inspectable after the bulky
+//! artifacts are gone.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+/// Top-level files that survive pruning: small metadata describing what
+/// the run was and how it went.
+const PRESERVED: &[&str] = &["manifest.json", "run.json", "marks.json", "failure.json"];
+
+/// Prune old numbered directories under `root`. The newest `keep_last`
+/// are never touched; with `older_than` only directories modified
+/// before the cutoff are pruned.
+pub fn run(root: &Path, keep_last: Option<usize>, older_than: Option<Duration>) -> ExitCode {
+    if keep_last.is_none() && older_than.is_none() {
+        eprintln!("gc: pass --keep-last and/or --older-than, refusing to prune everything");
+        return ExitCode::FAILURE;
+    }
+
+    let mut dirs = match numbered_dirs(root) {
+        Ok(dirs) => dirs,
+        Err(e) => {
+            eprintln!("gc: cannot read {}: {e}", root.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    dirs.sort_by_key(|(number, _)| *number);
+    let protected = dirs.len().saturating_sub(keep_last.unwrap_or(0));
+    let cutoff = older_than.map(|age| SystemTime::now() - age);
+
+    let mut failed = false;
+    for (_, dir) in &dirs[..protected] {
+        if let Some(cutoff) = cutoff {
+            let modified = std::fs::metadata(dir).and_then(|meta| meta.modified());
+            if !modified.is_ok_and(|time| time < cutoff) {
+                continue;
+            }
+        }
+        match prune(dir) {
+            Ok(removed) => eprintln!("gc: pruned {} ({removed} entries)", dir.display()),
+            Err(e) => {
+                eprintln!("gc: cannot prune {}: {e}", dir.display());
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn numbered_dirs(root: &Path) -> std::io::Result<Vec<(u64, PathBuf)>> {
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(root)? {
+        let entry = entry?;
+        let Ok(number) = entry.file_name().to_string_lossy().parse() else {
+            continue;
+        };
+        if entry.file_type()?.is_dir() {
+            dirs.push((number, entry.path()));
+        }
+    }
+    Ok(dirs)
+}
+
+/// Remove everything in the directory except the preserved metadata
+/// files. A directory left with nothing to preserve (an agent session,
+/// say) is removed entirely.
+fn prune(dir: &Path) -> std::io::Result<usize> {
+    let mut removed = 0;
+    let mut preserved = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if PRESERVED.contains(&name.to_string_lossy().as_ref()) {
+            preserved += 1;
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            std::fs::remove_dir_all(entry.path())?;
+        } else {
+            std::fs::remove_file(entry.path())?;
+        }
+        removed += 1;
+    }
+    if preserved == 0 {
+        std::fs::remove_dir(dir)?;
+    }
+    Ok(removed)
+}
+
+/// Parse an age like `30d`, `12h`, `45m` or `90s`.
+pub fn parse_age(s: &str) -> Result<Duration, String> {
+    let (number, unit) = s.split_at(s.len().saturating_sub(1));
+    let number: u64 = number.parse().map_err(|_| format!("bad age '{s}'"))?;
+    let seconds = match unit {
+        "d" => number * 24 * 60 * 60,
+        "h" => number * 60 * 60,
+        "m" => number * 60,
+        "s" => number,
+        _ => return Err(format!("bad age unit in '{s}', expected d/h/m/s")),
+    };
+    Ok(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn age_units_parse() {
+        assert_eq!(parse_age("30d"), Ok(Duration::from_secs(30 * 24 * 3600)));
+        assert_eq!(parse_age("90s"), Ok(Duration::from_secs(90)));
+        assert!(parse_age("30x").is_err());
+        assert!(parse_age("d").is_err());
+    }
+}
@@ -0,0 +1,14 @@
+//! Command-line entry points, shared between the individual binaries
+//! (`pmppt_agent`, `pmppt_controller`, `pmppt_plotter`) and the unified
+//! `pmppt` binary that mounts them as subcommands.
+
+pub mod agent;
+#[cfg(feature = "plotter")]
+pub mod check;
+#[cfg(all(feature = "controller", feature = "plotter"))]
+pub mod controller;
+pub mod gc;
+#[cfg(feature = "plotter")]
+pub mod plotter;
+#[cfg(feature = "controller")]
+pub mod shell;
@@ -0,0 +1,462 @@
+//! The plotter command line: render HTML reports from collected output.
+//!
+//! The main `plot` mode takes an agent directory (unpacked session files
+//! or a collected `out.tgz`), a whole run directory with agent
+//! subdirectories, or a bare `out.tgz` archive.
+
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use clap::{Args, Subcommand};
+use clap_complete::Shell;
+use rayon::prelude::*;
+use regex::Regex;
+
+use crate::common::{millis_to_naive, readfile};
+use crate::export::Format;
+use crate::plot::Theme;
+use crate::plotters::sysstat::mpstat::HeatScale;
+use crate::plotters::{
+    compare, correlate, filter, flame, procfs, quality, read_mapping, read_marks, registry, report,
+    summary, timeline, trend,
+};
+
+/// Render time axes as seconds since the run start instead of absolute
+/// local timestamps.
+static RELATIVE: AtomicBool = AtomicBool::new(false);
+
+/// Start of the run in the agent's local clock: the epoch milliseconds of
+/// the first journal entry.
+fn journal_start(dir: &Path) -> Option<i64> {
+    let text = readfile(&dir.join("journal.log")).ok()?;
+    text.lines().next()?.split_whitespace().next()?.parse().ok()
+}
+
+/// Register relative-time origins for every agent of a run. The origin is
+/// the earliest journal start translated onto the controller clock; each
+/// agent directory gets it translated back into that agent's local time
+/// using the recorded clock offsets, so the axes line up across agents
+/// despite clock skew.
+fn set_relative_origins(run_dir: &Path, dirs: &[(String, PathBuf)]) {
+    if !RELATIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    let agents: Vec<String> = dirs.iter().map(|(name, _)| name.clone()).collect();
+    let offsets = timeline::agent_offsets(run_dir, &agents);
+    let offset = |name: &str| offsets.get(name).copied().unwrap_or(0);
+    let start = dirs
+        .iter()
+        .filter_map(|(name, dir)| {
+            let _ = unpack_if_needed(dir);
+            Some(journal_start(dir)? - offset(name))
+        })
+        .min();
+    let Some(start) = start else { return };
+    for (name, dir) in dirs {
+        let local = start + offset(name);
+        crate::plot::set_relative_origin(dir, millis_to_naive(local as u64));
+    }
+    crate::plot::set_relative_origin(run_dir, millis_to_naive(start as u64));
+}
+
+/// Register a relative-time origin for one stand-alone agent directory.
+/// Without the run storage no clock offset is known, so the agent's own
+/// journal start serves as the origin.
+fn set_relative_origin_single(dir: &Path) -> io::Result<()> {
+    if !RELATIVE.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    unpack_if_needed(dir)?;
+    if let Some(start) = journal_start(dir) {
+        crate::plot::set_relative_origin(dir, millis_to_naive(start as u64));
+    }
+    Ok(())
+}
+
+/// Unpack a collected `out.tgz` into `dir`. The agent archives its output
+/// directory with a two-component prefix (root/session); strip however
+/// many leading directories the entries carry so the session files land
+/// directly in `dir` regardless of where the agent kept them.
+fn unpack_archive(archive: &Path, dir: &Path) -> io::Result<()> {
+    let tgz = flate2::read::GzDecoder::new(File::open(archive)?);
+    for entry in tar::Archive::new(tgz).entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        let Some(name) = path.file_name() else { continue };
+        entry.unpack(dir.join(name))?;
+    }
+    Ok(())
+}
+
+/// Unpack a collected archive in place if the directory only holds one.
+fn unpack_if_needed(dir: &Path) -> io::Result<()> {
+    if !dir.join("out.map").exists() && dir.join("out.tgz").exists() {
+        unpack_archive(&dir.join("out.tgz"), dir)?;
+    }
+    Ok(())
+}
+
+/// Parse and plot everything found in one agent directory, optionally
+/// exporting the parsed series as tidy tables. The independent data
+/// sources are processed in parallel.
+fn process_dir(dir: &Path, export_to: Option<Format>, scale: HeatScale) -> io::Result<()> {
+    unpack_if_needed(dir)?;
+
+    let marks = read_marks(dir);
+    let mapping = read_mapping(dir)?;
+    let quality = Mutex::new(Vec::new());
+    mapping.par_iter().try_for_each(|(id, name)| -> io::Result<()> {
+        let Some(handler) = registry::lookup(name) else {
+            eprintln!("pmppt_plotter: no plotter registered for activity '{name}' ({id})");
+            return Ok(());
+        };
+        let ctx = registry::Context {
+            dir,
+            id,
+            name,
+            marks: &marks,
+            export_to,
+            scale,
+        };
+        if let Some(assessment) = handler(&ctx)? {
+            quality.lock().unwrap().push(assessment);
+        }
+        Ok(())
+    })?;
+
+    let quality = quality.into_inner().unwrap();
+    if !quality.is_empty() {
+        quality::plot(&quality, dir)?;
+    }
+
+    // Profiling data is keyed by well-known file names like fio.json, not
+    // by the activity mapping.
+    flame::process(dir)
+}
+
+/// Plot every agent directory of a run and tie the generated pages
+/// together in a single `report.html` with navigation tabs.
+fn process_run(run_dir: &Path, export_to: Option<Format>, scale: HeatScale) -> io::Result<()> {
+    let dirs = agent_dirs(run_dir)?;
+    set_relative_origins(run_dir, &dirs);
+
+    let mut agents = dirs
+        .par_iter()
+        .map(|(name, dir)| {
+            process_dir(dir, export_to, scale)?;
+            Ok((name.clone(), report::collect_pages(dir)?))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    agents.sort();
+    if agents.is_empty() {
+        return Err(io::Error::other("no agent directories found in the run"));
+    }
+    report::write(run_dir, &agents)
+}
+
+/// Agent subdirectories of a run, as (agent name, path) pairs.
+/// Render the full HTML report of a finished run with default options.
+/// The one-shot `run --plot` workflow uses this right after the
+/// controller finishes.
+pub fn report_run(run_dir: &Path) -> io::Result<()> {
+    process_run(run_dir, None, HeatScale::default())
+}
+
+fn agent_dirs(run_dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(run_dir)? {
+        let entry = entry?;
+        let dir = entry.path();
+        if dir.is_dir() && (dir.join("out.map").exists() || dir.join("out.tgz").exists()) {
+            dirs.push((entry.file_name().to_string_lossy().into_owned(), dir));
+        }
+    }
+    Ok(dirs)
+}
+
+/// Plot whatever the given path turns out to be: a bare `out.tgz`, one
+/// agent directory, or a whole run directory with agent subdirectories.
+fn process_path(path: &Path, export_to: Option<Format>, scale: HeatScale) -> io::Result<()> {
+    if path.is_file() {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        unpack_archive(path, dir)?;
+        set_relative_origin_single(dir)?;
+        return process_dir(dir, export_to, scale);
+    }
+    if path.join("out.map").exists() || path.join("out.tgz").exists() {
+        set_relative_origin_single(path)?;
+        return process_dir(path, export_to, scale);
+    }
+    process_run(path, export_to, scale)
+}
+
+/// Unpack every agent directory of a run and render the combined
+/// multi-agent timeline.
+fn process_timeline(run_dir: &Path) -> io::Result<()> {
+    unpack_run(run_dir)?;
+    set_relative_origins(run_dir, &agent_dirs(run_dir)?);
+    timeline::plot(run_dir, &read_marks(run_dir))
+}
+
+/// Unpack every collected agent archive of a run in place.
+pub(crate) fn unpack_run(run_dir: &Path) -> io::Result<()> {
+    for entry in std::fs::read_dir(run_dir)? {
+        let dir = entry?.path();
+        if dir.is_dir() && (dir.join("out.map").exists() || dir.join("out.tgz").exists()) {
+            unpack_if_needed(&dir)?;
+        }
+    }
+    Ok(())
+}
+
+/// Overlay two runs and emit the delta summary table.
+fn process_compare(run_a: &Path, run_b: &Path) -> io::Result<()> {
+    unpack_run(run_a)?;
+    unpack_run(run_b)?;
+    compare::plot(run_a, run_b)
+}
+
+/// Plot the long-term metric trends across every run under the root.
+fn process_trend(runs_root: &Path) -> io::Result<()> {
+    for entry in std::fs::read_dir(runs_root)? {
+        let dir = entry?.path();
+        if dir.is_dir() && dir.join("run.json").exists() {
+            unpack_run(&dir)?;
+        }
+    }
+    trend::plot(runs_root)
+}
+
+/// Relate two metrics of one agent directory in `correlate.html`.
+fn process_correlate(dir: &Path, metric_a: &str, metric_b: &str) -> io::Result<()> {
+    unpack_if_needed(dir)?;
+    correlate::plot(dir, metric_a, metric_b)
+}
+
+/// Compute and write the headline statistics for one agent directory.
+fn process_summary(dir: &Path) -> io::Result<()> {
+    unpack_if_needed(dir)?;
+    let summary = summary::compute(dir, summary::mark_window(&read_marks(dir)))?;
+    summary::write(&summary, dir)?;
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
+
+fn parse_format(s: &str) -> Result<Format, String> {
+    s.parse()
+}
+
+fn parse_theme(s: &str) -> Result<Theme, String> {
+    s.parse()
+}
+
+fn parse_heat_scale(s: &str) -> Result<HeatScale, String> {
+    s.parse()
+}
+
+fn parse_regex(s: &str) -> Result<Regex, String> {
+    Regex::new(s).map_err(|e| e.to_string())
+}
+
+fn parse_plot_size(s: &str) -> Result<(u32, u32), String> {
+    let parsed = s.split_once('x').and_then(|(w, h)| {
+        Some((w.parse().ok()?, h.parse().ok()?))
+    });
+    parsed.ok_or_else(|| format!("expected WxH, got '{s}'"))
+}
+
+/// Display, filtering and export options shared by all plotter modes.
+#[derive(Args)]
+pub struct Options {
+    /// Also export the parsed series as tidy tables in this format.
+    #[arg(long, global = true, value_name = "csv|json|openmetrics|influx",
+          value_parser = parse_format)]
+    export: Option<Format>,
+
+    /// Cap the number of points per rendered scatter trace.
+    #[arg(long, global = true, value_name = "N")]
+    max_points: Option<usize>,
+
+    /// Cap the number of traces per rendered plot; truncated plots say
+    /// so in their title.
+    #[arg(long, global = true, value_name = "N")]
+    max_traces: Option<usize>,
+
+    /// Size of every rendered plot in pixels.
+    #[arg(long, global = true, value_name = "WxH", value_parser = parse_plot_size)]
+    plot_size: Option<(u32, u32)>,
+
+    /// Page color scheme.
+    #[arg(long, global = true, value_name = "light|dark", value_parser = parse_theme)]
+    theme: Option<Theme>,
+
+    /// Arrange plots in a grid with this many columns.
+    #[arg(long, global = true, value_name = "N")]
+    columns: Option<usize>,
+
+    /// Smooth rendered scatter traces with a moving average of this window.
+    #[arg(long, global = true, value_name = "N")]
+    smooth: Option<usize>,
+
+    /// Break trace lines across sample gaps longer than this multiple of
+    /// the typical interval; 0 draws through gaps.
+    #[arg(long, global = true, value_name = "N")]
+    gap_factor: Option<usize>,
+
+    /// Use a logarithmic Y axis on panels whose title matches the regex.
+    #[arg(long, global = true, value_name = "RE", value_parser = parse_regex)]
+    log_y: Option<Regex>,
+
+    /// Render time axes as seconds since the run start instead of
+    /// absolute local timestamps, aligned across agents.
+    #[arg(long, global = true)]
+    relative: bool,
+
+    /// Keep only these meminfo fields, comma separated.
+    #[arg(long, global = true, value_name = "A,B", value_delimiter = ',')]
+    mem_fields: Vec<String>,
+
+    /// Plot meminfo as deltas from the first sample.
+    #[arg(long, global = true)]
+    mem_delta: bool,
+
+    /// Fail on malformed chunks instead of skipping and counting them.
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Color range of the per-CPU heatmaps.
+    #[arg(long, global = true, value_name = "fixed|auto", value_parser = parse_heat_scale)]
+    heat_scale: Option<HeatScale>,
+
+    /// Also render per-CPU usage line charts for these CPUs on the
+    /// mpstat page, comma separated.
+    #[arg(long, global = true, value_name = "0,1", value_delimiter = ',')]
+    cpu_lines: Vec<String>,
+
+    /// Show only block devices matching the regex.
+    #[arg(long, global = true, value_name = "RE", value_parser = parse_regex)]
+    devices: Option<Regex>,
+
+    /// Hide block devices matching the regex.
+    #[arg(long, global = true, value_name = "RE", value_parser = parse_regex)]
+    drop_devices: Option<Regex>,
+
+    /// Show only network interfaces matching the regex.
+    #[arg(long, global = true, value_name = "RE", value_parser = parse_regex)]
+    ifaces: Option<Regex>,
+
+    /// Hide network interfaces matching the regex.
+    #[arg(long, global = true, value_name = "RE", value_parser = parse_regex)]
+    drop_ifaces: Option<Regex>,
+}
+
+/// The plotter modes, one subcommand each.
+#[derive(Subcommand)]
+pub enum Mode {
+    /// Plot an agent directory, a collected out.tgz, or a whole run.
+    Plot { path: PathBuf },
+    /// Plot every agent of a run and tie the pages into report.html.
+    Report { run_dir: PathBuf },
+    /// Compute and print headline statistics of one agent directory.
+    Summary { dir: PathBuf },
+    /// Render the combined multi-agent timeline of a run.
+    Timeline { run_dir: PathBuf },
+    /// Overlay two runs and emit the delta summary table.
+    Compare { run_a: PathBuf, run_b: PathBuf },
+    /// Plot headline metrics across every run under a root over time.
+    Trend { runs_root: PathBuf },
+    /// Show what differed between two runs' configs and agent sysinfo.
+    DiffConfig { run_a: PathBuf, run_b: PathBuf },
+    /// Relate two metrics of one agent with a fitted trend.
+    Correlate {
+        dir: PathBuf,
+        metric_a: String,
+        metric_b: String,
+    },
+    /// Generate a shell completion script to stdout.
+    Completions { shell: Shell },
+}
+
+/// Apply the options to the process-wide plotting knobs and execute the
+/// mode. `cmd` is the full command of the calling binary, used by the
+/// completions mode.
+pub fn run(mode: Mode, options: Options, mut cmd: clap::Command) -> ExitCode {
+    if let Mode::Completions { shell } = mode {
+        let name = cmd.get_name().to_string();
+        clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(limit) = options.max_points {
+        crate::plot::set_max_points(limit);
+    }
+    if let Some(limit) = options.max_traces {
+        crate::plot::set_max_traces(limit);
+    }
+    if let Some((width, height)) = options.plot_size {
+        crate::plot::set_size(width, height);
+    }
+    if let Some(theme) = options.theme {
+        crate::plot::set_theme(theme);
+    }
+    if let Some(columns) = options.columns {
+        crate::plot::set_columns(columns);
+    }
+    if let Some(window) = options.smooth {
+        crate::plot::set_smooth(window);
+    }
+    if let Some(factor) = options.gap_factor {
+        crate::plot::set_gap_factor(factor);
+    }
+    crate::plot::set_log_y(options.log_y);
+    if options.relative {
+        RELATIVE.store(true, Ordering::Relaxed);
+    }
+    if !options.mem_fields.is_empty() {
+        procfs::set_meminfo_fields(options.mem_fields);
+    }
+    if options.mem_delta {
+        procfs::set_meminfo_delta(true);
+    }
+    if options.strict {
+        crate::plotters::set_strict(true);
+    }
+    if !options.cpu_lines.is_empty() {
+        crate::plotters::sysstat::mpstat::set_cpu_lines(options.cpu_lines);
+    }
+    filter::set_devices(options.devices, options.drop_devices);
+    filter::set_ifaces(options.ifaces, options.drop_ifaces);
+    let export_to = options.export;
+    let scale = options.heat_scale.unwrap_or_default();
+
+    let result = match mode {
+        Mode::Plot { path } => process_path(&path, export_to, scale),
+        Mode::Report { run_dir } => process_run(&run_dir, export_to, scale),
+        Mode::Summary { dir } => process_summary(&dir),
+        Mode::Timeline { run_dir } => process_timeline(&run_dir),
+        Mode::Compare { run_a, run_b } => process_compare(&run_a, &run_b),
+        Mode::Trend { runs_root } => process_trend(&runs_root),
+        Mode::DiffConfig { run_a, run_b } => compare::diff_config(&run_a, &run_b),
+        Mode::Correlate {
+            dir,
+            metric_a,
+            metric_b,
+        } => process_correlate(&dir, &metric_a, &metric_b),
+        Mode::Completions { .. } => unreachable!("handled above"),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("plotter: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
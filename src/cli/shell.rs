@@ -0,0 +1,262 @@
+//! Interactive agent shell.
+//!
+//! Connects to a single agent and maps shell commands directly onto the
+//! wire protocol, which makes it useful both for ad-hoc poking at one
+//! machine and for debugging protocol changes.
+
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
+
+use crate::connection::{ConnError, ConnectionOps, TcpConnection};
+use crate::proto::ActivityId;
+
+const COMMANDS: &[&str] = &[
+    "check", "collect", "exec", "fetch", "help", "poll", "quit", "spawn", "status", "stop",
+];
+
+const HELP: &str = "\
+check [tool...]                probe the agent, listing missing tools
+poll <name> <period_ms> <path...>  start polling files into a log
+spawn <name> <cmd...>          start a background command, printing its id
+exec <name> <cmd...>           run a foreground command to completion
+stop <id>|all                  stop a background activity
+status                         list activities started from this shell
+fetch <remote> [local]         read a remote file (prints it without <local>)
+collect [file]                 stop everything and download the session archive
+quit                           leave the shell";
+
+/// Completes the command word; arguments are free-form.
+struct ShellHelper;
+
+impl rustyline::Helper for ShellHelper {}
+impl rustyline::highlight::Highlighter for ShellHelper {}
+impl rustyline::validate::Validator for ShellHelper {}
+
+impl rustyline::hint::Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl rustyline::completion::Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let head = &line[..pos];
+        if head.contains(' ') {
+            return Ok((pos, vec![]));
+        }
+        let candidates = COMMANDS
+            .iter()
+            .filter(|cmd| cmd.starts_with(head))
+            .map(|cmd| format!("{cmd} "))
+            .collect();
+        Ok((0, candidates))
+    }
+}
+
+/// What this shell has started on the agent, for `status` and for
+/// resolving `stop all` without relying on protocol-level bookkeeping.
+struct Activity {
+    name: String,
+    kind: &'static str,
+    running: bool,
+}
+
+/// Connect to an agent and run the interactive loop.
+pub fn run(addr: &str) -> ExitCode {
+    let mut conn = match TcpConnection::connect(addr) {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("shell: cannot connect to {addr}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!("shell: connected to {addr}, 'help' lists commands");
+
+    let mut editor = match Editor::<ShellHelper, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("shell: cannot initialize line editor: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    editor.set_helper(Some(ShellHelper));
+
+    let mut activities: BTreeMap<ActivityId, Activity> = BTreeMap::new();
+    // Ids are proposed by our side of the protocol; a session-wide
+    // counter keeps repeated commands from colliding.
+    let mut seq: u32 = 0;
+    loop {
+        let line = match editor.readline("pmppt> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Eof | ReadlineError::Interrupted) => return ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("shell: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(&line);
+
+        if words[0] == "quit" || words[0] == "exit" {
+            return ExitCode::SUCCESS;
+        }
+        match dispatch(&mut conn, &mut activities, &mut seq, &words) {
+            Ok(()) => {}
+            Err(ConnError::Proto(e)) => {
+                eprintln!("shell: connection lost: {e}");
+                return ExitCode::FAILURE;
+            }
+            Err(e) => eprintln!("shell: {e}"),
+        }
+    }
+}
+
+fn dispatch(
+    conn: &mut TcpConnection,
+    activities: &mut BTreeMap<ActivityId, Activity>,
+    seq: &mut u32,
+    words: &[&str],
+) -> Result<(), ConnError> {
+    let args: Vec<String> = words[1..].iter().map(|s| s.to_string()).collect();
+    let mut next_id = |name: &str| {
+        *seq += 1;
+        format!("{name}-{seq}")
+    };
+    match words[0] {
+        "help" => println!("{HELP}"),
+        "check" => {
+            let report = conn.check(&args)?;
+            println!(
+                "uname: {}\ncpus: {}\nmemory: {} kB\noutdir writable: {}\nclock offset: {} ms",
+                report.uname,
+                report.cpus,
+                report.mem_total_kb,
+                report.outdir_writable,
+                report.clock_offset_ms
+            );
+            for tool in &report.missing_tools {
+                println!("missing tool: {tool}");
+            }
+        }
+        "poll" => {
+            let (name, period, paths) = match &args[..] {
+                [name, period, paths @ ..] if !paths.is_empty() => (name, period, paths),
+                _ => return usage("poll <name> <period_ms> <path...>"),
+            };
+            let period_ms = period
+                .parse()
+                .map_err(|_| ConnError::Agent(format!("bad period '{period}'")))?;
+            let id = conn.poll(&next_id(name), name, period_ms, paths)?;
+            started(activities, id, name, "poll");
+        }
+        "spawn" => {
+            let [name, cmd @ ..] = &args[..] else {
+                return usage("spawn <name> <cmd...>");
+            };
+            if cmd.is_empty() {
+                return usage("spawn <name> <cmd...>");
+            }
+            let id = conn.spawn_bg(&next_id(name), name, cmd)?;
+            started(activities, id, name, "spawn");
+        }
+        "exec" => {
+            let [name, cmd @ ..] = &args[..] else {
+                return usage("exec <name> <cmd...>");
+            };
+            if cmd.is_empty() {
+                return usage("exec <name> <cmd...>");
+            }
+            let result = conn.spawn_fg(&next_id(name), name, cmd)?;
+            print!("{}", String::from_utf8_lossy(&result.stdout));
+            eprint!("{}", String::from_utf8_lossy(&result.stderr));
+            println!("exit status: {}", result.status);
+        }
+        "stop" => match &args[..] {
+            [all] if all == "all" => {
+                conn.stop_all()?;
+                for activity in activities.values_mut() {
+                    activity.running = false;
+                }
+                println!("all stopped");
+            }
+            [id] => {
+                conn.stop(id)?;
+                if let Some(activity) = activities.get_mut(id.as_str()) {
+                    activity.running = false;
+                }
+                println!("{id} stopped");
+            }
+            _ => return usage("stop <id>|all"),
+        },
+        "status" => {
+            for (id, activity) in activities.iter() {
+                let state = if activity.running { "running" } else { "stopped" };
+                println!("{id:15} {:5} {state:7} {}", activity.kind, activity.name);
+            }
+        }
+        "fetch" => {
+            let (remote, local) = match &args[..] {
+                [remote] => (remote, None),
+                [remote, local] => (remote, Some(local)),
+                _ => return usage("fetch <remote> [local]"),
+            };
+            let cat = ["cat".to_string(), remote.clone()];
+            let result = conn.spawn_fg(&next_id("fetch"), "fetch", &cat)?;
+            if result.status != 0 {
+                eprint!("{}", String::from_utf8_lossy(&result.stderr));
+                return Err(ConnError::Agent(format!("fetch failed: {}", result.status)));
+            }
+            match local {
+                Some(local) => {
+                    std::fs::write(local, &result.stdout)
+                        .map_err(|e| ConnError::Agent(e.to_string()))?;
+                    println!("{} bytes into {local}", result.stdout.len());
+                }
+                None => print!("{}", String::from_utf8_lossy(&result.stdout)),
+            }
+        }
+        "collect" => {
+            let file = match &args[..] {
+                [] => "out.tgz",
+                [file] => file,
+                _ => return usage("collect [file]"),
+            };
+            let archive = conn.collect()?;
+            std::fs::write(file, &archive).map_err(|e| ConnError::Agent(e.to_string()))?;
+            for activity in activities.values_mut() {
+                activity.running = false;
+            }
+            println!("{} bytes into {file}", archive.len());
+        }
+        other => eprintln!("shell: unknown command '{other}', 'help' lists commands"),
+    }
+    Ok(())
+}
+
+fn started(activities: &mut BTreeMap<ActivityId, Activity>, id: ActivityId, name: &str, kind: &'static str) {
+    println!("{id} started");
+    activities.insert(
+        id,
+        Activity {
+            name: name.to_string(),
+            kind,
+            running: true,
+        },
+    );
+}
+
+fn usage(usage: &str) -> Result<(), ConnError> {
+    Err(ConnError::Agent(format!("usage: {usage}")))
+}
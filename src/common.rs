@@ -0,0 +1,60 @@
+//! Small helpers shared by the agent, the controller and the plotter.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Read a whole file into a string.
+pub fn readfile(path: &Path) -> io::Result<String> {
+    fs::read_to_string(path)
+}
+
+/// Create the next numbered subdirectory (`000`, `001`, ...) in `root`,
+/// creating `root` itself if needed.
+pub fn create_next_numeric_dir_in(root: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(root)?;
+
+    let mut next = 0;
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if let Some(n) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) {
+            next = next.max(n + 1);
+        }
+    }
+
+    let path = root.join(format!("{next:03}"));
+    fs::create_dir(&path)?;
+    Ok(path)
+}
+
+/// Current wall-clock time in milliseconds since the Unix epoch.
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock before Unix epoch")
+        .as_millis() as u64
+}
+
+/// Convert epoch milliseconds to a naive UTC timestamp for plotting.
+#[cfg(feature = "parsers")]
+pub fn millis_to_naive(millis: u64) -> chrono::NaiveDateTime {
+    chrono::DateTime::from_timestamp_millis(millis as i64)
+        .expect("timestamp out of range")
+        .naive_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_dirs_are_sequential() {
+        let root = std::env::temp_dir().join(format!("pmppt-test-{}", std::process::id()));
+        let first = create_next_numeric_dir_in(&root).unwrap();
+        let second = create_next_numeric_dir_in(&root).unwrap();
+        assert_eq!(first.file_name().unwrap(), "000");
+        assert_eq!(second.file_name().unwrap(), "001");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,573 @@
+//! Controller-side view of a single agent connection.
+
+use std::fmt;
+use std::io;
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::mpsc;
+
+use crate::agent::{Agent, AgentOps};
+use crate::proto::{self, ActivityId, ProtoError, Request, Response, PROTO_VERSION};
+
+/// Result of a pre-flight check on the agent.
+#[derive(Debug, Clone)]
+pub struct CheckReport {
+    pub missing_tools: Vec<String>,
+    pub outdir_writable: bool,
+    /// Agent clock minus controller clock, estimated at mid-round-trip.
+    pub clock_offset_ms: i64,
+    /// Kernel and architecture of the agent host (`uname -srm`).
+    pub uname: String,
+    pub cpus: usize,
+    pub mem_total_kb: u64,
+}
+
+/// Result of a foreground spawn on the agent.
+#[derive(Debug, Clone)]
+pub struct FgResult {
+    pub status: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum ConnError {
+    /// Transport-level failure.
+    Proto(ProtoError),
+    /// The agent reported an error executing the request.
+    Agent(String),
+    /// The agent answered with a response that does not match the request.
+    Unexpected(String),
+}
+
+impl fmt::Display for ConnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnError::Proto(e) => write!(f, "{e}"),
+            ConnError::Agent(msg) => write!(f, "agent error: {msg}"),
+            ConnError::Unexpected(msg) => write!(f, "unexpected response: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ConnError {}
+
+impl From<ProtoError> for ConnError {
+    fn from(e: ProtoError) -> Self {
+        ConnError::Proto(e)
+    }
+}
+
+/// Control-plane statistics of one agent connection, accumulated per
+/// round trip. On slow links the control plane itself can perturb a
+/// measurement; these numbers make that visible in the manifest.
+#[derive(Debug, Clone, Default)]
+pub struct ConnStats {
+    /// Round trips performed (pipelined batches count as one).
+    pub round_trips: u64,
+    pub total_rtt_ms: u64,
+    pub max_rtt_ms: u64,
+    /// Wire bytes, counted by transports that actually serialize; the
+    /// in-process channel transport moves messages and reports zero.
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Transport-level failures. The connection layer does not retry,
+    /// so any nonzero count means requests were lost, not delayed.
+    pub transport_errors: u64,
+    /// Per-round-trip samples `(start millis, request kind, rtt ms)`,
+    /// kept for the per-agent control-plane debug page.
+    pub samples: Vec<(u64, &'static str, u64)>,
+}
+
+impl ConnStats {
+    /// Mean round-trip time, zero before the first round trip.
+    pub fn avg_rtt_ms(&self) -> u64 {
+        self.total_rtt_ms.checked_div(self.round_trips).unwrap_or(0)
+    }
+
+    fn record(&mut self, kind: &'static str, start_ms: u64) {
+        let rtt = crate::common::now_millis().saturating_sub(start_ms);
+        self.round_trips += 1;
+        self.total_rtt_ms += rtt;
+        self.max_rtt_ms = self.max_rtt_ms.max(rtt);
+        self.samples.push((start_ms, kind, rtt));
+    }
+
+    fn note_error(&mut self, error: &ConnError) {
+        if matches!(error, ConnError::Proto(_)) {
+            self.transport_errors += 1;
+        }
+    }
+}
+
+/// The request kind as recorded in control-plane samples.
+fn req_kind(req: &Request) -> &'static str {
+    match req {
+        Request::Version => "version",
+        Request::Check { .. } => "check",
+        Request::Poll { .. } => "poll",
+        Request::SpawnBg { .. } => "spawn-bg",
+        Request::SpawnFg { .. } => "spawn-fg",
+        Request::Stop { .. } => "stop",
+        Request::StopAll => "stop-all",
+        Request::WaitUntil { .. } => "wait-until",
+        Request::Fetch { .. } => "fetch",
+        Request::Collect => "collect",
+    }
+}
+
+/// Typed request/response operations on an agent connection.
+///
+/// The controller run loop and the activity database are written against
+/// this trait rather than a concrete transport.
+pub trait ConnectionOps {
+    fn check(&mut self, tools: &[String]) -> Result<CheckReport, ConnError>;
+    fn poll(
+        &mut self,
+        id: &str,
+        name: &str,
+        period_ms: u64,
+        paths: &[String],
+    ) -> Result<ActivityId, ConnError>;
+    fn spawn_bg(&mut self, id: &str, name: &str, cmd: &[String]) -> Result<ActivityId, ConnError>;
+    fn spawn_fg(&mut self, id: &str, name: &str, cmd: &[String]) -> Result<FgResult, ConnError>;
+    fn stop(&mut self, id: &str) -> Result<(), ConnError>;
+    fn stop_all(&mut self) -> Result<(), ConnError>;
+    fn fetch(&mut self, path: &str) -> Result<Vec<u8>, ConnError>;
+    fn collect(&mut self) -> Result<Vec<u8>, ConnError>;
+
+    /// Control-plane statistics accumulated so far. Doubles without an
+    /// instrumented transport report the empty default.
+    fn stats(&self) -> ConnStats {
+        ConnStats::default()
+    }
+
+    /// Send several requests back to back, then read all responses.
+    ///
+    /// Pipelining transports pay one round trip for the whole batch. Note
+    /// that the agent still executes the requests in order, so a `SpawnFg`
+    /// in the middle delays everything after it.
+    fn transact_many(&mut self, reqs: &[Request]) -> Result<Vec<Response>, ConnError>;
+}
+
+/// A boxed connection as the controller holds it, one per agent.
+pub type AgentConnection = Box<dyn ConnectionOps + Send>;
+
+/// Message transport under a typed [`Connection`]: moves whole requests
+/// and responses, ordered, without interpreting them.
+pub trait Transport {
+    fn send(&mut self, req: &Request) -> Result<(), ConnError>;
+    fn recv(&mut self) -> Result<Response, ConnError>;
+
+    /// Wire bytes sent and received since connecting. Zero for
+    /// transports that move messages without serializing them.
+    fn io_bytes(&self) -> (u64, u64) {
+        (0, 0)
+    }
+
+    /// Receive one message of a chunk stream, appending its payload to
+    /// `sink`; `Ok(true)` means the stream is complete. Transports that
+    /// can decode in place override this to skip the per-chunk copy.
+    fn recv_chunk(&mut self, sink: &mut Vec<u8>) -> Result<bool, ConnError> {
+        match self.recv()? {
+            Response::Chunk { data, last } => {
+                if sink.is_empty() {
+                    *sink = data;
+                } else {
+                    sink.extend_from_slice(&data);
+                }
+                Ok(last)
+            }
+            Response::Error { message } => Err(ConnError::Agent(message)),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+}
+
+/// Typed agent connection over any [`Transport`].
+pub struct Connection<T: Transport> {
+    transport: T,
+    stats: ConnStats,
+}
+
+impl<T: Transport> Connection<T> {
+    /// Perform the version handshake, consuming the fresh connection.
+    fn handshake(mut self) -> Result<Self, ConnError> {
+        match self.transact(&Request::Version)? {
+            Response::Version { version } if version == PROTO_VERSION => Ok(self),
+            Response::Version { version } => Err(ConnError::Unexpected(format!(
+                "protocol version mismatch: agent {version}, controller {PROTO_VERSION}"
+            ))),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn transact(&mut self, req: &Request) -> Result<Response, ConnError> {
+        let start = crate::common::now_millis();
+        let sent_recvd = self.transport.send(req).and_then(|()| self.transport.recv());
+        let resp = match sent_recvd {
+            Ok(resp) => resp,
+            Err(e) => {
+                self.stats.note_error(&e);
+                return Err(e);
+            }
+        };
+        self.stats.record(req_kind(req), start);
+        if let Response::Error { message } = resp {
+            return Err(ConnError::Agent(message));
+        }
+        Ok(resp)
+    }
+
+    /// Assemble a streamed Fetch/Collect reply from its chunk sequence.
+    /// The agent sends bounded chunks; only the controller side holds
+    /// the whole transfer.
+    fn recv_chunked(&mut self) -> Result<Vec<u8>, ConnError> {
+        let mut data = Vec::new();
+        while !self.transport.recv_chunk(&mut data)? {}
+        Ok(data)
+    }
+}
+
+/// The TCP msgpack transport, for externally started agents.
+pub struct TcpTransport {
+    stream: TcpStream,
+    /// Receive frame buffer, reused across messages so steady traffic
+    /// settles on one allocation.
+    buf: Vec<u8>,
+    /// Wire byte counters for the control-plane statistics.
+    sent: u64,
+    received: u64,
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, req: &Request) -> Result<(), ConnError> {
+        self.sent += proto::send_msg(&mut self.stream, req)? as u64;
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Response, ConnError> {
+        proto::recv_frame(&mut self.stream, &mut self.buf)?;
+        self.received += self.buf.len() as u64 + 4;
+        Ok(proto::decode(&self.buf)?)
+    }
+
+    fn io_bytes(&self) -> (u64, u64) {
+        (self.sent, self.received)
+    }
+
+    /// Decode the chunk borrowed from the frame buffer and append it to
+    /// `sink` directly — large transfers skip the intermediate `Vec`
+    /// an owned [`Response`] would allocate per message.
+    fn recv_chunk(&mut self, sink: &mut Vec<u8>) -> Result<bool, ConnError> {
+        proto::recv_frame(&mut self.stream, &mut self.buf)?;
+        self.received += self.buf.len() as u64 + 4;
+        match proto::decode::<crate::proto::ResponseRef>(&self.buf)? {
+            crate::proto::ResponseRef::Chunk { data, last } => {
+                sink.extend_from_slice(data);
+                Ok(last)
+            }
+            crate::proto::ResponseRef::Error { message } => Err(ConnError::Agent(message)),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+}
+
+/// Agent connection over the TCP msgpack transport.
+pub type TcpConnection = Connection<TcpTransport>;
+
+impl TcpConnection {
+    /// Connect to an agent and perform the version handshake.
+    pub fn connect(addr: &str) -> Result<Self, ConnError> {
+        let stream = TcpStream::connect(addr).map_err(ProtoError::Io)?;
+        Connection {
+            transport: TcpTransport {
+                stream,
+                buf: Vec::new(),
+                sent: 0,
+                received: 0,
+            },
+            stats: ConnStats::default(),
+        }
+        .handshake()
+    }
+}
+
+/// In-memory channel transport: the agent session runs on a thread of
+/// this very process. Used by the localhost auto-agent mode and by
+/// integration tests, exercising the full request/response flow without
+/// TCP.
+pub struct ChannelTransport {
+    tx: mpsc::Sender<Request>,
+    rx: mpsc::Receiver<Response>,
+}
+
+impl ChannelTransport {
+    fn gone() -> ConnError {
+        ConnError::Proto(ProtoError::Io(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "in-process agent is gone",
+        )))
+    }
+}
+
+impl Transport for ChannelTransport {
+    fn send(&mut self, req: &Request) -> Result<(), ConnError> {
+        self.tx.send(req.clone()).map_err(|_| Self::gone())
+    }
+
+    fn recv(&mut self) -> Result<Response, ConnError> {
+        self.rx.recv().map_err(|_| Self::gone())
+    }
+}
+
+/// Agent connection over the in-memory channel transport.
+pub type ChannelConnection = Connection<ChannelTransport>;
+
+impl ChannelConnection {
+    /// Start a real agent session under `root` on an in-process thread
+    /// and connect to it. Dropping the connection ends the session; the
+    /// thread stops whatever is still running, like a TCP hang-up does.
+    pub fn start(root: &Path) -> Result<Self, ConnError> {
+        let mut agent = Agent::new(root).map_err(|e| ConnError::Proto(ProtoError::Io(e)))?;
+        let (req_tx, req_rx) = mpsc::channel::<Request>();
+        let (resp_tx, resp_rx) = mpsc::channel::<Response>();
+        std::thread::spawn(move || {
+            for req in req_rx {
+                // Transfers stream in bounded chunks, like the TCP
+                // session loop does.
+                let delivered = match req {
+                    Request::Fetch { path } => crate::agent::FileChunks::new(Path::new(&path))
+                        .all(|resp| resp_tx.send(resp).is_ok()),
+                    Request::Collect => match agent.collect() {
+                        Ok(archive) => crate::agent::FileChunks::new(&archive)
+                            .all(|resp| resp_tx.send(resp).is_ok()),
+                        Err(e) => resp_tx
+                            .send(Response::Error {
+                                message: e.to_string(),
+                            })
+                            .is_ok(),
+                    },
+                    req => resp_tx.send(agent.handle(req)).is_ok(),
+                };
+                if !delivered {
+                    break;
+                }
+            }
+            let _ = agent.handle(Request::StopAll);
+        });
+        Connection {
+            transport: ChannelTransport {
+                tx: req_tx,
+                rx: resp_rx,
+            },
+            stats: ConnStats::default(),
+        }
+        .handshake()
+    }
+}
+
+impl<T: Transport> ConnectionOps for Connection<T> {
+    fn check(&mut self, tools: &[String]) -> Result<CheckReport, ConnError> {
+        let before = crate::common::now_millis();
+        let resp = self.transact(&Request::Check {
+            tools: tools.to_vec(),
+        })?;
+        let after = crate::common::now_millis();
+        match resp {
+            Response::Checked {
+                missing_tools,
+                outdir_writable,
+                agent_millis,
+                uname,
+                cpus,
+                mem_total_kb,
+            } => Ok(CheckReport {
+                missing_tools,
+                outdir_writable,
+                clock_offset_ms: agent_millis as i64 - ((before + after) / 2) as i64,
+                uname,
+                cpus,
+                mem_total_kb,
+            }),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn poll(
+        &mut self,
+        id: &str,
+        name: &str,
+        period_ms: u64,
+        paths: &[String],
+    ) -> Result<ActivityId, ConnError> {
+        let req = Request::Poll {
+            id: id.to_string(),
+            name: name.to_string(),
+            period_ms,
+            paths: paths.to_vec(),
+            // Interactive polls stay in the text format: the shell reads
+            // them back with tail-style fetches.
+            binary: false,
+        };
+        match self.transact(&req)? {
+            Response::Started { id } => Ok(id),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn spawn_bg(&mut self, id: &str, name: &str, cmd: &[String]) -> Result<ActivityId, ConnError> {
+        let req = Request::SpawnBg {
+            id: id.to_string(),
+            name: name.to_string(),
+            cmd: cmd.to_vec(),
+        };
+        match self.transact(&req)? {
+            Response::Started { id } => Ok(id),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn spawn_fg(&mut self, id: &str, name: &str, cmd: &[String]) -> Result<FgResult, ConnError> {
+        let req = Request::SpawnFg {
+            id: id.to_string(),
+            name: name.to_string(),
+            cmd: cmd.to_vec(),
+        };
+        match self.transact(&req)? {
+            Response::Finished {
+                status,
+                stdout,
+                stderr,
+            } => Ok(FgResult {
+                status,
+                stdout,
+                stderr,
+            }),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn stop(&mut self, id: &str) -> Result<(), ConnError> {
+        let req = Request::Stop { id: id.to_string() };
+        match self.transact(&req)? {
+            Response::Stopped { .. } => Ok(()),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn stop_all(&mut self) -> Result<(), ConnError> {
+        match self.transact(&Request::StopAll)? {
+            Response::AllStopped => Ok(()),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn fetch(&mut self, path: &str) -> Result<Vec<u8>, ConnError> {
+        let req = Request::Fetch {
+            path: path.to_string(),
+        };
+        let start = crate::common::now_millis();
+        let data = self
+            .transport
+            .send(&req)
+            .and_then(|()| self.recv_chunked())
+            .inspect_err(|e| self.stats.note_error(e))?;
+        self.stats.record("fetch", start);
+        Ok(data)
+    }
+
+    fn collect(&mut self) -> Result<Vec<u8>, ConnError> {
+        let start = crate::common::now_millis();
+        let data = self
+            .transport
+            .send(&Request::Collect)
+            .and_then(|()| self.recv_chunked())
+            .inspect_err(|e| self.stats.note_error(e))?;
+        self.stats.record("collect", start);
+        Ok(data)
+    }
+
+    fn stats(&self) -> ConnStats {
+        let mut stats = self.stats.clone();
+        (stats.bytes_sent, stats.bytes_received) = self.transport.io_bytes();
+        stats
+    }
+
+    fn transact_many(&mut self, reqs: &[Request]) -> Result<Vec<Response>, ConnError> {
+        let start = crate::common::now_millis();
+        for req in reqs {
+            if let Err(e) = self.transport.send(req) {
+                self.stats.note_error(&e);
+                return Err(e);
+            }
+        }
+        let mut resps = Vec::with_capacity(reqs.len());
+        for _ in reqs {
+            let resp = match self.transport.recv() {
+                Ok(resp) => resp,
+                Err(e) => {
+                    self.stats.note_error(&e);
+                    return Err(e);
+                }
+            };
+            resps.push(resp);
+        }
+        self.stats.record("batch", start);
+        for resp in &resps {
+            if let Response::Error { message } = resp {
+                return Err(ConnError::Agent(message.clone()));
+            }
+        }
+        Ok(resps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_connection_runs_a_full_session() {
+        let root = std::env::temp_dir().join(format!("pmppt-chan-{}", std::process::id()));
+        let mut conn = ChannelConnection::start(&root).unwrap();
+
+        let cmd = ["echo", "hello"].map(str::to_string);
+        let result = conn.spawn_fg("greet", "echo", &cmd).unwrap();
+        assert_eq!(result.status, 0);
+        assert_eq!(String::from_utf8_lossy(&result.stdout).trim(), "hello");
+
+        conn.stop_all().unwrap();
+
+        // Handshake, spawn and stop each cost one recorded round trip;
+        // the channel transport has no wire, so bytes stay zero.
+        let stats = conn.stats();
+        assert_eq!(stats.round_trips, 3);
+        assert_eq!(stats.samples.len(), 3);
+        assert_eq!(stats.transport_errors, 0);
+        assert_eq!((stats.bytes_sent, stats.bytes_received), (0, 0));
+        assert!(stats.total_rtt_ms >= stats.max_rtt_ms);
+
+        drop(conn);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn fetch_reassembles_the_chunk_stream() {
+        let root = std::env::temp_dir().join(format!("pmppt-fetch-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("sample.bin");
+        std::fs::write(&file, b"chunked transfer payload").unwrap();
+
+        let mut conn = ChannelConnection::start(&root).unwrap();
+        let content = conn.fetch(file.to_str().unwrap()).unwrap();
+        assert_eq!(content, b"chunked transfer payload");
+        assert!(matches!(
+            conn.fetch("/nonexistent/path"),
+            Err(ConnError::Agent(_))
+        ));
+
+        drop(conn);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
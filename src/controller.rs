@@ -0,0 +1,1002 @@
+//! The run orchestrator: drives the configured stages over all agents.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::activities::{self, Started};
+use crate::cfgparse::Config;
+use crate::connection::{
+    AgentConnection, ChannelConnection, ConnError, ConnStats, FgResult, TcpConnection,
+};
+use crate::storage::{Key, Storage};
+
+#[derive(Debug)]
+pub enum RunError {
+    /// The configuration references unknown agents or is inconsistent.
+    Config(String),
+    /// An agent could not be connected.
+    Connect { agent: String, error: ConnError },
+    /// An activity failed during a stage.
+    Stage {
+        stage: String,
+        agent: String,
+        error: ConnError,
+    },
+    /// The pre-flight health check found problems on the agents.
+    Preflight(Vec<String>),
+    /// A controller-host pre/post hook failed.
+    Hook {
+        stage: String,
+        command: String,
+        error: String,
+    },
+    /// Collecting results from an agent failed.
+    Collect { agent: String, error: String },
+    /// Writing run output on the controller host failed.
+    Io(std::io::Error),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Config(msg) => write!(f, "configuration error: {msg}"),
+            RunError::Connect { agent, error } => {
+                write!(f, "cannot connect agent '{agent}': {error}")
+            }
+            RunError::Stage {
+                stage,
+                agent,
+                error,
+            } => write!(f, "stage '{stage}' failed on agent '{agent}': {error}"),
+            RunError::Hook {
+                stage,
+                command,
+                error,
+            } => write!(f, "stage '{stage}' hook '{command}' failed: {error}"),
+            RunError::Preflight(problems) => {
+                write!(f, "pre-flight check failed: {}", problems.join("; "))
+            }
+            RunError::Collect { agent, error } => {
+                write!(f, "collect from agent '{agent}' failed: {error}")
+            }
+            RunError::Io(e) => write!(f, "output error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+impl From<std::io::Error> for RunError {
+    fn from(e: std::io::Error) -> Self {
+        RunError::Io(e)
+    }
+}
+
+/// Process exit codes of the controller, one per failure class, so CI
+/// systems can react without parsing stderr.
+pub mod exit_code {
+    pub const CONFIG: u8 = 2;
+    pub const CONNECT: u8 = 3;
+    pub const STAGE: u8 = 4;
+    pub const COLLECT: u8 = 5;
+    pub const IO: u8 = 6;
+    pub const PREFLIGHT: u8 = 7;
+}
+
+impl RunError {
+    /// The process exit code corresponding to this failure.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            RunError::Config(_) => exit_code::CONFIG,
+            RunError::Connect { .. } => exit_code::CONNECT,
+            RunError::Stage { .. } => exit_code::STAGE,
+            RunError::Hook { .. } => exit_code::STAGE,
+            RunError::Collect { .. } => exit_code::COLLECT,
+            RunError::Io(_) => exit_code::IO,
+            RunError::Preflight(_) => exit_code::PREFLIGHT,
+        }
+    }
+
+    /// Machine-readable description of the failure for `failure.json`.
+    pub fn failure_report(&self) -> serde_json::Value {
+        let (kind, agent, stage) = match self {
+            RunError::Config(_) => ("config", None, None),
+            RunError::Connect { agent, .. } => ("connect", Some(agent.as_str()), None),
+            RunError::Stage { agent, stage, .. } => {
+                ("stage", Some(agent.as_str()), Some(stage.as_str()))
+            }
+            RunError::Hook { stage, .. } => ("hook", None, Some(stage.as_str())),
+            RunError::Collect { agent, .. } => ("collect", Some(agent.as_str()), None),
+            RunError::Io(_) => ("io", None, None),
+            RunError::Preflight(_) => ("preflight", None, None),
+        };
+        let mut report = serde_json::json!({
+            "kind": kind,
+            "agent": agent,
+            "stage": stage,
+            "message": self.to_string(),
+            "exit_code": self.exit_code(),
+        });
+        if let RunError::Preflight(problems) = self {
+            report["problems"] = serde_json::json!(problems);
+        }
+        report
+    }
+}
+
+/// Lifecycle hooks of a run, for embedders that want their own progress
+/// UIs, metrics or notifications without patching the run loop. Every
+/// hook has an empty default, so implementors pick what they need. Stage
+/// chains run on worker threads, hence the `Sync` bound.
+pub trait RunObserver: Sync {
+    fn on_stage_start(&self, _stage: &str) {}
+    fn on_activity_start(&self, _agent: &str, _activity: &str, _id: &str) {}
+    fn on_activity_stop(&self, _agent: &str, _id: &str) {}
+    fn on_agent_error(&self, _agent: &str, _error: &str) {}
+    fn on_collect_progress(&self, _agent: &str, _bytes: usize) {}
+}
+
+/// The observer used when nobody is watching.
+struct Silent;
+
+impl RunObserver for Silent {}
+
+/// Execute a full run: connect agents, drive stages, collect results and
+/// write the manifest into `outdir`.
+pub fn run(config: &Config, outdir: &Path) -> Result<(), RunError> {
+    run_observed(config, outdir, &Silent)
+}
+
+/// Like [`run`], reporting lifecycle events to the given observer.
+pub fn run_observed(
+    config: &Config,
+    outdir: &Path,
+    observer: &dyn RunObserver,
+) -> Result<(), RunError> {
+    for stage in &config.stages {
+        for agent in stage.chains.keys() {
+            if !config.setup.agents.iter().any(|a| &a.name == agent) {
+                return Err(RunError::Config(format!(
+                    "stage '{}' references unknown agent '{agent}'",
+                    stage.name
+                )));
+            }
+        }
+    }
+
+    fs::create_dir_all(outdir)?;
+    let storage_path = outdir.join("storage.json");
+    // Restore artifact state from a previous attempt of this run, if any,
+    // so a controller crash does not lose what earlier stages published.
+    let mut storage = if storage_path.exists() {
+        Storage::load(&storage_path)?
+    } else {
+        Storage::new()
+    };
+
+    let mut conns: BTreeMap<String, Mutex<AgentConnection>> = BTreeMap::new();
+    for agent in &config.setup.agents {
+        let connected: Result<AgentConnection, ConnError> = match &agent.addr {
+            Some(addr) => {
+                eprintln!("controller: connecting agent '{}' at {addr}", agent.name);
+                TcpConnection::connect(addr).map(|conn| Box::new(conn) as AgentConnection)
+            }
+            None if agent.local => {
+                // No loopback socket: the agent session is served from a
+                // thread of this very process over in-memory channels.
+                eprintln!("controller: starting in-process agent '{}'", agent.name);
+                let root = outdir.join(format!("{}.agent", agent.name));
+                ChannelConnection::start(&root).map(|conn| Box::new(conn) as AgentConnection)
+            }
+            None => {
+                return Err(RunError::Config(format!(
+                    "agent '{}' has neither addr nor local: true",
+                    agent.name
+                )))
+            }
+        };
+        let conn = connected.map_err(|error| {
+            observer.on_agent_error(&agent.name, &error.to_string());
+            RunError::Connect {
+                agent: agent.name.clone(),
+                error,
+            }
+        })?;
+        conns.insert(agent.name.clone(), Mutex::new(conn));
+    }
+
+    let conns = std::sync::Arc::new(conns);
+    preflight(config, &conns, &mut storage)?;
+
+    // The seed pins the load generators' random patterns; recording it
+    // (configured or generated) lets a run be replayed exactly.
+    let seed = config.setup.seed.unwrap_or_else(crate::common::now_millis);
+    storage.set_or_replace(&Key::run("seed"), &seed);
+    storage.save(&storage_path)?;
+
+    // Samples agents between our own requests; dropped (and thereby
+    // stopped) on any exit from this function.
+    let _dashboard = crate::live::maybe_start(&conns);
+
+    let mut marks: BTreeMap<String, u64> = BTreeMap::new();
+    let mut stage_times = Vec::new();
+    let mut fg_results: Vec<(String, crate::proto::ActivityId, FgResult)> = Vec::new();
+    for stage in &config.stages {
+        eprintln!("controller: stage '{}'", stage.name);
+        observer.on_stage_start(&stage.name);
+        let start_ms = crate::common::now_millis();
+        if let Err(error) = run_stage(
+            stage,
+            &conns,
+            seed,
+            config.setup.poll_format,
+            config.setup.concurrency,
+            &mut storage,
+            &mut marks,
+            &mut fg_results,
+            observer,
+        ) {
+            // Losing all monitoring data exactly when something went
+            // wrong is the worst outcome for a profiler: salvage what
+            // the reachable agents have before reporting the failure.
+            recovery_collect(
+                config, &conns, outdir, seed, &mut storage, &marks, &fg_results, observer,
+            );
+            return Err(error);
+        }
+        stage_times.push(serde_json::json!({
+            "name": stage.name,
+            "start_ms": start_ms,
+            "stop_ms": crate::common::now_millis(),
+        }));
+        storage.save(&storage_path)?;
+        fs::write(
+            outdir.join("marks.json"),
+            serde_json::to_string_pretty(&marks).expect("serializable"),
+        )?;
+        write_run_meta(config, outdir, &storage, &stage_times)?;
+    }
+
+    for agent in &config.setup.agents {
+        eprintln!("controller: collecting agent '{}'", agent.name);
+        let mut conn = conns[&agent.name].lock().unwrap();
+        let (agent_dir, archive_name) = agent_output(config, outdir, &agent.name, seed);
+        collect_agent(
+            &agent.name,
+            &mut conn,
+            &agent_dir,
+            &archive_name,
+            &mut storage,
+            &fg_results,
+            observer,
+        )?;
+    }
+
+    storage.save(&storage_path)?;
+    let manifest = serde_json::json!({ "artifacts": storage.dump(), "marks": marks });
+    fs::write(
+        outdir.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest).expect("serializable"),
+    )?;
+    Ok(())
+}
+
+/// Where one agent's results land: the agent directory and the archive
+/// file name inside it, from the `agent_dir`/`archive_name` templates
+/// with their `outdir/<agent>/out.tgz` defaults.
+fn agent_output(config: &Config, outdir: &Path, agent: &str, seed: u64) -> (PathBuf, String) {
+    let run_label = config.setup.run_label.clone().unwrap_or_else(|| {
+        outdir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let expand = |template: &str| {
+        template
+            .replace("{agent}", agent)
+            .replace("{run_label}", &run_label)
+            .replace("{seed}", &seed.to_string())
+    };
+    let dir = outdir.join(expand(config.setup.agent_dir.as_deref().unwrap_or("{agent}")));
+    let archive = expand(config.setup.archive_name.as_deref().unwrap_or("out.tgz"));
+    (dir, archive)
+}
+
+/// Collect one agent's session archive into its output directory along
+/// with the per-agent report pages and manifest entries.
+fn collect_agent(
+    agent: &str,
+    conn: &mut AgentConnection,
+    agent_dir: &Path,
+    archive_name: &str,
+    storage: &mut Storage,
+    fg_results: &[(String, crate::proto::ActivityId, FgResult)],
+    observer: &dyn RunObserver,
+) -> Result<(), RunError> {
+    let archive = conn.collect().map_err(|e| {
+        observer.on_agent_error(agent, &e.to_string());
+        RunError::Collect {
+            agent: agent.to_string(),
+            error: e.to_string(),
+        }
+    })?;
+    observer.on_collect_progress(agent, archive.len());
+
+    // Templated directories may nest; only the leaf must not pre-exist.
+    if let Some(parent) = agent_dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::create_dir(agent_dir).map_err(|e| match e.kind() {
+        // Duplicate agent names or leftovers from a previous attempt
+        // would silently mix two agents' results otherwise.
+        std::io::ErrorKind::AlreadyExists => RunError::Collect {
+            agent: agent.to_string(),
+            error: format!("output directory {} already exists", agent_dir.display()),
+        },
+        _ => RunError::Io(e),
+    })?;
+    fs::write(agent_dir.join(archive_name), &archive)?;
+    storage.set_or_replace(&Key::agent(agent, "archive_bytes"), &archive.len());
+    write_fg_results(agent_dir, agent, fg_results)?;
+
+    // Control-plane statistics: summarized in the manifest, per
+    // round trip on the debug page.
+    let stats = conn.stats();
+    storage.set_or_replace(
+        &Key::agent(agent, "control_plane"),
+        &serde_json::json!({
+            "round_trips": stats.round_trips,
+            "avg_rtt_ms": stats.avg_rtt_ms(),
+            "max_rtt_ms": stats.max_rtt_ms,
+            "bytes_sent": stats.bytes_sent,
+            "bytes_received": stats.bytes_received,
+            "transport_errors": stats.transport_errors,
+        }),
+    );
+    write_control_page(agent_dir, agent, &stats)?;
+    Ok(())
+}
+
+/// Best-effort salvage after a stage failure: stop whatever is still
+/// running on every reachable agent, collect their archives, and write
+/// the manifest marked as partial. Errors here are logged and swallowed
+/// — the stage failure is what gets reported.
+#[allow(clippy::too_many_arguments)]
+fn recovery_collect(
+    config: &Config,
+    conns: &BTreeMap<String, Mutex<AgentConnection>>,
+    outdir: &Path,
+    seed: u64,
+    storage: &mut Storage,
+    marks: &BTreeMap<String, u64>,
+    fg_results: &[(String, crate::proto::ActivityId, FgResult)],
+    observer: &dyn RunObserver,
+) {
+    eprintln!("controller: stage failed, collecting partial data");
+    for agent in &config.setup.agents {
+        let mut conn = conns[&agent.name].lock().unwrap();
+        if let Err(e) = conn.stop_all() {
+            eprintln!("controller: stop-all on agent '{}' failed: {e}", agent.name);
+            continue;
+        }
+        let (agent_dir, archive_name) = agent_output(config, outdir, &agent.name, seed);
+        if let Err(e) = collect_agent(
+            &agent.name,
+            &mut conn,
+            &agent_dir,
+            &archive_name,
+            storage,
+            fg_results,
+            observer,
+        ) {
+            eprintln!("controller: recovery collect of agent '{}' failed: {e}", agent.name);
+        }
+    }
+    if let Err(e) = storage.save(&outdir.join("storage.json")) {
+        eprintln!("controller: cannot save storage: {e}");
+    }
+    let manifest = serde_json::json!({
+        "artifacts": storage.dump(),
+        "marks": marks,
+        // Tells the plotter and any consumer that the run did not finish:
+        // the data covers only the stages up to the failure.
+        "partial": true,
+    });
+    if let Err(e) = fs::write(
+        outdir.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest).expect("serializable"),
+    ) {
+        eprintln!("controller: cannot write manifest: {e}");
+    }
+}
+
+/// Write `run.json` describing what is being run: the config text, the
+/// agents with the hardware facts gathered at pre-flight, and the stage
+/// timeline so far. Rewritten after every stage so a crashed run still
+/// carries its metadata.
+fn write_run_meta(
+    config: &Config,
+    outdir: &Path,
+    storage: &Storage,
+    stage_times: &[serde_json::Value],
+) -> Result<(), RunError> {
+    let agents: Vec<serde_json::Value> = config
+        .setup
+        .agents
+        .iter()
+        .map(|agent| {
+            serde_json::json!({
+                "name": agent.name,
+                "addr": agent.addr,
+                "local": agent.local,
+                "uname": storage.get_as::<String>(&Key::agent(&agent.name, "uname")).ok(),
+                "cpus": storage.get_as::<usize>(&Key::agent(&agent.name, "cpus")).ok(),
+                "mem_total_kb": storage
+                    .get_as::<u64>(&Key::agent(&agent.name, "mem_total_kb"))
+                    .ok(),
+                "governor": storage
+                    .get_as::<String>(&Key::agent(&agent.name, "governor"))
+                    .ok(),
+                "smt": storage.get_as::<String>(&Key::agent(&agent.name, "smt")).ok(),
+                "cmdline": storage
+                    .get_as::<String>(&Key::agent(&agent.name, "cmdline"))
+                    .ok(),
+            })
+        })
+        .collect();
+    let meta = serde_json::json!({
+        "config": config.raw,
+        "seed": storage.get_as::<u64>(&Key::run("seed")).ok(),
+        "agents": agents,
+        "stages": stage_times,
+    });
+    fs::write(
+        outdir.join("run.json"),
+        serde_json::to_string_pretty(&meta).expect("serializable"),
+    )?;
+    Ok(())
+}
+
+/// Largest tolerated agent/controller clock skew.
+const MAX_CLOCK_OFFSET_MS: i64 = 5000;
+
+/// How far past the barrier rendezvous the agreed resume deadline is
+/// placed, covering worker scheduling jitter and the dispatch of the
+/// batch that follows.
+const BARRIER_LEAD_MS: u64 = 100;
+
+/// Write `fgresults.html` into an agent output directory: one section
+/// per completed foreground command with its exit status, stdout and
+/// stderr. The page sits next to the plotter-generated ones and is
+/// picked up by `report.html` the same way, so fg output is finally
+/// labelled and reachable instead of being buried in the agent archive.
+fn write_fg_results(
+    agent_dir: &Path,
+    agent: &str,
+    fg_results: &[(String, crate::proto::ActivityId, FgResult)],
+) -> Result<(), RunError> {
+    use std::io::Write;
+
+    let results: Vec<_> = fg_results.iter().filter(|(a, _, _)| a == agent).collect();
+    if results.is_empty() {
+        return Ok(());
+    }
+    let mut out = std::io::BufWriter::new(fs::File::create(agent_dir.join("fgresults.html"))?);
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>foreground results: {agent}</title></head><body>")?;
+    writeln!(out, "<h1>Foreground command results</h1>")?;
+    for (_, id, result) in results {
+        writeln!(out, "<h2>{id} (exit status {})</h2>", result.status)?;
+        for (label, bytes) in [("stdout", &result.stdout), ("stderr", &result.stderr)] {
+            if bytes.is_empty() {
+                continue;
+            }
+            let text = html_escape(&String::from_utf8_lossy(bytes));
+            writeln!(out, "<h3>{label}</h3>")?;
+            writeln!(out, "<pre>{text}</pre>")?;
+        }
+    }
+    writeln!(out, "</body></html>")?;
+    Ok(())
+}
+
+/// Write `control.html` into the agent output directory: one row per
+/// control-plane round trip, so slow-link overheads can be traced to
+/// specific requests. Picked up by the report like any other page.
+fn write_control_page(agent_dir: &Path, agent: &str, stats: &ConnStats) -> Result<(), RunError> {
+    use std::io::Write;
+
+    let mut out = std::io::BufWriter::new(fs::File::create(agent_dir.join("control.html"))?);
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>control plane: {agent}</title></head><body>")?;
+    writeln!(out, "<h1>Control plane round trips</h1>")?;
+    writeln!(
+        out,
+        "<p>{} round trips, avg {} ms, max {} ms, {} bytes sent, \
+         {} bytes received, {} transport errors</p>",
+        stats.round_trips,
+        stats.avg_rtt_ms(),
+        stats.max_rtt_ms,
+        stats.bytes_sent,
+        stats.bytes_received,
+        stats.transport_errors,
+    )?;
+    writeln!(out, "<table border=\"1\" cellpadding=\"4\">")?;
+    writeln!(out, "<tr><th>offset (s)</th><th>request</th><th>rtt (ms)</th></tr>")?;
+    let first = stats.samples.first().map_or(0, |(start, _, _)| *start);
+    for (start, kind, rtt) in &stats.samples {
+        writeln!(
+            out,
+            "<tr><td>{:.3}</td><td>{kind}</td><td>{rtt}</td></tr>",
+            (start - first) as f64 / 1000.0,
+        )?;
+    }
+    writeln!(out, "</table>")?;
+    writeln!(out, "</body></html>")?;
+    Ok(())
+}
+
+/// Escape command output for embedding in the results page.
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Pick the artifact value out of a fg launch stdout: the first capture
+/// group of the extract regex, or the trimmed stdout without one. The
+/// regex was validated at config parse time, and a non-matching output
+/// is an error — a missing artifact would only fail later and further
+/// from the cause.
+fn extract_artifact(stdout: &str, extract: &Option<String>) -> Result<String, ConnError> {
+    let Some(extract) = extract else {
+        return Ok(stdout.trim().to_string());
+    };
+    let re = regex::Regex::new(extract).expect("validated at parse time");
+    re.captures(stdout)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().to_string())
+        .ok_or_else(|| {
+            ConnError::Unexpected(format!("launch artifact regex '{extract}' matched nothing"))
+        })
+}
+
+/// Verify every agent before the first stage: required tools for the
+/// configured activities, writable outdir and clock offset. All problems
+/// across all agents are reported at once. Also records the environment
+/// facts (governor, SMT, kernel command line) for the run metadata.
+fn preflight(
+    config: &Config,
+    conns: &BTreeMap<String, Mutex<AgentConnection>>,
+    storage: &mut Storage,
+) -> Result<(), RunError> {
+    let mut problems = Vec::new();
+    for agent in &config.setup.agents {
+        let mut tools: Vec<String> = config
+            .stages
+            .iter()
+            .filter_map(|stage| stage.chains.get(&agent.name))
+            .flatten()
+            .flat_map(activities::required_tools)
+            .collect();
+        tools.sort();
+        tools.dedup();
+
+        let mut conn = conns[&agent.name].lock().unwrap();
+        let report = conn.check(&tools).map_err(|error| RunError::Connect {
+            agent: agent.name.clone(),
+            error,
+        })?;
+
+        // Environment facts that decide whether two runs are comparable:
+        // CPU governor, SMT state and the kernel command line. Best
+        // effort — a file missing on this kernel is not a problem, it is
+        // recorded as absent.
+        for (field, path) in [
+            (
+                "governor",
+                "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+            ),
+            ("smt", "/sys/devices/system/cpu/smt/control"),
+            ("cmdline", "/proc/cmdline"),
+        ] {
+            if let Ok(content) = conn.fetch(path) {
+                let value = String::from_utf8_lossy(&content).trim().to_string();
+                storage.set_or_replace(&Key::agent(&agent.name, field), &value);
+            }
+        }
+        drop(conn);
+
+        for tool in &report.missing_tools {
+            problems.push(format!("agent '{}': tool '{tool}' not found", agent.name));
+        }
+        if !report.outdir_writable {
+            problems.push(format!("agent '{}': outdir not writable", agent.name));
+        }
+        // Recorded for the plotter, which corrects multi-agent timelines
+        // onto the controller clock.
+        storage.set_or_replace(
+            &Key::agent(&agent.name, "clock_offset_ms"),
+            &report.clock_offset_ms,
+        );
+        // Hardware facts for the run metadata page.
+        storage.set_or_replace(&Key::agent(&agent.name, "uname"), &report.uname);
+        storage.set_or_replace(&Key::agent(&agent.name, "cpus"), &report.cpus);
+        storage.set_or_replace(&Key::agent(&agent.name, "mem_total_kb"), &report.mem_total_kb);
+        if report.clock_offset_ms.abs() > MAX_CLOCK_OFFSET_MS {
+            problems.push(format!(
+                "agent '{}': clock offset {} ms exceeds {} ms",
+                agent.name, report.clock_offset_ms, MAX_CLOCK_OFFSET_MS
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(RunError::Preflight(problems))
+    }
+}
+
+/// Rendezvous point for barrier entries. Unlike [`std::sync::Barrier`]
+/// it can be poisoned: a chain that fails never arrives, so its worker
+/// poisons the rendezvous instead, waking every waiter and letting
+/// later arrivals pass straight through rather than blocking forever on
+/// a stage that is already failing.
+struct BarrierSync {
+    total: usize,
+    inner: Mutex<BarrierSyncInner>,
+    cond: Condvar,
+}
+
+struct BarrierSyncInner {
+    arrived: usize,
+    generation: u64,
+    deadline: u64,
+    poisoned: bool,
+}
+
+impl BarrierSync {
+    fn new(total: usize) -> BarrierSync {
+        BarrierSync {
+            total,
+            inner: Mutex::new(BarrierSyncInner {
+                arrived: 0,
+                generation: 0,
+                deadline: 0,
+                poisoned: false,
+            }),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Block until every chain has arrived and return the resume
+    /// deadline picked by the last arrival, or `None` if the rendezvous
+    /// was poisoned because some chain failed.
+    fn wait(&self) -> Option<u64> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.poisoned {
+            return None;
+        }
+        inner.arrived += 1;
+        if inner.arrived == self.total {
+            inner.arrived = 0;
+            inner.generation += 1;
+            inner.deadline = crate::common::now_millis() + BARRIER_LEAD_MS;
+            self.cond.notify_all();
+            return Some(inner.deadline);
+        }
+        let generation = inner.generation;
+        loop {
+            inner = self.cond.wait(inner).unwrap();
+            if inner.poisoned {
+                return None;
+            }
+            if inner.generation != generation {
+                return Some(inner.deadline);
+            }
+        }
+    }
+
+    /// Wake every waiter and make all later arrivals pass through: the
+    /// failing chain is never going to arrive.
+    fn poison(&self) {
+        self.inner.lock().unwrap().poisoned = true;
+        self.cond.notify_all();
+    }
+}
+
+/// Shared state the chain workers of one stage append to.
+struct StageState {
+    /// Ids of background activities started in this stage, to stop on exit.
+    started: Mutex<Vec<(String, crate::proto::ActivityId)>>,
+    /// Restore requests of state-changing activities, run after the stops.
+    cleanups: Mutex<Vec<(String, crate::proto::Request)>>,
+    /// Named timestamps recorded by mark entries in this stage.
+    stage_marks: Mutex<Vec<(String, u64)>>,
+    /// Values published by fg launch entries with an artifact name.
+    artifacts: Mutex<Vec<(String, String)>>,
+    /// Completed foreground commands, kept for the per-agent results page.
+    fg_done: Mutex<Vec<(String, crate::proto::ActivityId, FgResult)>>,
+    /// Synchronization point for barrier entries: all chain workers meet
+    /// here and agree on the deadline their agents resume at. Poisoned
+    /// by a worker whose chain fails, so nobody waits for it in vain.
+    barrier: BarrierSync,
+    /// Clock offsets measured at pre-flight, folded into the barrier
+    /// deadlines sent to the agents.
+    offsets: BTreeMap<String, i64>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_stage(
+    stage: &crate::cfgparse::Stage,
+    conns: &BTreeMap<String, Mutex<AgentConnection>>,
+    seed: u64,
+    poll_format: crate::cfgparse::PollFormat,
+    concurrency: Option<usize>,
+    storage: &mut Storage,
+    marks: &mut BTreeMap<String, u64>,
+    fg_results: &mut Vec<(String, crate::proto::ActivityId, FgResult)>,
+    observer: &dyn RunObserver,
+) -> Result<(), RunError> {
+    run_hooks(&stage.name, &stage.pre)?;
+
+    // Fixture paths are fixed by the config, so they can be published
+    // before the chains run and later stages can refer to them.
+    for (agent, chain) in &stage.chains {
+        let paths: Vec<String> = chain.iter().flat_map(activities::fixture_paths).collect();
+        if !paths.is_empty() {
+            storage.set_or_replace(&Key::stage(agent, &stage.name, "fixtures"), &paths);
+        }
+    }
+
+    let state = StageState {
+        started: Mutex::new(Vec::new()),
+        cleanups: Mutex::new(Vec::new()),
+        stage_marks: Mutex::new(Vec::new()),
+        artifacts: Mutex::new(Vec::new()),
+        fg_done: Mutex::new(Vec::new()),
+        barrier: BarrierSync::new(stage.chains.len()),
+        offsets: stage
+            .chains
+            .keys()
+            .map(|agent| {
+                let offset = storage
+                    .get_as::<i64>(&Key::agent(agent, "clock_offset_ms"))
+                    .unwrap_or(0);
+                (agent.clone(), offset)
+            })
+            .collect(),
+    };
+
+    // One worker per chain unless the config bounds them; with a bound,
+    // finished workers pull the next chain off the queue. The parser
+    // guarantees stages with barriers never run with fewer workers than
+    // chains, so the rendezvous in run_chain cannot deadlock.
+    let workers = stage
+        .chains
+        .len()
+        .min(concurrency.unwrap_or(stage.chains.len()));
+    let queue: Mutex<VecDeque<_>> = Mutex::new(stage.chains.iter().collect());
+
+    std::thread::scope(|scope| -> Result<(), RunError> {
+        let mut handles = Vec::new();
+        for _ in 0..workers {
+            let queue = &queue;
+            let state = &state;
+            handles.push(scope.spawn(move || -> Result<(), RunError> {
+                loop {
+                    let task = queue.lock().unwrap().pop_front();
+                    let Some((agent, chain)) = task else {
+                        return Ok(());
+                    };
+                    if let Err(error) =
+                        run_chain(stage, agent, chain, conns, seed, poll_format, state, observer)
+                    {
+                        // This chain will never reach its barriers;
+                        // wake any workers already waiting there.
+                        state.barrier.poison();
+                        return Err(error);
+                    }
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().expect("stage worker panicked")?;
+        }
+        Ok(())
+    })?;
+
+    marks.extend(state.stage_marks.into_inner().unwrap());
+
+    for (name, value) in state.artifacts.into_inner().unwrap() {
+        storage.set_or_replace(&Key::run(&name), &value);
+    }
+    fg_results.extend(state.fg_done.into_inner().unwrap());
+    let started = state.started;
+    let cleanups = state.cleanups;
+
+    if let Some(duration) = stage.duration {
+        std::thread::sleep(Duration::from_secs(duration));
+    }
+
+    for (agent, id) in started.into_inner().unwrap() {
+        let mut conn = conns[&agent].lock().unwrap();
+        conn.stop(&id).map_err(|error| {
+            observer.on_agent_error(&agent, &error.to_string());
+            RunError::Stage {
+                stage: stage.name.clone(),
+                agent: agent.clone(),
+                error,
+            }
+        })?;
+        drop(conn);
+        observer.on_activity_stop(&agent, &id);
+    }
+
+    // State-changing activities restore the machine only after everything
+    // else has stopped, last applied first.
+    for (agent, req) in cleanups.into_inner().unwrap().into_iter().rev() {
+        let mut conn = conns[&agent].lock().unwrap();
+        conn.transact_many(std::slice::from_ref(&req))
+            .map_err(|error| {
+                observer.on_agent_error(&agent, &error.to_string());
+                RunError::Stage {
+                    stage: stage.name.clone(),
+                    agent: agent.clone(),
+                    error,
+                }
+            })?;
+    }
+
+    run_hooks(&stage.name, &stage.post)
+}
+
+/// Run one agent chain of a stage: marks and barriers are handled here,
+/// the runs of plain entries between them start with one pipelined
+/// exchange each.
+#[allow(clippy::too_many_arguments)]
+fn run_chain(
+    stage: &crate::cfgparse::Stage,
+    agent: &str,
+    chain: &[crate::cfgparse::Activity],
+    conns: &BTreeMap<String, Mutex<AgentConnection>>,
+    seed: u64,
+    poll_format: crate::cfgparse::PollFormat,
+    state: &StageState,
+    observer: &dyn RunObserver,
+) -> Result<(), RunError> {
+    let mut conn = conns[agent].lock().unwrap();
+    let mut ids = activities::IdAlloc::new(&stage.name);
+    let mut pending: Vec<&crate::cfgparse::Activity> = Vec::new();
+    let mut resume_at: Option<u64> = None;
+    for activity in chain {
+        if let crate::cfgparse::Activity::Mark { name } = activity {
+            start_batch(
+                &mut pending, &mut resume_at, &mut **conn, &mut ids, seed, poll_format, stage,
+                agent, state, observer,
+            )?;
+            state
+                .stage_marks
+                .lock()
+                .unwrap()
+                .push((name.clone(), crate::common::now_millis()));
+            continue;
+        }
+        if matches!(activity, crate::cfgparse::Activity::Barrier {}) {
+            start_batch(
+                &mut pending, &mut resume_at, &mut **conn, &mut ids, seed, poll_format, stage,
+                agent, state, observer,
+            )?;
+            // The last chain to arrive picks the deadline. Instead of
+            // sleeping here, each worker folds the deadline onto its
+            // agent's own clock and sends it ahead of the next batch as
+            // a WaitUntil: the agent sleeps it out locally, so the
+            // entries that follow start at one absolute instant instead
+            // of one request latency apart. On a link slower than the
+            // lead the wait is already over on arrival, degrading to
+            // dispatch alignment.
+            let Some(deadline) = state.barrier.wait() else {
+                // Another chain failed; its error is already on the way
+                // up, this chain just stops early.
+                return Ok(());
+            };
+            let offset = state.offsets.get(agent).copied().unwrap_or(0);
+            resume_at = Some(deadline.saturating_add_signed(offset));
+            continue;
+        }
+        pending.push(activity);
+    }
+    start_batch(
+        &mut pending, &mut resume_at, &mut **conn, &mut ids, seed, poll_format, stage, agent,
+        state, observer,
+    )
+}
+
+/// Start the pending run of chain entries with one pipelined exchange
+/// and record what it produced, draining the batch.
+#[allow(clippy::too_many_arguments)]
+fn start_batch(
+    pending: &mut Vec<&crate::cfgparse::Activity>,
+    resume_at: &mut Option<u64>,
+    conn: &mut dyn crate::connection::ConnectionOps,
+    ids: &mut activities::IdAlloc,
+    seed: u64,
+    poll_format: crate::cfgparse::PollFormat,
+    stage: &crate::cfgparse::Stage,
+    agent: &str,
+    state: &StageState,
+    observer: &dyn RunObserver,
+) -> Result<(), RunError> {
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let stage_error = |error: ConnError| {
+        observer.on_agent_error(agent, &error.to_string());
+        RunError::Stage {
+            stage: stage.name.clone(),
+            agent: agent.to_string(),
+            error,
+        }
+    };
+    let entry_results =
+        activities::start_all(conn, pending, ids, seed, poll_format, resume_at.take())
+            .map_err(stage_error)?;
+    for (activity, results) in pending.iter().zip(entry_results) {
+        let specs = activities::artifact_specs(activity);
+        for (result, spec) in results.into_iter().zip(specs) {
+            match result {
+                Started::Bg(id) => {
+                    observer.on_activity_start(agent, activity.name(), &id);
+                    state.started.lock().unwrap().push((agent.to_string(), id));
+                }
+                Started::Fg(id, result) => {
+                    if let Some((name, extract)) = spec {
+                        let stdout = String::from_utf8_lossy(&result.stdout);
+                        let value = extract_artifact(&stdout, &extract).map_err(stage_error)?;
+                        state.artifacts.lock().unwrap().push((name, value));
+                    }
+                    state
+                        .fg_done
+                        .lock()
+                        .unwrap()
+                        .push((agent.to_string(), id, result));
+                }
+            }
+        }
+    }
+    for activity in pending.drain(..) {
+        for req in activities::cleanup_requests(activity, ids) {
+            state.cleanups.lock().unwrap().push((agent.to_string(), req));
+        }
+    }
+    Ok(())
+}
+
+/// Run stage pre/post commands on the controller host through the shell.
+fn run_hooks(stage: &str, commands: &[String]) -> Result<(), RunError> {
+    for command in commands {
+        eprintln!("controller: stage '{stage}' hook: {command}");
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .status()
+            .map_err(|e| RunError::Hook {
+                stage: stage.to_string(),
+                command: command.clone(),
+                error: e.to_string(),
+            })?;
+        if !status.success() {
+            return Err(RunError::Hook {
+                stage: stage.to_string(),
+                command: command.clone(),
+                error: format!("exited with {status}"),
+            });
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,443 @@
+//! Tidy export of parsed time series: CSV, JSON, OpenMetrics and
+//! InfluxDB line protocol.
+//!
+//! Every parser result can be flattened into one long-format table
+//! (`time, key..., metric, value`) suitable for pandas/R, so users do not
+//! have to re-parse raw tool output.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::plot::plotly_time;
+use crate::plotters::procfs::{Meminfo, NetDev};
+use crate::plotters::sysstat::iostat::Iostat;
+use crate::plotters::sysstat::mpstat::Mpstat;
+use crate::plotters::vmstat::Vmstat;
+
+/// Export output format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+    /// OpenMetrics text with original timestamps, suitable for backfilling
+    /// into Prometheus/VictoriaMetrics (`promtool tsdb create-blocks-from
+    /// openmetrics`).
+    OpenMetrics,
+    /// InfluxDB line protocol for ingestion into an existing TSDB, tagged
+    /// with the run and agent taken from the output directory layout.
+    Influx,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "openmetrics" => Ok(Format::OpenMetrics),
+            "influx" => Ok(Format::Influx),
+            other => Err(format!("unknown export format '{other}'")),
+        }
+    }
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+            Format::OpenMetrics => "om",
+            Format::Influx => "lp",
+        }
+    }
+}
+
+/// One long-format table: fixed columns, stringly-typed rows.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new(name: &str, columns: &[&str]) -> Self {
+        Table {
+            name: name.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Write the table as `<name>.<ext>` into `dir`.
+    pub fn write(&self, dir: &Path, format: Format) -> io::Result<()> {
+        let path = dir.join(format!("{}.{}", self.name, format.extension()));
+        let mut out = BufWriter::new(File::create(path)?);
+        match format {
+            Format::Csv => {
+                writeln!(out, "{}", self.columns.join(","))?;
+                for row in &self.rows {
+                    writeln!(out, "{}", row.join(","))?;
+                }
+            }
+            Format::Json => {
+                let objects: Vec<serde_json::Value> = self
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        self.columns
+                            .iter()
+                            .zip(row)
+                            .map(|(c, v)| (c.clone(), serde_json::Value::from(v.as_str())))
+                            .collect()
+                    })
+                    .collect();
+                serde_json::to_writer_pretty(&mut out, &objects)?;
+                writeln!(out)?;
+            }
+            Format::OpenMetrics => {
+                // One gauge family per table; every key column except the
+                // time and the value becomes a label.
+                let family = format!("pmppt_{}", sanitize_metric(&self.name));
+                writeln!(out, "# TYPE {family} gauge")?;
+                for row in &self.rows {
+                    let Some(seconds) = parse_seconds(&row[0]) else {
+                        continue;
+                    };
+                    let labels: Vec<String> = self.columns[1..self.columns.len() - 1]
+                        .iter()
+                        .zip(&row[1..])
+                        .map(|(c, v)| {
+                            format!("{}=\"{}\"", sanitize_metric(c), escape_label(v))
+                        })
+                        .collect();
+                    let value = row.last().expect("nonempty row");
+                    if labels.is_empty() {
+                        writeln!(out, "{family} {value} {seconds}")?;
+                    } else {
+                        writeln!(out, "{family}{{{}}} {value} {seconds}", labels.join(","))?;
+                    }
+                }
+                writeln!(out, "# EOF")?;
+            }
+            Format::Influx => {
+                // `<table>,run=...,agent=...,<keys> value=<v> <ts_ns>`.
+                // The run and agent tags come from the directory layout so
+                // multiple agents land distinguishable in one TSDB.
+                let mut tags = vec![format!("run={}", escape_tag(&dir_tag(dir, 1)))];
+                tags.push(format!("agent={}", escape_tag(&dir_tag(dir, 0))));
+                for row in &self.rows {
+                    let Some(seconds) = parse_seconds(&row[0]) else {
+                        continue;
+                    };
+                    let keys: Vec<String> = self.columns[1..self.columns.len() - 1]
+                        .iter()
+                        .zip(&row[1..])
+                        .map(|(c, v)| format!("{}={}", escape_tag(c), escape_tag(v)))
+                        .collect();
+                    let value = row.last().expect("nonempty row");
+                    writeln!(
+                        out,
+                        "{},{} value={value} {}",
+                        escape_tag(&self.name),
+                        tags.iter().chain(&keys).cloned().collect::<Vec<_>>().join(","),
+                        (seconds * 1e9) as i64,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read back a table written as CSV, the one format the plotters can
+/// fall back to when an agent pre-exported and removed the raw logs.
+/// Symmetric with the naive writer: values must not contain commas,
+/// which holds for every table this crate emits.
+pub fn read_csv(path: &Path) -> io::Result<Table> {
+    let text = crate::common::readfile(path)?;
+    let mut lines = text.lines();
+    let columns: Vec<String> = lines
+        .next()
+        .ok_or_else(|| io::Error::other("empty export table"))?
+        .split(',')
+        .map(str::to_string)
+        .collect();
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let rows = lines
+        .map(|line| line.split(',').map(str::to_string).collect())
+        .collect();
+    Ok(Table {
+        name,
+        columns,
+        rows,
+    })
+}
+
+/// Convert the recognized outputs of an agent session directory into
+/// export tables, deleting the raw logs that were converted so a
+/// subsequent collect transfers the compact tables instead
+/// (`pmppt_agent --pre-export`). Activities without a table flattener
+/// keep their raw output. Returns the number of converted activities.
+pub fn pre_export_dir(dir: &Path, format: Format) -> io::Result<usize> {
+    let Ok(map) = crate::common::readfile(&dir.join("out.map")) else {
+        return Ok(0);
+    };
+    let open = |path: &Path| File::open(path).map(std::io::BufReader::new);
+
+    let mut converted = 0;
+    for line in map.lines() {
+        let Some((id, name)) = line.split_once(' ') else {
+            continue;
+        };
+        let raw = match name {
+            "mpstat" | "iostat" | "vmstat" => dir.join(format!("{id}-out.log")),
+            "meminfo" | "netdev" => dir.join(format!("{id}-poll.log")),
+            _ => continue,
+        };
+        let table = match name {
+            "mpstat" => mpstat(
+                &crate::plotters::sysstat::mpstat::parse_reader(open(&raw)?)
+                    .map_err(io::Error::other)?,
+            ),
+            "iostat" => iostat(
+                &crate::plotters::sysstat::iostat::parse_reader(open(&raw)?)
+                    .map_err(io::Error::other)?,
+            ),
+            "vmstat" => vmstat(
+                &crate::plotters::vmstat::parse(&crate::common::readfile(&raw)?)
+                    .map_err(io::Error::other)?,
+            ),
+            "meminfo" => meminfo(
+                &crate::plotters::procfs::parse_meminfo_reader(open(&raw)?)
+                    .map_err(io::Error::other)?,
+            ),
+            "netdev" => net_dev(
+                &crate::plotters::procfs::parse_net_dev_reader(open(&raw)?)
+                    .map_err(io::Error::other)?,
+            ),
+            _ => unreachable!("matched above"),
+        };
+        table.write(dir, format)?;
+        std::fs::remove_file(&raw)?;
+        converted += 1;
+    }
+    Ok(converted)
+}
+
+/// The `level`-th path component of the output directory from the end:
+/// 0 is the agent directory name, 1 the run directory above it.
+fn dir_tag(dir: &Path, level: usize) -> String {
+    dir.canonicalize()
+        .unwrap_or_else(|_| dir.to_path_buf())
+        .iter()
+        .rev()
+        .nth(level)
+        .map(|c| c.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Escape an InfluxDB line protocol tag key/value.
+fn escape_tag(value: &str) -> String {
+    value.replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+/// Restrict a name to the OpenMetrics metric/label alphabet.
+fn sanitize_metric(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Escape a label value per the OpenMetrics text format.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Interpret a table time column: either the `plotly_time` wall-clock
+/// format or plain seconds (fio bandwidth logs).
+fn parse_seconds(time: &str) -> Option<f64> {
+    if let Ok(stamp) =
+        chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.3f")
+    {
+        return Some(stamp.and_utc().timestamp_millis() as f64 / 1000.0);
+    }
+    time.parse().ok()
+}
+
+/// Flatten an mpstat capture into `time, cpu, metric, value` rows.
+pub fn mpstat(stat: &Mpstat) -> Table {
+    let mut table = Table::new("mpstat", &["time", "cpu", "metric", "value"]);
+    for (column, cpus) in &stat.data {
+        for (cpu, values) in stat.cpus.iter().zip(cpus) {
+            for (time, value) in stat.times.iter().zip(values) {
+                table.rows.push(vec![
+                    plotly_time(time),
+                    cpu.clone(),
+                    column.header().to_string(),
+                    value.to_string(),
+                ]);
+            }
+        }
+    }
+    table
+}
+
+/// Flatten an iostat capture into `time, device, metric, value` rows.
+pub fn iostat(stat: &Iostat) -> Table {
+    let mut table = Table::new("iostat", &["time", "device", "metric", "value"]);
+    for (device, stats) in &stat.devices {
+        let metrics = [
+            ("r/s", &stats.rps),
+            ("w/s", &stats.wps),
+            ("rkB/s", &stats.rkbps),
+            ("wkB/s", &stats.wkbps),
+            ("%util", &stats.util),
+            ("r_await", &stats.r_await),
+            ("w_await", &stats.w_await),
+            ("d/s", &stats.dps),
+        ];
+        for (metric, values) in metrics {
+            for (time, value) in stat.times.iter().zip(values) {
+                table.rows.push(vec![
+                    plotly_time(time),
+                    device.clone(),
+                    metric.to_string(),
+                    value.to_string(),
+                ]);
+            }
+        }
+    }
+    table
+}
+
+/// Flatten a meminfo poll into `time, field, kb` rows.
+pub fn meminfo(stat: &Meminfo) -> Table {
+    let mut table = Table::new("meminfo", &["time", "field", "kb"]);
+    for (field, values) in &stat.fields {
+        for (time, value) in stat.times.iter().zip(values) {
+            table
+                .rows
+                .push(vec![plotly_time(time), field.clone(), value.to_string()]);
+        }
+    }
+    table
+}
+
+/// Flatten a net/dev poll into `time, iface, metric, value` rows.
+pub fn net_dev(stat: &NetDev) -> Table {
+    let mut table = Table::new("netdev", &["time", "iface", "metric", "value"]);
+    for (iface, stats) in &stat.ifaces {
+        let metrics = [
+            ("rx_bytes", &stats.rx_bytes),
+            ("tx_bytes", &stats.tx_bytes),
+            ("rx_packets", &stats.rx_packets),
+            ("tx_packets", &stats.tx_packets),
+        ];
+        for (metric, values) in metrics {
+            for (time, value) in stat.times.iter().zip(values) {
+                table.rows.push(vec![
+                    plotly_time(time),
+                    iface.clone(),
+                    metric.to_string(),
+                    value.to_string(),
+                ]);
+            }
+        }
+    }
+    table
+}
+
+/// Flatten a vmstat capture into `time, metric, value` rows.
+pub fn vmstat(stat: &Vmstat) -> Table {
+    let mut table = Table::new("vmstat", &["time", "metric", "value"]);
+    let metrics = [
+        ("r", &stat.running),
+        ("b", &stat.blocked),
+        ("si", &stat.swap_in),
+        ("so", &stat.swap_out),
+        ("in", &stat.interrupts),
+        ("cs", &stat.ctx_switches),
+    ];
+    for (metric, values) in metrics {
+        for (time, value) in stat.times.iter().zip(values) {
+            table
+                .rows
+                .push(vec![plotly_time(time), metric.to_string(), value.to_string()]);
+        }
+    }
+    table
+}
+
+/// Flatten fio bandwidth log samples into `sec, mibps` rows.
+pub fn fio_bw(log_name: &str, samples: &[(f64, f64)]) -> Table {
+    let name = log_name.trim_end_matches(".log");
+    let mut table = Table::new(name, &["sec", "mibps"]);
+    for (sec, mibps) in samples {
+        table.rows.push(vec![sec.to_string(), mibps.to_string()]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_parses() {
+        assert_eq!("csv".parse(), Ok(Format::Csv));
+        assert_eq!("json".parse(), Ok(Format::Json));
+        assert_eq!("openmetrics".parse(), Ok(Format::OpenMetrics));
+        assert_eq!("influx".parse(), Ok(Format::Influx));
+        assert!("xml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn openmetrics_timestamps() {
+        assert_eq!(parse_seconds("1970-01-01 00:00:01.500"), Some(1.5));
+        assert_eq!(parse_seconds("2.25"), Some(2.25));
+        assert_eq!(parse_seconds("not a time"), None);
+        assert_eq!(sanitize_metric("net/dev"), "net_dev");
+        assert_eq!(escape_label("sda \"fast\""), "sda \\\"fast\\\"");
+    }
+
+    #[test]
+    fn influx_tags_escape() {
+        assert_eq!(escape_tag("r/s"), "r/s");
+        assert_eq!(escape_tag("my run,a=b"), "my\\ run\\,a\\=b");
+    }
+
+    #[test]
+    fn csv_round_trips() {
+        let mut table = Table::new("mpstat", &["time", "cpu", "metric", "value"]);
+        table.rows.push(vec![
+            "2026-08-26 10:00:00.000".to_string(),
+            "all".to_string(),
+            "usr".to_string(),
+            "12.5".to_string(),
+        ]);
+        let dir = std::env::temp_dir().join(format!("pmppt-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        table.write(&dir, Format::Csv).unwrap();
+
+        let back = read_csv(&dir.join("mpstat.csv")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(back.name, table.name);
+        assert_eq!(back.columns, table.columns);
+        assert_eq!(back.rows, table.rows);
+    }
+
+    #[test]
+    fn fio_bw_table_shape() {
+        let table = fio_bw("fio_bw.1.log", &[(1.0, 2.0)]);
+        assert_eq!(table.name, "fio_bw.1");
+        assert_eq!(table.columns, ["sec", "mibps"]);
+        assert_eq!(table.rows, [["1", "2"]]);
+    }
+}
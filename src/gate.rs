@@ -0,0 +1,180 @@
+//! Machine-readable regression gate.
+//!
+//! Compares the headline summaries of a baseline and a candidate run
+//! against thresholds from a rules file, so CI can fail a change on
+//! "p99 latency regressed more than 5%" without a human reading the
+//! reports. A rules file is a list of constraints on the metrics of
+//! [`crate::plotters::summary::Summary`]:
+//!
+//! ```yaml
+//! rules:
+//!   - metric: fio_iops
+//!     min_ratio: 0.95        # candidate / baseline
+//!   - metric: fio_clat_p99_ms
+//!     max_ratio: 1.05
+//!   - metric: mem_free_min_gib
+//!     agent: db               # default: every agent in both runs
+//!     min_value: 1.0          # absolute bound on the candidate
+//! ```
+
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::plotters::{compare, read_marks, summary};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rules {
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Summary metric name, e.g. `fio_clat_p99_ms`.
+    pub metric: String,
+    /// Restrict the rule to one agent; default is every agent present
+    /// in both runs.
+    #[serde(default)]
+    pub agent: Option<String>,
+    /// Candidate / baseline must stay at or above this.
+    #[serde(default)]
+    pub min_ratio: Option<f64>,
+    /// Candidate / baseline must stay at or below this.
+    #[serde(default)]
+    pub max_ratio: Option<f64>,
+    /// Absolute lower bound on the candidate value.
+    #[serde(default)]
+    pub min_value: Option<f64>,
+    /// Absolute upper bound on the candidate value.
+    #[serde(default)]
+    pub max_value: Option<f64>,
+}
+
+/// Check the rules, returning one line per violation. A metric a rule
+/// needs but a run did not capture is a violation, not a pass: a gate
+/// that silently stops measuring protects nothing.
+pub fn check(baseline: &Path, candidate: &Path, rules: &Rules) -> io::Result<Vec<String>> {
+    let mut violations = Vec::new();
+    let base_dirs = compare::agent_dirs(baseline)?;
+    let cand_dirs = compare::agent_dirs(candidate)?;
+
+    for rule in &rules.rules {
+        let mut applied = false;
+        for (agent, cand_dir) in &cand_dirs {
+            if rule.agent.as_ref().is_some_and(|a| a != agent) {
+                continue;
+            }
+            let Some((_, base_dir)) = base_dirs.iter().find(|(b, _)| b == agent) else {
+                continue;
+            };
+            applied = true;
+
+            let base = metric(base_dir, &rule.metric)?;
+            let cand = metric(cand_dir, &rule.metric)?;
+            check_one(rule, agent, base, cand, &mut violations);
+        }
+        if !applied {
+            violations.push(format!(
+                "{}: no agent in both runs matches the rule",
+                rule.metric
+            ));
+        }
+    }
+    Ok(violations)
+}
+
+fn check_one(rule: &Rule, agent: &str, base: Option<f64>, cand: Option<f64>, out: &mut Vec<String>) {
+    let tag = format!("{agent}/{}", rule.metric);
+    let Some(cand) = cand else {
+        out.push(format!("{tag}: not captured in the candidate run"));
+        return;
+    };
+    if let Some(min) = rule.min_value {
+        if cand < min {
+            out.push(format!("{tag}: {cand:.2} below minimum {min:.2}"));
+        }
+    }
+    if let Some(max) = rule.max_value {
+        if cand > max {
+            out.push(format!("{tag}: {cand:.2} above maximum {max:.2}"));
+        }
+    }
+
+    if rule.min_ratio.is_none() && rule.max_ratio.is_none() {
+        return;
+    }
+    let Some(base) = base else {
+        out.push(format!("{tag}: not captured in the baseline run"));
+        return;
+    };
+    if base == 0.0 {
+        out.push(format!("{tag}: baseline is zero, ratio undefined"));
+        return;
+    }
+    let ratio = cand / base;
+    if let Some(min) = rule.min_ratio {
+        if ratio < min {
+            out.push(format!(
+                "{tag}: {cand:.2} is {ratio:.3}x of baseline {base:.2}, below {min}"
+            ));
+        }
+    }
+    if let Some(max) = rule.max_ratio {
+        if ratio > max {
+            out.push(format!(
+                "{tag}: {cand:.2} is {ratio:.3}x of baseline {base:.2}, above {max}"
+            ));
+        }
+    }
+}
+
+/// One metric of one agent directory, through the summary serializer so
+/// the rules file names match `summary.json`.
+fn metric(dir: &Path, name: &str) -> io::Result<Option<f64>> {
+    let window = summary::mark_window(&read_marks(dir));
+    let summary = serde_json::to_value(summary::compute(dir, window)?).expect("serializable");
+    Ok(summary[name].as_f64())
+}
+
+/// Parse a rules file.
+pub fn load_rules(path: &Path) -> io::Result<Rules> {
+    let text = crate::common::readfile(path)?;
+    serde_yaml::from_str(&text).map_err(|e| io::Error::other(format!("bad rules file: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(metric: &str) -> Rule {
+        Rule {
+            metric: metric.to_string(),
+            agent: None,
+            min_ratio: None,
+            max_ratio: None,
+            min_value: None,
+            max_value: None,
+        }
+    }
+
+    #[test]
+    fn ratio_bounds_flag_regressions() {
+        let mut out = Vec::new();
+        let mut worse = rule("fio_clat_p99_ms");
+        worse.max_ratio = Some(1.05);
+        check_one(&worse, "a", Some(10.0), Some(10.4), &mut out);
+        assert!(out.is_empty());
+        check_one(&worse, "a", Some(10.0), Some(11.0), &mut out);
+        assert_eq!(out.len(), 1);
+    }
+
+    #[test]
+    fn missing_candidate_metric_is_a_violation() {
+        let mut out = Vec::new();
+        check_one(&rule("fio_iops"), "a", Some(10.0), None, &mut out);
+        assert_eq!(out.len(), 1);
+    }
+}
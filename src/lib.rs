@@ -0,0 +1,42 @@
+//! Poor Man's Performance Profiler Tool.
+//!
+//! PMPPT is a small distributed profiling harness: a controller connects to
+//! agents running on the machines under test, drives configured activity
+//! chains (stat pollers, benchmark processes) through the stages of a run,
+//! collects the raw output and renders HTML reports from it.
+//!
+//! The agent core builds without any cargo features; the `controller` and
+//! `plotter` features (both on by default) pull in the run orchestration
+//! and the report rendering respectively. The `parsers` feature exposes
+//! just the sysstat/procfs parsers for reuse by other tools.
+
+#[cfg(feature = "controller")]
+pub mod activities;
+pub mod agent;
+#[cfg(feature = "controller")]
+pub mod cfgparse;
+pub mod cli;
+pub mod common;
+pub mod connection;
+#[cfg(feature = "controller")]
+pub mod controller;
+#[cfg(feature = "plotter")]
+pub mod export;
+#[cfg(feature = "plotter")]
+pub mod gate;
+#[cfg(feature = "controller")]
+pub mod live;
+#[cfg(feature = "controller")]
+pub mod notify;
+#[cfg(feature = "plotter")]
+pub mod plot;
+#[cfg(feature = "parsers")]
+pub mod plotters;
+pub mod polllog;
+pub mod proto;
+#[cfg(feature = "controller")]
+pub mod serve;
+pub mod storage;
+pub mod testing;
+#[cfg(feature = "controller")]
+pub mod upload;
@@ -0,0 +1,267 @@
+//! Live metrics dashboard for a run in progress.
+//!
+//! While the controller drives a run, a sampler thread periodically
+//! fetches `/proc/stat` and `/proc/vmstat` from every agent through the
+//! selective-fetch request and keeps short CPU and I/O histories. A
+//! local web page renders them as per-agent sparklines, so an operator
+//! can abort an obviously broken run early instead of discovering the
+//! problem hours later in the report.
+//!
+//! The dashboard is enabled through a process-wide knob (like the
+//! plotter options), so the run entry points do not have to thread it
+//! through their signatures.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::connection::AgentConnection;
+
+static ADDR: Mutex<Option<String>> = Mutex::new(None);
+
+/// How many samples each sparkline keeps (at one sample per period).
+const HISTORY: usize = 150;
+const PERIOD: Duration = Duration::from_secs(2);
+
+/// Serve the live dashboard on this address during subsequent runs.
+pub fn set_addr(addr: String) {
+    *ADDR.lock().unwrap() = Some(addr);
+}
+
+#[derive(Default)]
+struct AgentSamples {
+    /// Last raw (busy, total) jiffies from `/proc/stat`.
+    prev_cpu: Option<(u64, u64)>,
+    /// Last raw pgpgin + pgpgout KiB from `/proc/vmstat`.
+    prev_io: Option<u64>,
+    cpu: VecDeque<f64>,
+    io_kbps: VecDeque<f64>,
+}
+
+type Samples = Arc<Mutex<BTreeMap<String, AgentSamples>>>;
+
+/// A running dashboard; dropping it stops the sampling. The page keeps
+/// being served with the last data until the process exits.
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Start the dashboard if one was requested; failures to start only
+/// cost the dashboard, never the run.
+pub fn maybe_start(conns: &Arc<BTreeMap<String, Mutex<AgentConnection>>>) -> Option<Dashboard> {
+    let addr = ADDR.lock().unwrap().clone()?;
+    match start(&addr, conns.clone()) {
+        Ok(dashboard) => Some(dashboard),
+        Err(e) => {
+            eprintln!("live: cannot start dashboard on {addr}: {e}");
+            None
+        }
+    }
+}
+
+fn start(
+    addr: &str,
+    conns: Arc<BTreeMap<String, Mutex<AgentConnection>>>,
+) -> io::Result<Dashboard> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("live: dashboard on http://{addr}/");
+
+    let samples: Samples = Arc::new(Mutex::new(BTreeMap::new()));
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let sampler_stop = stop.clone();
+    let sampler_samples = samples.clone();
+    std::thread::spawn(move || {
+        while !sampler_stop.load(Ordering::Relaxed) {
+            for (name, conn) in conns.iter() {
+                let fetched = {
+                    let mut conn = conn.lock().unwrap();
+                    (conn.fetch("/proc/stat"), conn.fetch("/proc/vmstat"))
+                };
+                let (Ok(stat), Ok(vmstat)) = fetched else {
+                    continue;
+                };
+                let mut samples = sampler_samples.lock().unwrap();
+                let agent = samples.entry(name.clone()).or_default();
+                agent.update(
+                    &String::from_utf8_lossy(&stat),
+                    &String::from_utf8_lossy(&vmstat),
+                );
+            }
+            std::thread::sleep(PERIOD);
+        }
+    });
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            if let Err(e) = handle(stream, &samples) {
+                eprintln!("live: request failed: {e}");
+            }
+        }
+    });
+
+    Ok(Dashboard { stop })
+}
+
+impl AgentSamples {
+    fn update(&mut self, stat: &str, vmstat: &str) {
+        if let Some((busy, total)) = cpu_jiffies(stat) {
+            if let Some((prev_busy, prev_total)) = self.prev_cpu {
+                let d_total = total.saturating_sub(prev_total);
+                if d_total > 0 {
+                    let percent = busy.saturating_sub(prev_busy) as f64 / d_total as f64 * 100.0;
+                    push(&mut self.cpu, percent);
+                }
+            }
+            self.prev_cpu = Some((busy, total));
+        }
+        if let Some(paged) = paged_kb(vmstat) {
+            if let Some(prev) = self.prev_io {
+                let kbps = paged.saturating_sub(prev) as f64 / PERIOD.as_secs_f64();
+                push(&mut self.io_kbps, kbps);
+            }
+            self.prev_io = Some(paged);
+        }
+    }
+}
+
+fn push(series: &mut VecDeque<f64>, value: f64) {
+    if series.len() == HISTORY {
+        series.pop_front();
+    }
+    series.push_back(value);
+}
+
+/// Cumulative (busy, total) jiffies from the aggregate `cpu` line.
+fn cpu_jiffies(stat: &str) -> Option<(u64, u64)> {
+    let line = stat.lines().find(|l| l.starts_with("cpu "))?;
+    let fields: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|f| f.parse().ok())
+        .collect();
+    if fields.len() < 5 {
+        return None;
+    }
+    let total: u64 = fields.iter().sum();
+    // Everything but idle and iowait counts as busy.
+    Some((total - fields[3] - fields[4], total))
+}
+
+/// Cumulative paged-in plus paged-out KiB, the system-wide I/O volume
+/// without the per-partition double counting of `/proc/diskstats`.
+fn paged_kb(vmstat: &str) -> Option<u64> {
+    let field = |name: &str| {
+        vmstat
+            .lines()
+            .find_map(|l| l.strip_prefix(name))?
+            .trim()
+            .parse::<u64>()
+            .ok()
+    };
+    Some(field("pgpgin ")? + field("pgpgout ")?)
+}
+
+fn handle(mut stream: TcpStream, samples: &Samples) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let (kind, body) = match path {
+        "/" => ("text/html", PAGE.as_bytes().to_vec()),
+        "/data" => ("application/json", data_json(samples).into_bytes()),
+        _ => {
+            return respond(&mut stream, 404, "text/plain", b"not found\n");
+        }
+    };
+    respond(&mut stream, 200, kind, &body)
+}
+
+fn data_json(samples: &Samples) -> String {
+    let samples = samples.lock().unwrap();
+    let mut data = serde_json::Map::new();
+    for (name, agent) in samples.iter() {
+        data.insert(
+            name.clone(),
+            serde_json::json!({
+                "cpu": agent.cpu,
+                "io_kbps": agent.io_kbps,
+            }),
+        );
+    }
+    serde_json::Value::Object(data).to_string()
+}
+
+fn respond(stream: &mut TcpStream, code: u16, kind: &str, body: &[u8]) -> io::Result<()> {
+    let reason = if code == 200 { "OK" } else { "Not Found" };
+    write!(
+        stream,
+        "HTTP/1.1 {code} {reason}\r\nContent-Type: {kind}\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)
+}
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><title>pmppt live</title>
+<style>
+body { font-family: sans-serif; }
+canvas { border: 1px solid #ccc; margin-right: 8px; }
+.label { font-size: small; color: #555; }
+</style></head><body>
+<h1>pmppt live</h1>
+<div id="agents"></div>
+<script>
+function panel(agent) {
+  const div = document.createElement('div');
+  div.id = 'agent-' + agent;
+  div.innerHTML = '<h2>' + agent + '</h2>' +
+    '<canvas class="cpu" width="300" height="60"></canvas>' +
+    '<canvas class="io" width="300" height="60"></canvas><br>' +
+    '<span class="label cpu-label">cpu</span> ' +
+    '<span class="label io-label">io</span>';
+  document.getElementById('agents').appendChild(div);
+  return div;
+}
+function spark(canvas, points, max) {
+  const ctx = canvas.getContext('2d');
+  ctx.clearRect(0, 0, canvas.width, canvas.height);
+  ctx.beginPath();
+  ctx.strokeStyle = '#68a';
+  points.forEach((v, i) => {
+    const x = i / 149 * canvas.width;
+    const y = canvas.height - Math.min(v / max, 1) * canvas.height;
+    i === 0 ? ctx.moveTo(x, y) : ctx.lineTo(x, y);
+  });
+  ctx.stroke();
+}
+async function tick() {
+  const data = await (await fetch('data')).json();
+  for (const [agent, series] of Object.entries(data)) {
+    const div = document.getElementById('agent-' + agent) || panel(agent);
+    const last = list => list.length ? list[list.length - 1] : 0;
+    spark(div.querySelector('.cpu'), series.cpu, 100);
+    spark(div.querySelector('.io'), series.io_kbps,
+          Math.max(...series.io_kbps, 1));
+    div.querySelector('.cpu-label').textContent =
+      'cpu ' + last(series.cpu).toFixed(0) + '%';
+    div.querySelector('.io-label').textContent =
+      'io ' + (last(series.io_kbps) / 1024).toFixed(1) + ' MiB/s';
+  }
+}
+setInterval(tick, 2000);
+tick();
+</script></body></html>
+"#;
@@ -0,0 +1,746 @@
+//! Minimal plotly-based HTML chart generation.
+//!
+//! No plotting crate is involved: traces are serialized into plotly JSON
+//! and rendered by plotly.js loaded from a CDN in the generated page.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use chrono::NaiveDateTime;
+use regex::Regex;
+use serde_json::{json, Value};
+
+const PLOTLY_CDN: &str = "https://cdn.plot.ly/plotly-2.32.0.min.js";
+
+/// Rendered plot size in pixels. The defaults suit a large desktop
+/// monitor; laptops and projectors override them via the CLI.
+static PLOT_WIDTH: AtomicU32 = AtomicU32::new(1900);
+static PLOT_HEIGHT: AtomicU32 = AtomicU32::new(950);
+
+/// Plots per page row; 1 stacks them vertically as before.
+static COLUMNS: AtomicUsize = AtomicUsize::new(1);
+
+/// Dark color scheme toggle.
+static DARK: AtomicBool = AtomicBool::new(false);
+
+/// Set the size of every rendered plot process-wide.
+pub fn set_size(width: u32, height: u32) {
+    PLOT_WIDTH.store(width, Ordering::Relaxed);
+    PLOT_HEIGHT.store(height, Ordering::Relaxed);
+}
+
+/// Arrange plots in a grid with the given number of columns, each plot
+/// shrunk to fit. The number of rows follows from the plot count.
+pub fn set_columns(columns: usize) {
+    COLUMNS.store(columns.max(1), Ordering::Relaxed);
+}
+
+/// Page color scheme.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl std::str::FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "light" => Ok(Theme::Light),
+            "dark" => Ok(Theme::Dark),
+            other => Err(format!("unknown theme '{other}'")),
+        }
+    }
+}
+
+/// Switch every generated page to the given color scheme.
+pub fn set_theme(theme: Theme) {
+    DARK.store(theme == Theme::Dark, Ordering::Relaxed);
+}
+
+/// Moving-average window for scatter traces, 0/1 meaning off.
+static SMOOTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Panels whose title matches get a logarithmic Y axis.
+static LOG_Y: Mutex<Option<Regex>> = Mutex::new(None);
+
+/// Smooth every rendered scatter trace with a moving average of the given
+/// window. Exports are not affected, only the HTML pages.
+pub fn set_smooth(window: usize) {
+    SMOOTH.store(window, Ordering::Relaxed);
+}
+
+/// Use a logarithmic Y axis on every panel whose title matches the regex,
+/// e.g. `Traffic|IOPS` for bursty counters.
+pub fn set_log_y(title: Option<Regex>) {
+    *LOG_Y.lock().unwrap() = title;
+}
+
+/// Replace the values of a scatter trace with their trailing moving
+/// average over `window` samples. Non-scatter traces pass through.
+fn smooth_trace(trace: &Value, window: usize) -> Value {
+    let Some(y) = trace["y"].as_array() else {
+        return trace.clone();
+    };
+    if trace["type"] != "scatter" || window < 2 {
+        return trace.clone();
+    }
+
+    let values: Vec<f64> = y.iter().map(|v| v.as_f64().unwrap_or(f64::NAN)).collect();
+    let smoothed: Vec<f64> = (0..values.len())
+        .map(|i| {
+            let from = (i + 1).saturating_sub(window);
+            let slice = &values[from..=i];
+            slice.iter().sum::<f64>() / slice.len() as f64
+        })
+        .collect();
+    let mut smoothed_trace = trace.clone();
+    smoothed_trace["y"] = json!(smoothed);
+    smoothed_trace
+}
+
+/// Point cap per scatter trace, 0 meaning unlimited. Long runs at short
+/// poll periods otherwise produce HTML files that freeze the browser.
+static MAX_POINTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Cap the number of points per rendered scatter trace process-wide.
+/// Exports are not affected: only the HTML pages are decimated.
+pub fn set_max_points(limit: usize) {
+    MAX_POINTS.store(limit, Ordering::Relaxed);
+}
+
+/// Trace cap per plot, 0 meaning unlimited. Hundreds of devices or IRQs
+/// otherwise produce plots the browser cannot render anyway.
+static MAX_TRACES: AtomicUsize = AtomicUsize::new(0);
+
+/// Cap the number of traces per rendered plot process-wide. Truncated
+/// plots say so in their title and a warning is printed, so dropped
+/// series never go unnoticed.
+pub fn set_max_traces(limit: usize) {
+    MAX_TRACES.store(limit, Ordering::Relaxed);
+}
+
+/// Min/max bucket decimation of one scatter trace: each bucket keeps the
+/// samples with the smallest and largest value, preserving spikes that
+/// plain striding would drop. Non-scatter traces pass through untouched.
+fn downsample_trace(trace: &Value, limit: usize) -> Value {
+    let (Some(x), Some(y)) = (trace["x"].as_array(), trace["y"].as_array()) else {
+        return trace.clone();
+    };
+    let points = x.len().min(y.len());
+    if trace["type"] != "scatter" || limit < 4 || points <= limit {
+        return trace.clone();
+    }
+
+    let buckets = limit / 2;
+    let mut keep = Vec::with_capacity(limit);
+    for bucket in 0..buckets {
+        let from = points * bucket / buckets;
+        let to = points * (bucket + 1) / buckets;
+        let mut lo = from;
+        let mut hi = from;
+        for i in from..to {
+            let value = y[i].as_f64().unwrap_or(f64::NAN);
+            if value < y[lo].as_f64().unwrap_or(f64::NAN) {
+                lo = i;
+            }
+            if value > y[hi].as_f64().unwrap_or(f64::NAN) {
+                hi = i;
+            }
+        }
+        keep.push(lo.min(hi));
+        if lo != hi {
+            keep.push(lo.max(hi));
+        }
+    }
+
+    let mut decimated = trace.clone();
+    decimated["x"] = keep.iter().map(|&i| x[i].clone()).collect();
+    decimated["y"] = keep.iter().map(|&i| y[i].clone()).collect();
+    decimated
+}
+
+/// Gap threshold as a multiple of the median sample interval, 0 turning
+/// gap detection off. Pollers paused between stages otherwise produce
+/// long interpolated lines across the no-data region.
+static GAP_FACTOR: AtomicUsize = AtomicUsize::new(10);
+
+/// Break scatter trace lines across sample gaps longer than `factor`
+/// times the trace's median interval; 0 draws through gaps.
+pub fn set_gap_factor(factor: usize) {
+    GAP_FACTOR.store(factor, Ordering::Relaxed);
+}
+
+/// Insert a null sample into a scatter trace in the middle of every gap
+/// wider than `factor` times the median interval, so plotly breaks the
+/// line there instead of interpolating across it. Traces with
+/// non-timestamp X values pass through untouched.
+fn break_gaps(trace: &Value, factor: usize) -> Value {
+    let (Some(x), Some(y)) = (trace["x"].as_array(), trace["y"].as_array()) else {
+        return trace.clone();
+    };
+    if trace["type"] != "scatter" || factor == 0 || x.len() < 3 || x.len() != y.len() {
+        return trace.clone();
+    }
+    let times: Option<Vec<NaiveDateTime>> = x
+        .iter()
+        .map(|v| {
+            v.as_str()
+                .and_then(|s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.3f").ok())
+        })
+        .collect();
+    let Some(times) = times else {
+        return trace.clone();
+    };
+
+    let mut intervals: Vec<i64> = times
+        .windows(2)
+        .map(|w| (w[1] - w[0]).num_milliseconds())
+        .collect();
+    intervals.sort_unstable();
+    let median = intervals[intervals.len() / 2];
+    if median <= 0 {
+        return trace.clone();
+    }
+    let threshold = median.saturating_mul(factor as i64);
+
+    let mut broken_x = vec![x[0].clone()];
+    let mut broken_y = vec![y[0].clone()];
+    let mut broken = false;
+    for i in 1..times.len() {
+        let gap = times[i] - times[i - 1];
+        if gap.num_milliseconds() > threshold {
+            broken_x.push(json!(plotly_time(&(times[i - 1] + gap / 2))));
+            broken_y.push(Value::Null);
+            broken = true;
+        }
+        broken_x.push(x[i].clone());
+        broken_y.push(y[i].clone());
+    }
+    if !broken {
+        return trace.clone();
+    }
+    let mut out = trace.clone();
+    out["x"] = Value::Array(broken_x);
+    out["y"] = Value::Array(broken_y);
+    out
+}
+
+/// Format a timestamp the way plotly understands date axes.
+pub fn plotly_time(time: &NaiveDateTime) -> String {
+    time.format("%Y-%m-%d %H:%M:%S%.3f").to_string()
+}
+
+/// Time-axis origins for the relative mode, keyed by output directory.
+/// Pages written under a registered directory get their X axis rendered
+/// as seconds since the origin instead of absolute local timestamps. The
+/// origin is per directory because each agent keeps its own clock: the
+/// caller registers every agent directory with the run start translated
+/// into that agent's local time, so the axes line up across agents.
+static ORIGINS: Mutex<Vec<(PathBuf, NaiveDateTime)>> = Mutex::new(Vec::new());
+
+/// Render time axes of pages under `dir` as seconds since `origin`.
+/// When nested directories are registered, the first registration wins,
+/// so register the more specific directories first.
+pub fn set_relative_origin(dir: &Path, origin: NaiveDateTime) {
+    ORIGINS.lock().unwrap().push((dir.to_path_buf(), origin));
+}
+
+fn origin_for(path: &Path) -> Option<NaiveDateTime> {
+    let origins = ORIGINS.lock().unwrap();
+    let found = origins.iter().find(|(dir, _)| path.starts_with(dir));
+    found.map(|(_, origin)| *origin)
+}
+
+/// Turn a plotly timestamp into seconds since the origin. Values that are
+/// not timestamps (category axes, numeric X) pass through unchanged.
+fn relative_x(x: &Value, origin: &NaiveDateTime) -> Value {
+    let Some(text) = x.as_str() else {
+        return x.clone();
+    };
+    match NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.3f") {
+        Ok(time) => json!((time - *origin).num_milliseconds() as f64 / 1000.0),
+        Err(_) => x.clone(),
+    }
+}
+
+/// A single line/scatter trace.
+#[derive(Debug, Clone)]
+pub struct Scatter {
+    name: String,
+    x: Vec<String>,
+    y: Vec<f64>,
+}
+
+impl Scatter {
+    pub fn new(name: &str) -> Self {
+        Scatter {
+            name: name.to_string(),
+            x: Vec::new(),
+            y: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, x: String, y: f64) {
+        self.x.push(x);
+        self.y.push(y);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    pub fn to_trace(&self) -> Value {
+        json!({
+            "type": "scatter",
+            "mode": "lines",
+            "name": self.name,
+            "x": self.x,
+            "y": self.y,
+        })
+    }
+}
+
+/// A heatmap trace: `z[row][column]` with row labels in `y`.
+#[derive(Debug, Clone)]
+pub struct HeatMap {
+    x: Vec<String>,
+    y: Vec<String>,
+    z: Vec<Vec<f64>>,
+    zrange: Option<(f64, f64)>,
+}
+
+impl HeatMap {
+    pub fn new(x: Vec<String>, y: Vec<String>, z: Vec<Vec<f64>>) -> Self {
+        HeatMap {
+            x,
+            y,
+            z,
+            zrange: None,
+        }
+    }
+
+    /// Pin the colorbar to a fixed range instead of letting plotly scale
+    /// it to the data, e.g. 0–100 for percentage heatmaps.
+    pub fn set_zrange(&mut self, zmin: f64, zmax: f64) {
+        self.zrange = Some((zmin, zmax));
+    }
+
+    pub fn to_trace(&self) -> Value {
+        let mut trace = json!({
+            "type": "heatmap",
+            "x": self.x,
+            "y": self.y,
+            "z": self.z,
+            "colorscale": "Jet",
+        });
+        if let Some((zmin, zmax)) = self.zrange {
+            trace["zauto"] = json!(false);
+            trace["zmin"] = json!(zmin);
+            trace["zmax"] = json!(zmax);
+        }
+        trace
+    }
+}
+
+/// Human-readable label for a nanosecond bucket boundary.
+fn format_ns(ns: f64) -> String {
+    if ns < 1e3 {
+        format!("{ns:.0}ns")
+    } else if ns < 1e6 {
+        format!("{:.0}us", ns / 1e3)
+    } else if ns < 1e9 {
+        format!("{:.1}ms", ns / 1e6)
+    } else {
+        format!("{:.1}s", ns / 1e9)
+    }
+}
+
+/// Build a latency distribution heatmap: time on X, logarithmic latency
+/// buckets on Y and counts as color — the standard way to see
+/// distribution drift that percentile lines average away.
+///
+/// One input sample is the `(latency_ns, count)` pairs observed at the
+/// corresponding `x` label. The power-of-two bucket range is derived from
+/// the data; None when there are no observations at all.
+pub fn latency_heatmap(x: Vec<String>, samples: &[Vec<(f64, f64)>]) -> Option<HeatMap> {
+    let bucket = |ns: f64| ns.max(1.0).log2() as i32;
+    let mut lo = i32::MAX;
+    let mut hi = i32::MIN;
+    for sample in samples {
+        for (ns, count) in sample {
+            if *count > 0.0 {
+                lo = lo.min(bucket(*ns));
+                hi = hi.max(bucket(*ns));
+            }
+        }
+    }
+    if lo > hi {
+        return None;
+    }
+
+    let y: Vec<String> = (lo..=hi)
+        .map(|b| format!("<{}", format_ns(2f64.powi(b + 1))))
+        .collect();
+    let mut z = vec![vec![0.0; samples.len()]; y.len()];
+    for (column, sample) in samples.iter().enumerate() {
+        for (ns, count) in sample {
+            if *count > 0.0 {
+                z[(bucket(*ns) - lo) as usize][column] += count;
+            }
+        }
+    }
+    Some(HeatMap::new(x, y, z))
+}
+
+/// An HTML page holding one or more plots stacked vertically, optionally
+/// preceded by plain tables.
+pub struct Page {
+    title: String,
+    tables: Vec<(String, Vec<String>, Vec<Vec<String>>)>,
+    plots: Vec<Plot>,
+    marks: Vec<(String, String)>,
+    spans: Vec<(String, String, String)>,
+}
+
+/// One panel of a page. For selector panels, `selector` holds the button
+/// labels with the number of traces each button shows.
+struct Plot {
+    title: String,
+    traces: Vec<Value>,
+    selector: Option<Vec<(String, usize)>>,
+}
+
+impl Page {
+    pub fn new(title: &str) -> Self {
+        Page {
+            title: title.to_string(),
+            tables: Vec::new(),
+            plots: Vec::new(),
+            marks: Vec::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    /// Draw labeled vertical lines at the given times on every plot of the
+    /// page, e.g. for measurement window markers.
+    pub fn set_marks(&mut self, marks: &[(String, NaiveDateTime)]) {
+        self.marks = marks
+            .iter()
+            .map(|(name, time)| (name.clone(), plotly_time(time)))
+            .collect();
+    }
+
+    /// Shade labeled time intervals on every plot of the page, e.g. the
+    /// benchmark activity windows taken from the agent journal.
+    pub fn set_spans(&mut self, spans: &[(String, NaiveDateTime, NaiveDateTime)]) {
+        self.spans = spans
+            .iter()
+            .map(|(name, from, to)| (name.clone(), plotly_time(from), plotly_time(to)))
+            .collect();
+    }
+
+    /// Add a plot with the given title and traces.
+    pub fn add_plot(&mut self, title: &str, traces: Vec<Value>) {
+        self.plots.push(Plot {
+            title: title.to_string(),
+            traces,
+            selector: None,
+        });
+    }
+
+    /// Add a plot with a button row switching between named trace groups,
+    /// one group visible at a time — e.g. one heatmap per metric in a
+    /// single panel instead of a page of fixed ones.
+    pub fn add_selector_plot(&mut self, title: &str, options: Vec<(String, Vec<Value>)>) {
+        let mut traces = Vec::new();
+        let mut groups = Vec::new();
+        for (pos, (label, group)) in options.into_iter().enumerate() {
+            groups.push((label, group.len()));
+            for mut trace in group {
+                // Only the first group shows until a button is pressed.
+                if pos > 0 {
+                    trace["visible"] = json!(false);
+                }
+                traces.push(trace);
+            }
+        }
+        self.plots.push(Plot {
+            title: title.to_string(),
+            traces,
+            selector: Some(groups),
+        });
+    }
+
+    /// Add a plain table rendered above the plots.
+    pub fn add_table(&mut self, title: &str, header: Vec<String>, rows: Vec<Vec<String>>) {
+        self.tables.push((title.to_string(), header, rows));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plots.is_empty() && self.tables.is_empty()
+    }
+
+    /// Render the page to an HTML file.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+        writeln!(out, "<title>{}</title>", self.title)?;
+        writeln!(out, "<script src=\"{PLOTLY_CDN}\"></script>")?;
+        let dark = DARK.load(Ordering::Relaxed);
+        if dark {
+            writeln!(out, "<style>body {{ background: #111418; color: #dddddd; }}</style>")?;
+        }
+        writeln!(out, "</head><body>")?;
+        writeln!(out, "<h1>{}</h1>", self.title)?;
+
+        for (title, header, rows) in &self.tables {
+            writeln!(out, "<h2>{title}</h2>")?;
+            writeln!(out, "<table border=\"1\" cellpadding=\"4\">")?;
+            writeln!(out, "<tr><th>{}</th></tr>", header.join("</th><th>"))?;
+            for row in rows {
+                writeln!(out, "<tr><td>{}</td></tr>", row.join("</td><td>"))?;
+            }
+            writeln!(out, "</table>")?;
+        }
+
+        let origin = origin_for(path);
+        let time_x = |x: &str| -> Value {
+            match &origin {
+                Some(origin) => relative_x(&json!(x), origin),
+                None => json!(x),
+            }
+        };
+
+        let mut shapes: Vec<Value> = self
+            .marks
+            .iter()
+            .map(|(_, x)| {
+                json!({
+                    "type": "line",
+                    "x0": time_x(x), "x1": time_x(x),
+                    "yref": "paper", "y0": 0, "y1": 1,
+                    "line": { "color": "black", "dash": "dot" },
+                })
+            })
+            .collect();
+        let mut annotations: Vec<Value> = self
+            .marks
+            .iter()
+            .map(|(name, x)| {
+                json!({
+                    "x": time_x(x),
+                    "yref": "paper", "y": 1,
+                    "text": name,
+                    "showarrow": false,
+                    "yanchor": "bottom",
+                })
+            })
+            .collect();
+        for (name, x0, x1) in &self.spans {
+            shapes.push(json!({
+                "type": "rect",
+                "x0": time_x(x0), "x1": time_x(x1),
+                "yref": "paper", "y0": 0, "y1": 1,
+                "fillcolor": "rgba(128, 128, 160, 0.15)",
+                "line": { "width": 0 },
+                "layer": "below",
+            }));
+            annotations.push(json!({
+                "x": time_x(x0),
+                "yref": "paper", "y": 0.98,
+                "text": name,
+                "showarrow": false,
+                "xanchor": "left", "yanchor": "top",
+            }));
+        }
+
+        let limit = MAX_POINTS.load(Ordering::Relaxed);
+        let window = SMOOTH.load(Ordering::Relaxed);
+        let gap_factor = GAP_FACTOR.load(Ordering::Relaxed);
+        let log_y = LOG_Y.lock().unwrap().clone();
+        let columns = COLUMNS.load(Ordering::Relaxed);
+        writeln!(
+            out,
+            "<div style=\"display: grid; grid-template-columns: repeat({columns}, max-content)\">"
+        )?;
+        let max_traces = MAX_TRACES.load(Ordering::Relaxed);
+        for (index, plot) in self.plots.iter().enumerate() {
+            let traces = &plot.traces;
+            // Selector plots are exempt from the trace cap: dropping
+            // traces would desync the buttons' visibility masks.
+            let shown = match max_traces {
+                0 => traces.len(),
+                _ if plot.selector.is_some() => traces.len(),
+                cap => traces.len().min(cap),
+            };
+            let title = if shown < traces.len() {
+                eprintln!(
+                    "plotter: plot '{}': rendering {shown} of {} traces",
+                    plot.title,
+                    traces.len()
+                );
+                format!("{} (first {shown} of {} traces)", plot.title, traces.len())
+            } else {
+                plot.title.clone()
+            };
+            let mut layout = json!({
+                "title": { "text": title },
+                "width": PLOT_WIDTH.load(Ordering::Relaxed) / columns as u32,
+                "height": PLOT_HEIGHT.load(Ordering::Relaxed),
+                "shapes": shapes,
+                "annotations": annotations,
+            });
+            if log_y.as_ref().is_some_and(|re| re.is_match(&title)) {
+                layout["yaxis"] = json!({ "type": "log" });
+            }
+            if dark {
+                layout["paper_bgcolor"] = json!("#111418");
+                layout["plot_bgcolor"] = json!("#111418");
+                layout["font"] = json!({ "color": "#dddddd" });
+            }
+            if let Some(groups) = &plot.selector {
+                let buttons: Vec<Value> = groups
+                    .iter()
+                    .enumerate()
+                    .map(|(chosen, (label, _))| {
+                        let visible: Vec<bool> = groups
+                            .iter()
+                            .enumerate()
+                            .flat_map(|(pos, (_, count))| vec![pos == chosen; *count])
+                            .collect();
+                        json!({
+                            "label": label,
+                            "method": "update",
+                            "args": [{ "visible": visible }],
+                        })
+                    })
+                    .collect();
+                layout["updatemenus"] = json!([{
+                    "type": "buttons",
+                    "direction": "right",
+                    "x": 0.0, "xanchor": "left",
+                    "y": 1.08, "yanchor": "bottom",
+                    "buttons": buttons,
+                }]);
+            }
+            writeln!(out, "<div id=\"plot{index}\"></div>")?;
+            writeln!(out, "<script>")?;
+            // Stream the traces one by one: each is transformed, written
+            // out and dropped, so only one decimated copy is ever in
+            // memory no matter how many traces a plot accumulated.
+            write!(out, "Plotly.newPlot('plot{index}', [")?;
+            for (pos, trace) in traces.iter().take(shown).enumerate() {
+                let mut trace =
+                    break_gaps(&downsample_trace(&smooth_trace(trace, window), limit), gap_factor);
+                if let Some(origin) = &origin {
+                    if let Some(x) = trace["x"].as_array() {
+                        trace["x"] = x.iter().map(|v| relative_x(v, origin)).collect();
+                    }
+                }
+                if pos > 0 {
+                    write!(out, ",")?;
+                }
+                write!(out, "{trace}")?;
+            }
+            writeln!(out, "], {layout});")?;
+            writeln!(out, "</script>")?;
+        }
+        writeln!(out, "</div>")?;
+
+        writeln!(out, "</body></html>")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsampling_keeps_extremes() {
+        let mut trace = Scatter::new("t");
+        for i in 0..1000 {
+            trace.push(i.to_string(), if i == 500 { 100.0 } else { 0.0 });
+        }
+        let decimated = downsample_trace(&trace.to_trace(), 10);
+        let y = decimated["y"].as_array().unwrap();
+        assert!(y.len() <= 10);
+        assert!(y.iter().any(|v| v.as_f64() == Some(100.0)));
+    }
+
+    #[test]
+    fn smoothing_averages_trailing_window() {
+        let mut trace = Scatter::new("t");
+        for y in [0.0, 4.0, 8.0] {
+            trace.push(y.to_string(), y);
+        }
+        let smoothed = smooth_trace(&trace.to_trace(), 2);
+        assert_eq!(smoothed["y"], json!([0.0, 2.0, 6.0]));
+        assert_eq!(smooth_trace(&trace.to_trace(), 1), trace.to_trace());
+    }
+
+    #[test]
+    fn latency_heatmap_buckets_by_log2() {
+        let x = vec!["1".to_string(), "2".to_string()];
+        // 600ns and 1000ns share a power-of-two bucket, 5000ns lands
+        // three buckets up.
+        let samples = [vec![(600.0, 2.0), (1000.0, 1.0)], vec![(5000.0, 4.0)]];
+        let map = latency_heatmap(x, &samples).unwrap();
+        let trace = map.to_trace();
+        assert_eq!(trace["z"].as_array().unwrap().len(), 4);
+        assert_eq!(trace["z"][0], json!([3.0, 0.0]));
+        assert_eq!(trace["z"][3], json!([0.0, 4.0]));
+        assert_eq!(trace["y"][0], "<1us");
+        assert!(latency_heatmap(Vec::new(), &[]).is_none());
+    }
+
+    #[test]
+    fn wide_gaps_break_the_line() {
+        let origin = NaiveDateTime::parse_from_str("2026-08-26 10:00:00", "%Y-%m-%d %H:%M:%S")
+            .unwrap();
+        let mut trace = Scatter::new("t");
+        // One-second sampling with a minute-long pause in the middle.
+        for seconds in [0, 1, 2, 3, 63, 64, 65] {
+            trace.push(plotly_time(&(origin + chrono::Duration::seconds(seconds))), 1.0);
+        }
+        let broken = break_gaps(&trace.to_trace(), 10);
+        let y = broken["y"].as_array().unwrap();
+        assert_eq!(y.len(), 8);
+        assert!(y[4].is_null());
+        // The break sits in the middle of the gap.
+        assert_eq!(broken["x"][4], json!("2026-08-26 10:00:33.000"));
+
+        // Factor 0 turns the detection off.
+        assert_eq!(break_gaps(&trace.to_trace(), 0), trace.to_trace());
+        // Steady traces pass through untouched.
+        let mut steady = Scatter::new("s");
+        for seconds in 0..5 {
+            steady.push(plotly_time(&(origin + chrono::Duration::seconds(seconds))), 1.0);
+        }
+        assert_eq!(break_gaps(&steady.to_trace(), 10), steady.to_trace());
+    }
+
+    #[test]
+    fn relative_axis_converts_timestamps() {
+        let origin = NaiveDateTime::parse_from_str("2026-08-26 10:00:00", "%Y-%m-%d %H:%M:%S")
+            .unwrap();
+        let x = json!(plotly_time(&(origin + chrono::Duration::milliseconds(1500))));
+        assert_eq!(relative_x(&x, &origin), json!(1.5));
+        assert_eq!(relative_x(&json!("nvme0n1"), &origin), json!("nvme0n1"));
+    }
+
+    #[test]
+    fn short_traces_pass_through() {
+        let mut trace = Scatter::new("t");
+        trace.push("1".to_string(), 1.0);
+        let rendered = trace.to_trace();
+        assert_eq!(downsample_trace(&rendered, 10), rendered);
+    }
+}
@@ -0,0 +1,181 @@
+//! Cache server statistics sampled through the client tools.
+//!
+//! The agent runs a shell loop emitting the poll-log format with
+//! `redis-cli INFO` (`name:value` lines) or memcached `stats` output
+//! (`STAT name value` lines); one parser covers both.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::common::millis_to_naive;
+use crate::plot::{plotly_time, Page, Scatter};
+use crate::plotters::procfs::PollSamples;
+
+/// Parsed cache sampling: `values[<name>]`, cumulative counters and
+/// gauges alike. Non-numeric values are skipped at parse time.
+#[derive(Debug, Default)]
+pub struct CacheStat {
+    pub times: Vec<NaiveDateTime>,
+    pub values: BTreeMap<String, Vec<f64>>,
+}
+
+/// Parse a cache sampling log.
+pub fn parse(text: &str) -> Result<CacheStat, String> {
+    parse_reader(text.as_bytes())
+}
+
+/// Parse a cache sampling log incrementally from a reader.
+pub fn parse_reader<R: BufRead>(reader: R) -> Result<CacheStat, String> {
+    let mut stat = CacheStat::default();
+    for sample in PollSamples::new(reader) {
+        let sample = sample?;
+        stat.times.push(millis_to_naive(sample.millis));
+        for (_section, content) in &sample.files {
+            for line in content.lines() {
+                let line = line.trim_end_matches('\r');
+                let line = line.strip_prefix("STAT ").unwrap_or(line);
+                let Some((name, value)) = line
+                    .split_once(':')
+                    .or_else(|| line.split_once(char::is_whitespace))
+                else {
+                    continue;
+                };
+                let Ok(value) = value.trim().parse() else {
+                    continue;
+                };
+                stat.values
+                    .entry(name.trim().to_string())
+                    .or_default()
+                    .push(value);
+            }
+        }
+    }
+    Ok(stat)
+}
+
+/// Render the cache counters into `<name>.html`: operation rates, hit
+/// ratio, evictions and memory state. The counters present decide
+/// whether the Redis or the memcached set is plotted.
+pub fn plot(
+    stat: &CacheStat,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+    name: &str,
+) -> std::io::Result<()> {
+    let rates = |names: &[&str]| -> Vec<serde_json::Value> {
+        names
+            .iter()
+            .filter_map(|n| {
+                let counter = stat.values.get(*n)?;
+                Some(rate_trace(&stat.times, n, counter, 1.0))
+            })
+            .collect()
+    };
+    let gauges = |names: &[&str]| -> Vec<serde_json::Value> {
+        names
+            .iter()
+            .filter_map(|n| {
+                let values = stat.values.get(*n)?;
+                let mut trace = Scatter::new(n);
+                for (time, value) in stat.times.iter().zip(values) {
+                    trace.push(plotly_time(time), *value);
+                }
+                Some(trace.to_trace())
+            })
+            .collect()
+    };
+
+    let mut page = Page::new(name);
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    if stat.values.contains_key("total_commands_processed") {
+        page.add_plot("Commands/s", rates(&["total_commands_processed"]));
+        page.add_plot(
+            "Keyspace hit ratio, %",
+            hit_ratio(stat, "keyspace_hits", "keyspace_misses"),
+        );
+        page.add_plot(
+            "Evictions and expirations/s",
+            rates(&["evicted_keys", "expired_keys"]),
+        );
+        page.add_plot(
+            "Memory fragmentation ratio",
+            gauges(&["mem_fragmentation_ratio"]),
+        );
+    } else {
+        page.add_plot("Commands/s", rates(&["cmd_get", "cmd_set", "cmd_flush"]));
+        page.add_plot("Hit ratio, %", hit_ratio(stat, "get_hits", "get_misses"));
+        page.add_plot("Evictions/s", rates(&["evictions"]));
+        page.add_plot("Stored bytes", gauges(&["bytes"]));
+    }
+    page.write(&outdir.join(format!("{name}.html")))
+}
+
+/// Hit ratio over sampling windows: `hits / (hits + misses)` of the
+/// per-window deltas, in percent.
+fn hit_ratio(stat: &CacheStat, hits: &str, misses: &str) -> Vec<serde_json::Value> {
+    let (Some(hits), Some(misses)) = (stat.values.get(hits), stat.values.get(misses)) else {
+        return Vec::new();
+    };
+    let mut trace = Scatter::new("hit ratio");
+    let len = hits.len().min(misses.len()).min(stat.times.len());
+    for i in 1..len {
+        let dh = hits[i] - hits[i - 1];
+        let dm = misses[i] - misses[i - 1];
+        if dh + dm > 0.0 {
+            trace.push(plotly_time(&stat.times[i]), 100.0 * dh / (dh + dm));
+        }
+    }
+    vec![trace.to_trace()]
+}
+
+/// Turn a monotonic counter into a per-second rate trace.
+fn rate_trace(times: &[NaiveDateTime], name: &str, counter: &[f64], scale: f64) -> serde_json::Value {
+    let mut trace = Scatter::new(name);
+    for i in 1..counter.len().min(times.len()) {
+        let dt = (times[i] - times[i - 1]).num_milliseconds() as f64 / 1000.0;
+        if dt <= 0.0 {
+            continue;
+        }
+        let rate = (counter[i] - counter[i - 1]) / dt;
+        trace.push(plotly_time(&times[i]), rate * scale);
+    }
+    trace.to_trace()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REDIS_SAMPLE: &str = "\
+=== 1724690000000
+--- redis:info
+# Stats\r
+total_commands_processed:100\r
+keyspace_hits:90\r
+keyspace_misses:10\r
+mem_fragmentation_ratio:1.05\r
+db0:keys=3,expires=0\r
+";
+
+    #[test]
+    fn redis_info_parses() {
+        let stat = parse(REDIS_SAMPLE).unwrap();
+        assert_eq!(stat.times.len(), 1);
+        assert_eq!(stat.values["total_commands_processed"], [100.0]);
+        assert_eq!(stat.values["mem_fragmentation_ratio"], [1.05]);
+        // Keyspace summaries are not plain numbers.
+        assert!(!stat.values.contains_key("db0"));
+    }
+
+    #[test]
+    fn memcached_stats_parse() {
+        let sample = "=== 1724690000000\n--- memcached:stats\nSTAT cmd_get 42\nSTAT version 1.6.9\nEND\n";
+        let stat = parse(sample).unwrap();
+        assert_eq!(stat.values["cmd_get"], [42.0]);
+        assert!(!stat.values.contains_key("version"));
+    }
+}
@@ -0,0 +1,235 @@
+//! Side-by-side comparison of two runs.
+//!
+//! Overlays the same metrics from two run directories on a relative time
+//! axis and emits a per-agent delta table of the headline statistics, for
+//! quick regression analysis between a baseline and a candidate run.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::common::readfile;
+use crate::plot::{Page, Scatter};
+use crate::plotters::timeline::cpu_busy;
+use crate::plotters::{flame, read_mapping, read_marks, summary, sysstat};
+
+/// Unpacked agent subdirectories of a run, sorted by agent name.
+pub(crate) fn agent_dirs(run_dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(run_dir)? {
+        let entry = entry?;
+        if entry.path().join("out.map").exists() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            dirs.push((name, entry.path()));
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// CPU busy traces of one run on a relative time axis, labeled with the
+/// run tag.
+fn cpu_traces(run_dir: &Path, tag: &str) -> io::Result<Vec<serde_json::Value>> {
+    let mut traces = Vec::new();
+    for (agent, dir) in agent_dirs(run_dir)? {
+        for (id, name) in read_mapping(&dir)? {
+            if name != "mpstat" {
+                continue;
+            }
+            let text = readfile(&dir.join(format!("{id}-out.log")))?;
+            let stat = sysstat::mpstat::parse(&text).map_err(io::Error::other)?;
+            let Some(start) = stat.times.first().copied() else {
+                continue;
+            };
+            let mut trace = Scatter::new(&format!("{tag}/{agent}: cpu busy"));
+            for (time, busy) in stat.times.iter().zip(cpu_busy(&stat)) {
+                let sec = (*time - start).num_milliseconds() as f64 / 1000.0;
+                trace.push(format!("{sec:.3}"), busy);
+            }
+            traces.push(trace.to_trace());
+        }
+    }
+    Ok(traces)
+}
+
+/// Per-agent delta rows of the headline summaries of two runs.
+fn delta_rows(run_a: &Path, run_b: &Path) -> io::Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    for (agent, dir_a) in agent_dirs(run_a)? {
+        let Some((_, dir_b)) = agent_dirs(run_b)?.into_iter().find(|(b, _)| *b == agent) else {
+            continue;
+        };
+        let window_a = summary::mark_window(&read_marks(&dir_a));
+        let window_b = summary::mark_window(&read_marks(&dir_b));
+        let a = serde_json::to_value(summary::compute(&dir_a, window_a)?).expect("serializable");
+        let b = serde_json::to_value(summary::compute(&dir_b, window_b)?).expect("serializable");
+        for (metric, value_a) in a.as_object().expect("summary is an object") {
+            let (Some(va), Some(vb)) = (value_a.as_f64(), b[metric].as_f64()) else {
+                continue;
+            };
+            let delta = if va != 0.0 {
+                format!("{:+.1}%", (vb - va) / va * 100.0)
+            } else {
+                "-".to_string()
+            };
+            rows.push(vec![
+                agent.clone(),
+                metric.clone(),
+                format!("{va:.2}"),
+                format!("{vb:.2}"),
+                delta,
+            ]);
+        }
+    }
+    Ok(rows)
+}
+
+/// Generate differential flamegraphs for collapsed-stack files present
+/// under the same agent and name in both runs. The SVGs land next to
+/// `compare.html`; returned are their file names.
+fn diff_flamegraphs(run_a: &Path, run_b: &Path) -> io::Result<Vec<String>> {
+    let mut generated = Vec::new();
+    for (agent, dir_a) in agent_dirs(run_a)? {
+        let Some((_, dir_b)) = agent_dirs(run_b)?.into_iter().find(|(b, _)| *b == agent) else {
+            continue;
+        };
+        for before in flame::find_folded(&dir_a)? {
+            let Some(name) = before.file_name() else { continue };
+            let after = dir_b.join(name);
+            if !after.exists() {
+                continue;
+            }
+            let svg = format!(
+                "diff-{agent}-{}.svg",
+                name.to_string_lossy().trim_end_matches(".folded")
+            );
+            flame::diff(&before, &after, &run_a.join(&svg))?;
+            generated.push(svg);
+        }
+    }
+    Ok(generated)
+}
+
+/// The run metadata of one run directory, read from its `run.json`.
+fn run_meta(run_dir: &Path) -> io::Result<serde_json::Value> {
+    let text = readfile(&run_dir.join("run.json"))?;
+    serde_json::from_str(&text).map_err(io::Error::other)
+}
+
+/// Line diff with `-`/`+` markers and unchanged lines indented, based on
+/// the longest common subsequence — configs are small enough for the
+/// quadratic table.
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<String> {
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            out.push(format!("  {}", a[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            out.push(format!("- {}", a[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", b[j]));
+            j += 1;
+        }
+    }
+    out.extend(a[i..].iter().map(|line| format!("- {line}")));
+    out.extend(b[j..].iter().map(|line| format!("+ {line}")));
+    out
+}
+
+/// Print what differed between two runs' configurations: the config text
+/// as a line diff, plus the seed and the agent sysinfo recorded at
+/// pre-flight — the usual suspects when two runs of "the same" config
+/// disagree.
+pub fn diff_config(run_a: &Path, run_b: &Path) -> io::Result<()> {
+    let (meta_a, meta_b) = (run_meta(run_a)?, run_meta(run_b)?);
+
+    let config = |meta: &serde_json::Value| meta["config"].as_str().unwrap_or("").to_string();
+    let (config_a, config_b) = (config(&meta_a), config(&meta_b));
+    if config_a == config_b {
+        println!("config: identical");
+    } else {
+        println!("config:");
+        let a: Vec<&str> = config_a.lines().collect();
+        let b: Vec<&str> = config_b.lines().collect();
+        for line in diff_lines(&a, &b) {
+            println!("{line}");
+        }
+    }
+
+    if meta_a["seed"] != meta_b["seed"] {
+        println!("seed: {} -> {}", meta_a["seed"], meta_b["seed"]);
+    }
+
+    let agents = |meta: &serde_json::Value| meta["agents"].as_array().cloned().unwrap_or_default();
+    let (agents_a, agents_b) = (agents(&meta_a), agents(&meta_b));
+    let name_of = |agent: &serde_json::Value| agent["name"].as_str().unwrap_or("?").to_string();
+    for agent_a in &agents_a {
+        let name = name_of(agent_a);
+        let Some(agent_b) = agents_b.iter().find(|b| name_of(b) == name) else {
+            println!("agent '{name}': only in {}", run_a.display());
+            continue;
+        };
+        for field in ["addr", "local", "uname", "cpus", "mem_total_kb", "governor", "smt", "cmdline"]
+        {
+            if agent_a[field] != agent_b[field] {
+                println!("agent '{name}': {field}: {} -> {}", agent_a[field], agent_b[field]);
+            }
+        }
+    }
+    for agent_b in &agents_b {
+        let name = name_of(agent_b);
+        if !agents_a.iter().any(|a| name_of(a) == name) {
+            println!("agent '{name}': only in {}", run_b.display());
+        }
+    }
+    Ok(())
+}
+
+/// Render `compare.html` into the first run directory.
+pub fn plot(run_a: &Path, run_b: &Path) -> io::Result<()> {
+    let tag = |dir: &Path| {
+        dir.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| dir.display().to_string())
+    };
+    let (tag_a, tag_b) = (tag(run_a), tag(run_b));
+
+    let mut page = Page::new(&format!("compare {tag_a} vs {tag_b}"));
+    let header = ["agent", "metric", &tag_a, &tag_b, "delta"]
+        .map(str::to_string)
+        .to_vec();
+    page.add_table("Summary deltas", header, delta_rows(run_a, run_b)?);
+
+    let diffs = diff_flamegraphs(run_a, run_b)?;
+    if !diffs.is_empty() {
+        let rows = diffs
+            .iter()
+            .map(|svg| vec![format!("<a href=\"{svg}\">{svg}</a>")])
+            .collect();
+        page.add_table("Differential flamegraphs", vec!["graph".to_string()], rows);
+    }
+
+    let mut traces = cpu_traces(run_a, &tag_a)?;
+    traces.extend(cpu_traces(run_b, &tag_b)?);
+    if !traces.is_empty() {
+        page.add_plot("CPU busy, % (relative time, s)", traces);
+    }
+
+    if page.is_empty() {
+        return Err(io::Error::other("no comparable data found in the runs"));
+    }
+    page.write(&run_a.join("compare.html"))
+}
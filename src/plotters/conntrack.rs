@@ -0,0 +1,200 @@
+//! Connection tracking statistics from `/proc/sys/net/netfilter` and
+//! `/proc/net/stat/nf_conntrack`, optionally with nftables named
+//! counters.
+//!
+//! Plain file polling covers the sysctl values and the per-CPU stat
+//! table; with nftables counters enabled the activity switches to a
+//! shell loop emitting the same poll-log format, so both modes share
+//! this parser.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::common::millis_to_naive;
+use crate::plot::{plotly_time, Page, Scatter};
+use crate::plotters::procfs::PollSamples;
+
+/// Parsed conntrack sampling. `count`/`max` are gauges; `events` holds
+/// the cumulative per-CPU stat counters summed over CPUs; `nft` holds
+/// cumulative nftables counter values.
+#[derive(Debug, Default)]
+pub struct Conntrack {
+    pub times: Vec<NaiveDateTime>,
+    pub count: Vec<f64>,
+    pub max: Vec<f64>,
+    pub events: BTreeMap<String, Vec<f64>>,
+    pub nft: BTreeMap<String, Vec<f64>>,
+}
+
+/// Parse a conntrack sampling log.
+pub fn parse(text: &str) -> Result<Conntrack, String> {
+    parse_reader(text.as_bytes())
+}
+
+/// Parse a conntrack sampling log incrementally from a reader.
+pub fn parse_reader<R: BufRead>(reader: R) -> Result<Conntrack, String> {
+    let mut stat = Conntrack::default();
+    for sample in PollSamples::new(reader) {
+        let sample = sample?;
+        stat.times.push(millis_to_naive(sample.millis));
+        for (section, content) in &sample.files {
+            if section.ends_with("nf_conntrack_count") {
+                if let Ok(value) = content.trim().parse() {
+                    stat.count.push(value);
+                }
+            } else if section.ends_with("nf_conntrack_max") {
+                if let Ok(value) = content.trim().parse() {
+                    stat.max.push(value);
+                }
+            } else if section.ends_with("/proc/net/stat/nf_conntrack") {
+                parse_stat_table(content, &mut stat.events);
+            } else if section.ends_with("nft") {
+                parse_nft_counters(content, &mut stat.nft);
+            }
+        }
+    }
+    Ok(stat)
+}
+
+/// The per-CPU stat table: a header row followed by one row of hex
+/// values per CPU, summed here over the CPUs.
+fn parse_stat_table(content: &str, events: &mut BTreeMap<String, Vec<f64>>) {
+    let mut lines = content.lines();
+    let Some(header) = lines.next() else { return };
+    let names: Vec<&str> = header.split_whitespace().collect();
+    let mut sums = vec![0.0; names.len()];
+    for line in lines {
+        for (i, field) in line.split_whitespace().enumerate() {
+            if let Ok(value) = u64::from_str_radix(field, 16) {
+                if let Some(sum) = sums.get_mut(i) {
+                    *sum += value as f64;
+                }
+            }
+        }
+    }
+    for (name, sum) in names.iter().zip(sums) {
+        events.entry(name.to_string()).or_default().push(sum);
+    }
+}
+
+/// `nft list counters` output: named counter blocks with
+/// `packets N bytes M` lines.
+fn parse_nft_counters(content: &str, nft: &mut BTreeMap<String, Vec<f64>>) {
+    let mut counter = String::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("counter ") {
+            counter = rest.trim_end_matches('{').trim().to_string();
+        } else if let Some(rest) = line.strip_prefix("packets ") {
+            let mut fields = rest.split_whitespace();
+            let packets = fields.next().and_then(|f| f.parse().ok());
+            let bytes = fields.nth(1).and_then(|f| f.parse().ok());
+            if let (Some(packets), false) = (packets, counter.is_empty()) {
+                nft.entry(format!("{counter} packets"))
+                    .or_default()
+                    .push(packets);
+            }
+            if let (Some(bytes), false) = (bytes, counter.is_empty()) {
+                nft.entry(format!("{counter} bytes")).or_default().push(bytes);
+            }
+        }
+    }
+}
+
+/// Render conntrack occupancy and event rates into `conntrack.html`.
+pub fn plot(
+    stat: &Conntrack,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+) -> std::io::Result<()> {
+    let gauge = |name: &str, values: &[f64]| {
+        let mut trace = Scatter::new(name);
+        for (time, value) in stat.times.iter().zip(values) {
+            trace.push(plotly_time(time), *value);
+        }
+        trace.to_trace()
+    };
+
+    let mut page = Page::new("conntrack");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    page.add_plot(
+        "Tracked connections",
+        vec![gauge("count", &stat.count), gauge("max", &stat.max)],
+    );
+
+    // The telling counters around table exhaustion; the rest of the stat
+    // table is noise at this level.
+    let interesting = ["drop", "early_drop", "insert_failed", "invalid", "search_restart"];
+    let events = interesting
+        .iter()
+        .filter_map(|name| {
+            let counter = stat.events.get(*name)?;
+            Some(rate_trace(&stat.times, name, counter))
+        })
+        .collect();
+    page.add_plot("Conntrack events/s", events);
+
+    if !stat.nft.is_empty() {
+        let packets = stat
+            .nft
+            .iter()
+            .filter(|(name, _)| name.ends_with("packets"))
+            .map(|(name, counter)| rate_trace(&stat.times, name, counter))
+            .collect();
+        page.add_plot("nftables counters, packets/s", packets);
+    }
+    page.write(&outdir.join("conntrack.html"))
+}
+
+/// Turn a monotonic counter into a per-second rate trace.
+fn rate_trace(times: &[NaiveDateTime], name: &str, counter: &[f64]) -> serde_json::Value {
+    let mut trace = Scatter::new(name);
+    for i in 1..counter.len().min(times.len()) {
+        let dt = (times[i] - times[i - 1]).num_milliseconds() as f64 / 1000.0;
+        if dt <= 0.0 {
+            continue;
+        }
+        trace.push(plotly_time(&times[i]), (counter[i] - counter[i - 1]) / dt);
+    }
+    trace.to_trace()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+=== 1724690000000
+--- /proc/sys/net/netfilter/nf_conntrack_count
+1500
+--- /proc/sys/net/netfilter/nf_conntrack_max
+262144
+--- /proc/net/stat/nf_conntrack
+entries  searched found new invalid ignore delete delete_list insert insert_failed drop early_drop icmp_error  expect_new expect_create expect_delete search_restart
+000005dc  00000000 0000000a 00000000 00000002 00000000 00000000 00000000 00000000 00000001 00000003 00000000 00000000  00000000 00000000 00000000 00000000
+000005dc  00000000 00000014 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000001 00000000 00000000  00000000 00000000 00000000 00000000
+--- conntrack:nft
+table ip filter {
+\tcounter http {
+\t\tpackets 100 bytes 5000
+\t}
+}
+";
+
+    #[test]
+    fn conntrack_samples_parse() {
+        let stat = parse(SAMPLE).unwrap();
+        assert_eq!(stat.count, [1500.0]);
+        assert_eq!(stat.max, [262144.0]);
+        // Summed over the two CPU rows.
+        assert_eq!(stat.events["found"], [30.0]);
+        assert_eq!(stat.events["drop"], [4.0]);
+        assert_eq!(stat.events["insert_failed"], [1.0]);
+        assert_eq!(stat.nft["http packets"], [100.0]);
+        assert_eq!(stat.nft["http bytes"], [5000.0]);
+    }
+}
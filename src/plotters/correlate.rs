@@ -0,0 +1,193 @@
+//! Correlation view between two metrics of one agent.
+//!
+//! Renders both metrics on a shared time axis plus a scatter of one
+//! against the other with a fitted trend line, for quick "is X driven
+//! by Y" checks.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use serde_json::json;
+
+use crate::common::readfile;
+use crate::plot::{plotly_time, Page, Scatter};
+use crate::plotters::{read_mapping, sysstat, timeline};
+
+/// One named time series resolved from the parsed captures.
+pub struct Series {
+    pub times: Vec<NaiveDateTime>,
+    pub values: Vec<f64>,
+}
+
+/// Resolve a metric name against the captures of an agent directory.
+/// Supported names are `cpu_busy_avg` and `<device>_<column>` with an
+/// iostat column of util/rps/wps/rkbps/wkbps, e.g. `nvme0n1_util`.
+pub fn resolve(dir: &Path, metric: &str) -> io::Result<Series> {
+    for (id, name) in read_mapping(dir)? {
+        match name.as_str() {
+            "mpstat" if metric == "cpu_busy_avg" => {
+                let text = readfile(&dir.join(format!("{id}-out.log")))?;
+                let stat = sysstat::mpstat::parse(&text).map_err(io::Error::other)?;
+                return Ok(Series {
+                    values: timeline::cpu_busy(&stat),
+                    times: stat.times,
+                });
+            }
+            "iostat" => {
+                let Some((device, column)) = metric.rsplit_once('_') else {
+                    continue;
+                };
+                let text = readfile(&dir.join(format!("{id}-out.log")))?;
+                let stat = sysstat::iostat::parse(&text).map_err(io::Error::other)?;
+                let Some(stats) = stat.devices.get(device) else {
+                    continue;
+                };
+                let values = match column {
+                    "util" => &stats.util,
+                    "rps" => &stats.rps,
+                    "wps" => &stats.wps,
+                    "rkbps" => &stats.rkbps,
+                    "wkbps" => &stats.wkbps,
+                    _ => continue,
+                };
+                return Ok(Series {
+                    times: stat.times,
+                    values: values.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+    Err(io::Error::other(format!(
+        "unknown metric '{metric}': expected cpu_busy_avg or <device>_<util|rps|wps|rkbps|wkbps>"
+    )))
+}
+
+/// Pair two series sampled at independent periods by averaging each into
+/// one-second buckets and keeping the seconds both cover.
+fn paired(a: &Series, b: &Series) -> Vec<(f64, f64)> {
+    let bucket = |series: &Series| -> BTreeMap<i64, f64> {
+        let mut sums: BTreeMap<i64, (f64, usize)> = BTreeMap::new();
+        for (time, value) in series.times.iter().zip(&series.values) {
+            let entry = sums.entry(time.and_utc().timestamp()).or_default();
+            entry.0 += value;
+            entry.1 += 1;
+        }
+        sums.into_iter()
+            .map(|(sec, (sum, count))| (sec, sum / count as f64))
+            .collect()
+    };
+    let b = bucket(b);
+    bucket(a)
+        .into_iter()
+        .filter_map(|(sec, x)| Some((x, *b.get(&sec)?)))
+        .collect()
+}
+
+/// Least-squares line fit of y over x plus the Pearson correlation
+/// coefficient, as (slope, intercept, r). None for degenerate inputs.
+fn fit(pairs: &[(f64, f64)]) -> Option<(f64, f64, f64)> {
+    let n = pairs.len() as f64;
+    if pairs.len() < 2 {
+        return None;
+    }
+    let mean_x = pairs.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let mut cov = 0.0;
+    let mut var_x = 0.0;
+    let mut var_y = 0.0;
+    for (x, y) in pairs {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x) * (x - mean_x);
+        var_y += (y - mean_y) * (y - mean_y);
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        return None;
+    }
+    let slope = cov / var_x;
+    Some((slope, mean_y - slope * mean_x, cov / (var_x * var_y).sqrt()))
+}
+
+/// Render `correlate.html` relating the two metrics in an agent
+/// directory.
+pub fn plot(dir: &Path, metric_a: &str, metric_b: &str) -> io::Result<()> {
+    let a = resolve(dir, metric_a)?;
+    let b = resolve(dir, metric_b)?;
+    let pairs = paired(&a, &b);
+    if pairs.is_empty() {
+        return Err(io::Error::other(format!(
+            "no overlapping samples between {metric_a} and {metric_b}"
+        )));
+    }
+
+    let mut page = Page::new(&format!("{metric_a} vs {metric_b}"));
+
+    let mut time_traces = Vec::new();
+    for (metric, series) in [(metric_a, &a), (metric_b, &b)] {
+        let mut trace = Scatter::new(metric);
+        for (time, value) in series.times.iter().zip(&series.values) {
+            trace.push(plotly_time(time), *value);
+        }
+        time_traces.push(trace.to_trace());
+    }
+    page.add_plot("Shared time axis", time_traces);
+
+    let mut traces = vec![json!({
+        "type": "scatter",
+        "mode": "markers",
+        "name": "samples",
+        "x": pairs.iter().map(|(x, _)| x).collect::<Vec<_>>(),
+        "y": pairs.iter().map(|(_, y)| y).collect::<Vec<_>>(),
+    })];
+    if let Some((slope, intercept, r)) = fit(&pairs) {
+        let (min_x, max_x) = pairs
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (x, _)| {
+                (lo.min(*x), hi.max(*x))
+            });
+        traces.push(json!({
+            "type": "scatter",
+            "mode": "lines",
+            "name": format!("trend (r = {r:.2})"),
+            "x": [min_x, max_x],
+            "y": [slope * min_x + intercept, slope * max_x + intercept],
+        }));
+    }
+    page.add_plot(&format!("{metric_b} vs {metric_a}"), traces);
+
+    page.write(&dir.join("correlate.html"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perfect_line_fits() {
+        let pairs = [(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)];
+        let (slope, intercept, r) = fit(&pairs).unwrap();
+        assert_eq!(slope, 2.0);
+        assert_eq!(intercept, 1.0);
+        assert_eq!(r, 1.0);
+        assert_eq!(fit(&[(1.0, 1.0)]), None);
+    }
+
+    #[test]
+    fn pairing_keeps_common_seconds() {
+        let time = |s: &str| {
+            NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.3f").unwrap()
+        };
+        let a = Series {
+            times: vec![time("2026-08-26 10:00:00.100"), time("2026-08-26 10:00:00.600")],
+            values: vec![1.0, 3.0],
+        };
+        let b = Series {
+            times: vec![time("2026-08-26 10:00:00.400"), time("2026-08-26 10:00:01.000")],
+            values: vec![5.0, 7.0],
+        };
+        // Only second 0 overlaps; the two A samples in it are averaged.
+        assert_eq!(paired(&a, &b), [(2.0, 5.0)]);
+    }
+}
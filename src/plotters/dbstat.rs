@@ -0,0 +1,214 @@
+//! Database statistics sampled through the client tools.
+//!
+//! The agent runs a small shell loop emitting the poll-log format
+//! (`=== <millis>` / `--- pgstat:...` or `--- mysql:status`) with
+//! `name|value` (psql `-Atx`) or `name<TAB>value` (`mysql -NB`) lines,
+//! so one parser covers both databases.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::common::millis_to_naive;
+use crate::plot::{plotly_time, Page, Scatter};
+use crate::plotters::procfs::PollSamples;
+
+/// Parsed database sampling: cumulative `counters[<name>]`. Non-numeric
+/// values (timestamps, version strings) are skipped at parse time.
+#[derive(Debug, Default)]
+pub struct DbStat {
+    pub times: Vec<NaiveDateTime>,
+    pub counters: BTreeMap<String, Vec<f64>>,
+}
+
+/// Parse a database sampling log.
+pub fn parse(text: &str) -> Result<DbStat, String> {
+    parse_reader(text.as_bytes())
+}
+
+/// Parse a database sampling log incrementally from a reader.
+pub fn parse_reader<R: BufRead>(reader: R) -> Result<DbStat, String> {
+    let mut stat = DbStat::default();
+    for sample in PollSamples::new(reader) {
+        let sample = sample?;
+        stat.times.push(millis_to_naive(sample.millis));
+        for (_section, content) in &sample.files {
+            for line in content.lines() {
+                let Some((name, value)) = line
+                    .split_once('|')
+                    .or_else(|| line.split_once(char::is_whitespace))
+                else {
+                    continue;
+                };
+                let Ok(value) = value.trim().parse() else {
+                    continue;
+                };
+                stat.counters
+                    .entry(name.trim().to_string())
+                    .or_default()
+                    .push(value);
+            }
+        }
+    }
+    Ok(stat)
+}
+
+/// Render the database counters into `<name>.html`: transaction rates,
+/// buffer cache hit ratio and checkpoint/flush activity. The counters
+/// present decide whether the PostgreSQL or the MySQL set is plotted.
+pub fn plot(
+    stat: &DbStat,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+    name: &str,
+) -> std::io::Result<()> {
+    let rates = |names: &[&str]| -> Vec<serde_json::Value> {
+        names
+            .iter()
+            .filter_map(|n| {
+                let counter = stat.counters.get(*n)?;
+                Some(rate_trace(&stat.times, n, counter, 1.0))
+            })
+            .collect()
+    };
+
+    let mut page = Page::new(name);
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    if stat.counters.contains_key("xact_commit") {
+        page.add_plot(
+            "Transactions/s",
+            rates(&["xact_commit", "xact_rollback", "deadlocks"]),
+        );
+        page.add_plot(
+            "Buffer cache hit ratio, %",
+            hit_ratio(stat, "hit ratio", "blks_hit", "blks_read"),
+        );
+        page.add_plot(
+            "Checkpoints and bgwriter, per second",
+            rates(&[
+                "checkpoints_timed",
+                "checkpoints_req",
+                "buffers_checkpoint",
+                "buffers_clean",
+                "buffers_backend",
+            ]),
+        );
+        page.add_plot(
+            "Row operations/s",
+            rates(&["tup_inserted", "tup_updated", "tup_deleted"]),
+        );
+    } else {
+        page.add_plot(
+            "Statements/s",
+            rates(&["Questions", "Com_commit", "Com_rollback"]),
+        );
+        page.add_plot(
+            "InnoDB buffer pool hit ratio, %",
+            innodb_hit_ratio(stat),
+        );
+        page.add_plot(
+            "InnoDB flush activity",
+            rates(&["Innodb_buffer_pool_pages_flushed", "Innodb_os_log_written"]),
+        );
+    }
+    page.write(&outdir.join(format!("{name}.html")))
+}
+
+/// Hit ratio over sampling windows: `hits / (hits + misses)` of the
+/// per-window deltas, in percent.
+fn hit_ratio(stat: &DbStat, name: &str, hits: &str, misses: &str) -> Vec<serde_json::Value> {
+    let (Some(hits), Some(misses)) = (stat.counters.get(hits), stat.counters.get(misses)) else {
+        return Vec::new();
+    };
+    let mut trace = Scatter::new(name);
+    let len = hits.len().min(misses.len()).min(stat.times.len());
+    for i in 1..len {
+        let dh = hits[i] - hits[i - 1];
+        let dm = misses[i] - misses[i - 1];
+        if dh + dm > 0.0 {
+            trace.push(plotly_time(&stat.times[i]), 100.0 * dh / (dh + dm));
+        }
+    }
+    vec![trace.to_trace()]
+}
+
+/// InnoDB reports read requests and physical reads, not hits: hits are
+/// the requests the pool satisfied itself.
+fn innodb_hit_ratio(stat: &DbStat) -> Vec<serde_json::Value> {
+    let (Some(requests), Some(reads)) = (
+        stat.counters.get("Innodb_buffer_pool_read_requests"),
+        stat.counters.get("Innodb_buffer_pool_reads"),
+    ) else {
+        return Vec::new();
+    };
+    let mut trace = Scatter::new("hit ratio");
+    let len = requests.len().min(reads.len()).min(stat.times.len());
+    for i in 1..len {
+        let dreq = requests[i] - requests[i - 1];
+        let dreads = reads[i] - reads[i - 1];
+        if dreq > 0.0 {
+            trace.push(plotly_time(&stat.times[i]), 100.0 * (dreq - dreads) / dreq);
+        }
+    }
+    vec![trace.to_trace()]
+}
+
+/// Turn a monotonic counter into a per-second rate trace.
+fn rate_trace(times: &[NaiveDateTime], name: &str, counter: &[f64], scale: f64) -> serde_json::Value {
+    let mut trace = Scatter::new(name);
+    for i in 1..counter.len().min(times.len()) {
+        let dt = (times[i] - times[i - 1]).num_milliseconds() as f64 / 1000.0;
+        if dt <= 0.0 {
+            continue;
+        }
+        let rate = (counter[i] - counter[i - 1]) / dt;
+        trace.push(plotly_time(&times[i]), rate * scale);
+    }
+    trace.to_trace()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PG_SAMPLE: &str = "\
+=== 1724690000000
+--- pgstat:database
+xact_commit|100
+xact_rollback|1
+blks_read|50
+blks_hit|950
+--- pgstat:bgwriter
+checkpoints_timed|2
+stats_reset|2026-08-26 10:00:00+00
+=== 1724690001000
+--- pgstat:database
+xact_commit|200
+xact_rollback|2
+blks_read|60
+blks_hit|1940
+--- pgstat:bgwriter
+checkpoints_timed|2
+stats_reset|2026-08-26 10:00:00+00
+";
+
+    #[test]
+    fn psql_expanded_output_parses() {
+        let stat = parse(PG_SAMPLE).unwrap();
+        assert_eq!(stat.times.len(), 2);
+        assert_eq!(stat.counters["xact_commit"], [100.0, 200.0]);
+        assert_eq!(stat.counters["checkpoints_timed"], [2.0, 2.0]);
+        // Timestamps are not counters.
+        assert!(!stat.counters.contains_key("stats_reset"));
+    }
+
+    #[test]
+    fn mysql_tab_separated_output_parses() {
+        let sample = "=== 1724690000000\n--- mysql:status\nQuestions\t42\nUptime\t7\n";
+        let stat = parse(sample).unwrap();
+        assert_eq!(stat.counters["Questions"], [42.0]);
+    }
+}
@@ -0,0 +1,134 @@
+//! Per-queue NIC statistics via `ethtool -S` sampling.
+//!
+//! The agent runs a small shell loop emitting the poll-log format
+//! (`=== <millis>` / `--- ethtool:<iface>`), so the plotter reuses the
+//! streaming poll sample iterator.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::common::millis_to_naive;
+use crate::plot::{plotly_time, Page, Scatter};
+use crate::plotters::procfs::PollSamples;
+
+/// Parsed ethtool sampling: cumulative `counters[<iface> <name>]`.
+#[derive(Debug, Default)]
+pub struct Ethtool {
+    pub times: Vec<NaiveDateTime>,
+    pub counters: BTreeMap<String, Vec<f64>>,
+}
+
+/// Parse an ethtool sampling log.
+pub fn parse(text: &str) -> Result<Ethtool, String> {
+    parse_reader(text.as_bytes())
+}
+
+/// Parse an ethtool sampling log incrementally from a reader. Only the
+/// per-queue counters are kept: aggregate NIC totals are already covered
+/// by the netdev plots.
+pub fn parse_reader<R: BufRead>(reader: R) -> Result<Ethtool, String> {
+    let mut stat = Ethtool::default();
+    for sample in PollSamples::new(reader) {
+        let sample = sample?;
+        stat.times.push(millis_to_naive(sample.millis));
+        for (section, content) in &sample.files {
+            let Some(iface) = section.strip_prefix("ethtool:") else {
+                continue;
+            };
+            for line in content.lines() {
+                let Some((name, value)) = line.rsplit_once(':') else {
+                    continue; // "NIC statistics:" banner
+                };
+                let name = name.trim();
+                // Driver-specific queue naming: rx_queue_0_bytes (igb),
+                // tx-0.packets (mlx5), rx0_bytes and friends.
+                if !name.contains("queue") && !name.contains('-') {
+                    continue;
+                }
+                let Ok(value) = value.trim().parse() else {
+                    continue;
+                };
+                stat.counters
+                    .entry(format!("{iface} {name}"))
+                    .or_default()
+                    .push(value);
+            }
+        }
+    }
+    Ok(stat)
+}
+
+/// Render per-queue byte and packet rates into `ethtool.html`.
+pub fn plot(
+    stat: &Ethtool,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+) -> std::io::Result<()> {
+    let mut bits = Vec::new();
+    let mut packets = Vec::new();
+    for (name, counter) in &stat.counters {
+        let iface = name.split(' ').next().unwrap_or(name);
+        if !crate::plotters::filter::iface_shown(iface) {
+            continue;
+        }
+        if name.ends_with("bytes") {
+            bits.push(rate_trace(&stat.times, name, counter, 8e-6));
+        } else if name.ends_with("packets") {
+            packets.push(rate_trace(&stat.times, name, counter, 1.0));
+        }
+    }
+
+    let mut page = Page::new("ethtool");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    page.add_plot("Per-queue traffic, Mbit/s", bits);
+    page.add_plot("Per-queue packets/s", packets);
+    page.write(&outdir.join("ethtool.html"))
+}
+
+/// Turn a monotonic counter into a per-second rate trace.
+fn rate_trace(times: &[NaiveDateTime], name: &str, counter: &[f64], scale: f64) -> serde_json::Value {
+    let mut trace = Scatter::new(name);
+    for i in 1..counter.len().min(times.len()) {
+        let dt = (times[i] - times[i - 1]).num_milliseconds() as f64 / 1000.0;
+        if dt <= 0.0 {
+            continue;
+        }
+        let rate = (counter[i] - counter[i - 1]) / dt;
+        trace.push(plotly_time(&times[i]), rate * scale);
+    }
+    trace.to_trace()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+=== 1724690000000
+--- ethtool:eth0
+NIC statistics:
+     rx_packets: 100
+     rx_queue_0_bytes: 1000
+     tx_queue_0_bytes: 500
+=== 1724690001000
+--- ethtool:eth0
+NIC statistics:
+     rx_packets: 200
+     rx_queue_0_bytes: 3000
+     tx_queue_0_bytes: 700
+";
+
+    #[test]
+    fn queue_counters_parse() {
+        let stat = parse(SAMPLE).unwrap();
+        assert_eq!(stat.times.len(), 2);
+        assert_eq!(stat.counters["eth0 rx_queue_0_bytes"], [1000.0, 3000.0]);
+        assert_eq!(stat.counters["eth0 tx_queue_0_bytes"], [500.0, 700.0]);
+        // Aggregate counters stay out; netdev covers them.
+        assert!(!stat.counters.contains_key("eth0 rx_packets"));
+    }
+}
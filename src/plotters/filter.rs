@@ -0,0 +1,81 @@
+//! Trace filtering for hosts with many block devices and interfaces.
+//!
+//! Without filters every device found in the capture becomes a trace,
+//! which makes charts from hosts with dozens of loop/dm devices or veth
+//! interfaces unreadable. The filters are process-wide, like the point
+//! cap in [`crate::plot`], so the individual plotters stay unchanged.
+
+use std::sync::Mutex;
+
+use regex::Regex;
+
+/// Include/exclude pair applied to one name category.
+#[derive(Debug, Default)]
+struct Filter {
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+}
+
+impl Filter {
+    fn shown(&self, name: &str) -> bool {
+        if let Some(include) = &self.include {
+            if !include.is_match(name) {
+                return false;
+            }
+        }
+        if let Some(exclude) = &self.exclude {
+            if exclude.is_match(name) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+static DEVICES: Mutex<Option<Filter>> = Mutex::new(None);
+static IFACES: Mutex<Option<Filter>> = Mutex::new(None);
+
+fn set(slot: &Mutex<Option<Filter>>, include: Option<Regex>, exclude: Option<Regex>) {
+    *slot.lock().unwrap() = Some(Filter { include, exclude });
+}
+
+fn shown(slot: &Mutex<Option<Filter>>, name: &str) -> bool {
+    slot.lock().unwrap().as_ref().is_none_or(|f| f.shown(name))
+}
+
+/// Restrict the plotted block devices.
+pub fn set_devices(include: Option<Regex>, exclude: Option<Regex>) {
+    set(&DEVICES, include, exclude);
+}
+
+/// Restrict the plotted network interfaces.
+pub fn set_ifaces(include: Option<Regex>, exclude: Option<Regex>) {
+    set(&IFACES, include, exclude);
+}
+
+/// Whether a block device passes the configured filter.
+pub fn device_shown(name: &str) -> bool {
+    shown(&DEVICES, name)
+}
+
+/// Whether a network interface passes the configured filter.
+pub fn iface_shown(name: &str) -> bool {
+    shown(&IFACES, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn include_and_exclude() {
+        let filter = Filter {
+            include: Some(Regex::new("^(sd|nvme)").unwrap()),
+            exclude: Some(Regex::new("^sdz").unwrap()),
+        };
+        assert!(filter.shown("sda"));
+        assert!(filter.shown("nvme0n1"));
+        assert!(!filter.shown("loop3"));
+        assert!(!filter.shown("sdz"));
+    }
+}
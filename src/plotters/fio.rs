@@ -0,0 +1,415 @@
+//! fio log parsing and plotting.
+//!
+//! The fio activity runs with `--write_bw_log=<prefix>` and
+//! `--write_hist_log=<prefix>`, producing `<prefix>_bw.<job>.log` and
+//! `<prefix>_clat_hist.<job>.log` files in the session directory.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use crate::common::readfile;
+use crate::plot::{self, Page, Scatter};
+
+/// Parse one fio bandwidth log into `(seconds, MiB/s)` samples.
+///
+/// Log lines are `msec, value, direction, block_size, offset` with the
+/// value in KiB/s.
+pub fn parse_bw_log(text: &str) -> Result<Vec<(f64, f64)>, String> {
+    let mut samples = Vec::new();
+    for line in text.lines() {
+        let mut fields = line.split(',').map(str::trim);
+        let msec: f64 = fields
+            .next()
+            .ok_or("empty fio log line")?
+            .parse()
+            .map_err(|e| format!("bad fio timestamp in '{line}': {e}"))?;
+        let value: f64 = fields
+            .next()
+            .ok_or_else(|| format!("no value in fio log line: {line}"))?
+            .parse()
+            .map_err(|e| format!("bad fio value in '{line}': {e}"))?;
+        samples.push((msec / 1000.0, value / 1024.0));
+    }
+    Ok(samples)
+}
+
+/// One sample of a fio histogram log: a timestamp plus the latency bin
+/// counters, merged over data directions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistSample {
+    pub sec: f64,
+    pub bins: Vec<u64>,
+}
+
+/// Parse one fio histogram log (`msec, direction, block_size, bins...`),
+/// merging lines with the same timestamp.
+pub fn parse_hist_log(text: &str) -> Result<Vec<HistSample>, String> {
+    let mut samples: Vec<HistSample> = Vec::new();
+    for line in text.lines() {
+        let mut fields = line.split(',').map(str::trim);
+        let msec: f64 = fields
+            .next()
+            .ok_or("empty fio hist line")?
+            .parse()
+            .map_err(|e| format!("bad fio hist timestamp in '{line}': {e}"))?;
+        // Skip the direction and block size columns.
+        let bins: Vec<u64> = fields
+            .skip(2)
+            .map(|v| v.parse().map_err(|e| format!("bad fio hist bin '{v}': {e}")))
+            .collect::<Result<_, _>>()?;
+
+        let sec = msec / 1000.0;
+        match samples.last_mut() {
+            Some(last) if last.sec == sec && last.bins.len() == bins.len() => {
+                for (acc, bin) in last.bins.iter_mut().zip(&bins) {
+                    *acc += bin;
+                }
+            }
+            _ => samples.push(HistSample { sec, bins }),
+        }
+    }
+    Ok(samples)
+}
+
+// fio histogram bin layout (stat.h): FIO_IO_U_PLAT_BITS bits of precision
+// per power-of-two group.
+const PLAT_BITS: u64 = 6;
+const PLAT_VAL: u64 = 1 << PLAT_BITS;
+
+/// Midpoint latency of a fio histogram bin, in nanoseconds.
+pub fn plat_idx_to_val(idx: usize) -> f64 {
+    let idx = idx as u64;
+    if idx < (PLAT_VAL << 1) {
+        return idx as f64;
+    }
+    let error_bits = (idx >> PLAT_BITS) - 1;
+    let base = 1u64 << (error_bits + PLAT_BITS);
+    let k = idx % PLAT_VAL;
+    base as f64 + (k as f64 + 0.5) * (1u64 << error_bits) as f64
+}
+
+/// Latency value (ns) below which `pct` percent of the sample's IOs fall.
+fn hist_percentile(bins: &[u64], pct: f64) -> Option<f64> {
+    let total: u64 = bins.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let threshold = (total as f64 * pct / 100.0).ceil() as u64;
+    let mut seen = 0;
+    for (idx, count) in bins.iter().enumerate() {
+        seen += count;
+        if seen >= threshold {
+            return Some(plat_idx_to_val(idx));
+        }
+    }
+    None
+}
+
+/// Headline metrics of one fio job direction taken from the JSON output.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobDirection {
+    pub iops: f64,
+    pub bw_mibps: f64,
+    pub clat_p50_ms: Option<f64>,
+    pub clat_p99_ms: Option<f64>,
+}
+
+/// Per-job summary parsed from `fio --output-format=json`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobStats {
+    pub jobname: String,
+    pub read: JobDirection,
+    pub write: JobDirection,
+}
+
+fn parse_direction(job: &serde_json::Value, dir: &str) -> JobDirection {
+    let section = &job[dir];
+    let percentile = |pct: &str| {
+        section["clat_ns"]["percentile"][pct]
+            .as_f64()
+            .map(|ns| ns / 1e6)
+    };
+    JobDirection {
+        iops: section["iops"].as_f64().unwrap_or(0.0),
+        bw_mibps: section["bw_bytes"]
+            .as_f64()
+            .map(|b| b / (1024.0 * 1024.0))
+            .or_else(|| section["bw"].as_f64().map(|kib| kib / 1024.0))
+            .unwrap_or(0.0),
+        clat_p50_ms: percentile("50.000000"),
+        clat_p99_ms: percentile("99.000000"),
+    }
+}
+
+/// Parse the `fio --output-format=json` report into per-job summaries.
+pub fn parse_json(text: &str) -> Result<Vec<JobStats>, String> {
+    let root: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| format!("bad fio json: {e}"))?;
+    let jobs = root["jobs"]
+        .as_array()
+        .ok_or("no jobs array in fio json")?;
+    Ok(jobs
+        .iter()
+        .map(|job| JobStats {
+            jobname: job["jobname"].as_str().unwrap_or("?").to_string(),
+            read: parse_direction(job, "read"),
+            write: parse_direction(job, "write"),
+        })
+        .collect())
+}
+
+fn jobs_table(jobs: &[JobStats]) -> (Vec<String>, Vec<Vec<String>>) {
+    let header = [
+        "job", "dir", "IOPS", "BW, MiB/s", "clat p50, ms", "clat p99, ms",
+    ]
+    .map(str::to_string)
+    .to_vec();
+    let fmt_ms = |ms: Option<f64>| ms.map_or_else(|| "-".to_string(), |v| format!("{v:.3}"));
+    let mut rows = Vec::new();
+    for job in jobs {
+        for (dir, stats) in [("read", &job.read), ("write", &job.write)] {
+            if stats.iops == 0.0 && stats.bw_mibps == 0.0 {
+                continue;
+            }
+            rows.push(vec![
+                job.jobname.clone(),
+                dir.to_string(),
+                format!("{:.0}", stats.iops),
+                format!("{:.1}", stats.bw_mibps),
+                fmt_ms(stats.clat_p50_ms),
+                fmt_ms(stats.clat_p99_ms),
+            ]);
+        }
+    }
+    (header, rows)
+}
+
+/// The 1-based job number of a numbered fio log, e.g. 2 for
+/// `fio_bw.2.log`. fio numbers the logs in job definition order, matching
+/// the `jobs` array of the JSON report.
+fn log_index(name: &str) -> Option<usize> {
+    name.strip_suffix(".log")?.rsplit('.').next()?.parse().ok()
+}
+
+/// Sum the per-job bandwidth series into one total. The jobs log on
+/// independent timestamps, so each series is averaged into one-second
+/// buckets before summing.
+fn total_bw(jobs: &[Vec<(f64, f64)>]) -> Vec<(f64, f64)> {
+    let mut total: BTreeMap<i64, f64> = BTreeMap::new();
+    for samples in jobs {
+        let mut buckets: BTreeMap<i64, (f64, usize)> = BTreeMap::new();
+        for (sec, mibps) in samples {
+            let bucket = buckets.entry(*sec as i64).or_default();
+            bucket.0 += mibps;
+            bucket.1 += 1;
+        }
+        for (sec, (sum, count)) in buckets {
+            *total.entry(sec).or_default() += sum / count as f64;
+        }
+    }
+    total.into_iter().map(|(sec, v)| (sec as f64, v)).collect()
+}
+
+/// Merge the histogram samples of several jobs, summing the bins of
+/// samples falling into the same second.
+fn merge_hists(jobs: &[Vec<HistSample>]) -> Vec<HistSample> {
+    let mut merged: BTreeMap<i64, Vec<u64>> = BTreeMap::new();
+    for samples in jobs {
+        for sample in samples {
+            let bins = merged.entry(sample.sec as i64).or_default();
+            if bins.len() < sample.bins.len() {
+                bins.resize(sample.bins.len(), 0);
+            }
+            for (acc, bin) in bins.iter_mut().zip(&sample.bins) {
+                *acc += bin;
+            }
+        }
+    }
+    merged
+        .into_iter()
+        .map(|(sec, bins)| HistSample {
+            sec: sec as f64,
+            bins,
+        })
+        .collect()
+}
+
+fn find_logs(dir: &Path, prefix: &str, kind: &str) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&format!("{prefix}_{kind}.")) && name.ends_with(".log") {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Export all bandwidth logs with the given prefix as tidy tables.
+pub fn export(dir: &Path, prefix: &str, format: crate::export::Format) -> io::Result<()> {
+    for name in find_logs(dir, prefix, "bw")? {
+        let text = readfile(&dir.join(&name))?;
+        let samples = parse_bw_log(&text).map_err(io::Error::other)?;
+        crate::export::fio_bw(&name, &samples).write(dir, format)?;
+    }
+    Ok(())
+}
+
+/// Find all fio logs with the given prefix in `dir` and render bandwidth,
+/// completion latency percentiles and a latency heatmap into `fio.html`.
+pub fn plot(dir: &Path, prefix: &str) -> io::Result<()> {
+    let mut page = Page::new("fio");
+
+    let mut jobs = Vec::new();
+    if let Ok(text) = readfile(&dir.join("fio.json")) {
+        jobs = parse_json(&text).map_err(io::Error::other)?;
+        let (header, rows) = jobs_table(&jobs);
+        page.add_table("Job results", header, rows);
+    }
+    let label = |name: &str| {
+        log_index(name)
+            .and_then(|n| jobs.get(n - 1))
+            .map_or_else(|| name.to_string(), |job| job.jobname.clone())
+    };
+
+    let mut bw_traces = Vec::new();
+    let mut bw_samples = Vec::new();
+    for name in find_logs(dir, prefix, "bw")? {
+        let text = readfile(&dir.join(&name))?;
+        let samples = parse_bw_log(&text).map_err(io::Error::other)?;
+        let mut trace = Scatter::new(&label(&name));
+        for (sec, mibps) in &samples {
+            trace.push(format!("{sec:.3}"), *mibps);
+        }
+        bw_traces.push(trace.to_trace());
+        bw_samples.push(samples);
+    }
+    if bw_samples.len() > 1 {
+        let mut trace = Scatter::new("total");
+        for (sec, mibps) in total_bw(&bw_samples) {
+            trace.push(format!("{sec:.3}"), mibps);
+        }
+        bw_traces.push(trace.to_trace());
+    }
+    if !bw_traces.is_empty() {
+        page.add_plot("Bandwidth, MiB/s", bw_traces);
+    }
+
+    let mut hists = Vec::new();
+    for name in find_logs(dir, prefix, "clat_hist")? {
+        let text = readfile(&dir.join(&name))?;
+        hists.push(parse_hist_log(&text).map_err(io::Error::other)?);
+    }
+    let samples = merge_hists(&hists);
+    if !samples.is_empty() {
+        // Completion latency percentiles over time, all jobs merged.
+        let mut pct_traces = Vec::new();
+        for pct in [50.0, 90.0, 99.0] {
+            let mut trace = Scatter::new(&format!("p{pct}"));
+            for sample in &samples {
+                if let Some(ns) = hist_percentile(&sample.bins, pct) {
+                    trace.push(format!("{:.3}", sample.sec), ns / 1e6);
+                }
+            }
+            pct_traces.push(trace.to_trace());
+        }
+        page.add_plot("Completion latency percentiles, ms", pct_traces);
+
+        // Latency distribution heatmap over time.
+        let x: Vec<String> = samples.iter().map(|s| format!("{:.3}", s.sec)).collect();
+        let observed: Vec<Vec<(f64, f64)>> = samples
+            .iter()
+            .map(|sample| {
+                sample
+                    .bins
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, count)| **count > 0)
+                    .map(|(idx, count)| (plat_idx_to_val(idx), *count as f64))
+                    .collect()
+            })
+            .collect();
+        if let Some(map) = plot::latency_heatmap(x, &observed) {
+            page.add_plot("Latency distribution", vec![map.to_trace()]);
+        }
+    }
+
+    if page.is_empty() {
+        return Ok(());
+    }
+    page.write(&dir.join("fio.html"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bw_log_parses() {
+        let samples = parse_bw_log("1000, 2048, 0, 4096, 0\n2000, 4096, 0, 4096, 0\n").unwrap();
+        assert_eq!(samples, [(1.0, 2.0), (2.0, 4.0)]);
+    }
+
+    #[test]
+    fn hist_log_merges_directions() {
+        let samples = parse_hist_log("1000, 0, 4096, 1, 2, 3\n1000, 1, 4096, 1, 0, 1\n").unwrap();
+        assert_eq!(
+            samples,
+            [HistSample {
+                sec: 1.0,
+                bins: vec![2, 2, 4],
+            }]
+        );
+    }
+
+    #[test]
+    fn hist_percentiles() {
+        // 10 IOs, all but one in bin 1: p50 falls in bin 1, p99 in bin 3.
+        let bins = [0, 9, 0, 1];
+        assert_eq!(hist_percentile(&bins, 50.0), Some(1.0));
+        assert_eq!(hist_percentile(&bins, 99.0), Some(3.0));
+        // The first two groups are exact nanosecond values.
+        assert_eq!(plat_idx_to_val(127), 127.0);
+        assert!(plat_idx_to_val(128) > 127.0);
+    }
+
+    #[test]
+    fn numbered_logs_carry_job_index() {
+        assert_eq!(log_index("fio_bw.1.log"), Some(1));
+        assert_eq!(log_index("fio_clat_hist.12.log"), Some(12));
+        assert_eq!(log_index("fio_bw.log"), None);
+    }
+
+    #[test]
+    fn total_bw_sums_aligned_jobs() {
+        // Two jobs sampling at different sub-second offsets: the samples
+        // of one second are averaged per job, then summed across jobs.
+        let jobs = [vec![(1.1, 2.0), (1.6, 4.0)], vec![(1.4, 1.0)]];
+        assert_eq!(total_bw(&jobs), [(1.0, 4.0)]);
+    }
+
+    #[test]
+    fn json_output_parses() {
+        let text = r#"{
+            "jobs": [{
+                "jobname": "randrw",
+                "read": {
+                    "iops": 1000.5,
+                    "bw_bytes": 4194304,
+                    "clat_ns": { "percentile": { "50.000000": 1000000, "99.000000": 4000000 } }
+                },
+                "write": { "iops": 0, "bw_bytes": 0 }
+            }]
+        }"#;
+        let jobs = parse_json(text).unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].jobname, "randrw");
+        assert_eq!(jobs[0].read.iops, 1000.5);
+        assert_eq!(jobs[0].read.bw_mibps, 4.0);
+        assert_eq!(jobs[0].read.clat_p50_ms, Some(1.0));
+        assert_eq!(jobs[0].read.clat_p99_ms, Some(4.0));
+        assert_eq!(jobs[0].write, JobDirection::default());
+    }
+}
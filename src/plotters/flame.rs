@@ -0,0 +1,94 @@
+//! Flamegraph generation from collapsed stacks via the inferno crate.
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use inferno::collapse::Collapse;
+
+/// Collapsed-stack files (`*.folded`) found in one agent directory.
+pub fn find_folded(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "folded") {
+            found.push(path);
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Collapse `perf script` output into folded stack lines.
+pub fn collapse_perf(script: &[u8], folded: &Path) -> io::Result<()> {
+    let mut folder =
+        inferno::collapse::perf::Folder::from(inferno::collapse::perf::Options::default());
+    folder
+        .collapse(script, File::create(folded)?)
+        .map_err(io::Error::other)
+}
+
+/// Render a flamegraph SVG from a collapsed-stack file.
+pub fn graph(folded: &Path, svg: &Path) -> io::Result<()> {
+    let mut options = inferno::flamegraph::Options::default();
+    options.title = folded.display().to_string();
+    inferno::flamegraph::from_reader(
+        &mut options,
+        BufReader::new(File::open(folded)?),
+        File::create(svg)?,
+    )
+    .map_err(io::Error::other)
+}
+
+/// Turn profiling data found in an agent directory into `perf.folded`
+/// and `perf.svg`.
+///
+/// `perf.script` (text `perf script` output dumped by a launch activity)
+/// is collapsed directly with inferno, so the agent does not need the
+/// flamegraph.pl toolchain; a raw `perf.data` is converted through the
+/// local `perf script` first. Nothing found is not an error.
+pub fn process(dir: &Path) -> io::Result<()> {
+    let folded = dir.join("perf.folded");
+    if !folded.exists() {
+        let script = if dir.join("perf.script").exists() {
+            std::fs::read(dir.join("perf.script"))?
+        } else if dir.join("perf.data").exists() {
+            let output = Command::new("perf")
+                .arg("script")
+                .arg("-i")
+                .arg(dir.join("perf.data"))
+                .output()?;
+            if !output.status.success() {
+                return Err(io::Error::other(format!("perf script failed: {}", output.status)));
+            }
+            output.stdout
+        } else {
+            return Ok(());
+        };
+        collapse_perf(&script, &folded)?;
+    }
+    graph(&folded, &dir.join("perf.svg"))
+}
+
+/// Render a differential flamegraph of two collapsed-stack files into
+/// `svg`: stacks that grew from `before` to `after` show red, shrunk
+/// ones blue.
+pub fn diff(before: &Path, after: &Path, svg: &Path) -> io::Result<()> {
+    let mut folded = Vec::new();
+    inferno::differential::from_readers(
+        inferno::differential::Options::default(),
+        BufReader::new(File::open(before)?),
+        BufReader::new(File::open(after)?),
+        &mut folded,
+    )?;
+
+    let mut options = inferno::flamegraph::Options::default();
+    options.title = format!(
+        "diff: {} vs {}",
+        before.display(),
+        after.display()
+    );
+    inferno::flamegraph::from_reader(&mut options, folded.as_slice(), File::create(svg)?)
+        .map_err(io::Error::other)
+}
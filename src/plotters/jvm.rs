@@ -0,0 +1,129 @@
+//! JVM garbage collection statistics via `jstat -gcutil` sampling.
+//!
+//! The agent runs one single-shot `jstat` per sampling period inside the
+//! usual shell loop, so every poll sample carries the header line and one
+//! value line and the parser maps columns by name.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::common::millis_to_naive;
+use crate::plot::{plotly_time, Page, Scatter};
+use crate::plotters::procfs::PollSamples;
+
+/// Parsed jstat sampling: `columns[<name>]` as printed by `-gcutil`
+/// (occupancy percentages and cumulative GC counters/times).
+#[derive(Debug, Default)]
+pub struct Jvm {
+    pub times: Vec<NaiveDateTime>,
+    pub columns: BTreeMap<String, Vec<f64>>,
+}
+
+/// Parse a jstat sampling log.
+pub fn parse(text: &str) -> Result<Jvm, String> {
+    parse_reader(text.as_bytes())
+}
+
+/// Parse a jstat sampling log incrementally from a reader.
+pub fn parse_reader<R: BufRead>(reader: R) -> Result<Jvm, String> {
+    let mut stat = Jvm::default();
+    for sample in PollSamples::new(reader) {
+        let sample = sample?;
+        stat.times.push(millis_to_naive(sample.millis));
+        for (_section, content) in &sample.files {
+            let mut lines = content.lines();
+            let Some(header) = lines.next() else { continue };
+            let Some(values) = lines.next() else { continue };
+            for (name, value) in header.split_whitespace().zip(values.split_whitespace()) {
+                let Ok(value) = value.parse() else {
+                    continue; // CMS prints '-' for unused generations
+                };
+                stat.columns
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(value);
+            }
+        }
+    }
+    Ok(stat)
+}
+
+/// Render heap occupancy and GC activity into `jvm.html`.
+pub fn plot(stat: &Jvm, outdir: &Path, marks: &[(String, NaiveDateTime)]) -> std::io::Result<()> {
+    let gauges = |names: &[&str]| -> Vec<serde_json::Value> {
+        names
+            .iter()
+            .filter_map(|n| {
+                let values = stat.columns.get(*n)?;
+                let mut trace = Scatter::new(n);
+                for (time, value) in stat.times.iter().zip(values) {
+                    trace.push(plotly_time(time), *value);
+                }
+                Some(trace.to_trace())
+            })
+            .collect()
+    };
+    let rates = |names: &[&str]| -> Vec<serde_json::Value> {
+        names
+            .iter()
+            .filter_map(|n| {
+                let counter = stat.columns.get(*n)?;
+                Some(rate_trace(&stat.times, n, counter))
+            })
+            .collect()
+    };
+
+    let mut page = Page::new("jvm");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    page.add_plot(
+        "Heap occupancy, %",
+        gauges(&["S0", "S1", "E", "O", "M", "CCS"]),
+    );
+    page.add_plot("GC collections/s", rates(&["YGC", "FGC"]));
+    // Cumulative GC seconds turned into a rate: the share of wall time
+    // the JVM spent collecting.
+    page.add_plot("GC time share, s/s", rates(&["YGCT", "FGCT", "GCT"]));
+    page.write(&outdir.join("jvm.html"))
+}
+
+/// Turn a cumulative counter into a per-second rate trace.
+fn rate_trace(times: &[NaiveDateTime], name: &str, counter: &[f64]) -> serde_json::Value {
+    let mut trace = Scatter::new(name);
+    for i in 1..counter.len().min(times.len()) {
+        let dt = (times[i] - times[i - 1]).num_milliseconds() as f64 / 1000.0;
+        if dt <= 0.0 {
+            continue;
+        }
+        trace.push(plotly_time(&times[i]), (counter[i] - counter[i - 1]) / dt);
+    }
+    trace.to_trace()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+=== 1724690000000
+--- jstat
+  S0     S1     E      O      M     CCS    YGC     YGCT    FGC    FGCT     GCT
+  0.00  25.00  50.00  10.00  95.00  90.00      5    0.050     1    0.100    0.150
+=== 1724690001000
+--- jstat
+  S0     S1     E      O      M     CCS    YGC     YGCT    FGC    FGCT     GCT
+ 25.00   0.00  10.00  12.00  95.00  90.00      6    0.070     1    0.100    0.170
+";
+
+    #[test]
+    fn gcutil_columns_parse() {
+        let stat = parse(SAMPLE).unwrap();
+        assert_eq!(stat.times.len(), 2);
+        assert_eq!(stat.columns["E"], [50.0, 10.0]);
+        assert_eq!(stat.columns["YGC"], [5.0, 6.0]);
+        assert_eq!(stat.columns["GCT"], [0.15, 0.17]);
+    }
+}
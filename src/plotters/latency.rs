@@ -0,0 +1,170 @@
+//! Scheduling-latency benchmarks: cyclictest histograms and schbench
+//! percentile tables.
+//!
+//! Both tools run in the background for a configured duration, so their
+//! reports land in the activity output logs and are parsed after
+//! collection like any other monitor.
+
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::plot::{Page, Scatter};
+
+/// Parsed cyclictest histogram: per-thread sample counts per microsecond
+/// bucket, plus the per-thread summary latencies.
+#[derive(Debug, Default)]
+pub struct Cyclictest {
+    /// `(bucket_us, per-thread counts)` rows in bucket order.
+    pub buckets: Vec<(u64, Vec<f64>)>,
+    pub min: Vec<f64>,
+    pub avg: Vec<f64>,
+    pub max: Vec<f64>,
+}
+
+/// Parse `cyclictest -q -h` output.
+pub fn parse_cyclictest(text: &str) -> Result<Cyclictest, String> {
+    let mut stat = Cyclictest::default();
+    for line in text.lines() {
+        if let Some(values) = line.strip_prefix("# Min Latencies:") {
+            stat.min = numbers(values);
+        } else if let Some(values) = line.strip_prefix("# Avg Latencies:") {
+            stat.avg = numbers(values);
+        } else if let Some(values) = line.strip_prefix("# Max Latencies:") {
+            stat.max = numbers(values);
+        } else if !line.starts_with('#') {
+            let mut fields = line.split_whitespace();
+            let Some(bucket) = fields.next().and_then(|f| f.parse().ok()) else {
+                continue;
+            };
+            let counts: Vec<f64> = fields.filter_map(|f| f.parse().ok()).collect();
+            if !counts.is_empty() {
+                stat.buckets.push((bucket, counts));
+            }
+        }
+    }
+    Ok(stat)
+}
+
+fn numbers(text: &str) -> Vec<f64> {
+    text.split_whitespace().filter_map(|f| f.parse().ok()).collect()
+}
+
+/// Render the cyclictest histogram into `cyclictest.html`.
+pub fn plot_cyclictest(
+    stat: &Cyclictest,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+) -> std::io::Result<()> {
+    let threads = stat.buckets.iter().map(|(_, c)| c.len()).max().unwrap_or(0);
+    let mut traces = Vec::new();
+    for thread in 0..threads {
+        let mut trace = Scatter::new(&format!("thread {thread}"));
+        for (bucket, counts) in &stat.buckets {
+            if let Some(count) = counts.get(thread) {
+                trace.push(bucket.to_string(), *count);
+            }
+        }
+        traces.push(trace.to_trace());
+    }
+
+    let mut page = Page::new("cyclictest");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    page.add_plot("Latency histogram, samples per µs", traces);
+
+    let summary = |name: &str, values: &[f64]| {
+        let mut trace = Scatter::new(name);
+        for (thread, value) in values.iter().enumerate() {
+            trace.push(thread.to_string(), *value);
+        }
+        trace.to_trace()
+    };
+    page.add_plot(
+        "Per-thread latency summary, µs",
+        vec![
+            summary("min", &stat.min),
+            summary("avg", &stat.avg),
+            summary("max", &stat.max),
+        ],
+    );
+    page.write(&outdir.join("cyclictest.html"))
+}
+
+/// Parsed schbench report: `(percentile, latency_us)` in table order.
+#[derive(Debug, Default)]
+pub struct Schbench {
+    pub percentiles: Vec<(f64, f64)>,
+}
+
+/// Parse the schbench percentile table (printed to stderr), e.g.
+/// `        *99.0th: 122`.
+pub fn parse_schbench(text: &str) -> Result<Schbench, String> {
+    let mut stat = Schbench::default();
+    for line in text.lines() {
+        let Some((pct, value)) = line.trim().trim_start_matches('*').split_once("th:") else {
+            continue;
+        };
+        let (Ok(pct), Ok(value)) = (pct.trim().parse(), value.trim().parse()) else {
+            continue;
+        };
+        stat.percentiles.push((pct, value));
+    }
+    Ok(stat)
+}
+
+/// Render the schbench percentile curve into `schbench.html`.
+pub fn plot_schbench(
+    stat: &Schbench,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+) -> std::io::Result<()> {
+    let mut trace = Scatter::new("latency");
+    for (pct, value) in &stat.percentiles {
+        trace.push(pct.to_string(), *value);
+    }
+
+    let mut page = Page::new("schbench");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    page.add_plot("Latency percentiles, µs", vec![trace.to_trace()]);
+    page.write(&outdir.join("schbench.html"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CYCLICTEST: &str = "\
+# /dev/cpu_dma_latency set to 0us
+000001 000005 000003
+000002 000100 000090
+000003 000002 000008
+# Total: 000000107 000000101
+# Min Latencies: 00001 00001
+# Avg Latencies: 00002 00002
+# Max Latencies: 00003 00003
+";
+
+    #[test]
+    fn cyclictest_histogram_parses() {
+        let stat = parse_cyclictest(CYCLICTEST).unwrap();
+        assert_eq!(stat.buckets.len(), 3);
+        assert_eq!(stat.buckets[1], (2, vec![100.0, 90.0]));
+        assert_eq!(stat.max, [3.0, 3.0]);
+    }
+
+    const SCHBENCH: &str = "\
+Latency percentiles (usec)
+        50.0th: 45
+        75.0th: 62
+        *99.0th: 122
+        min=0, max=1242
+";
+
+    #[test]
+    fn schbench_percentiles_parse() {
+        let stat = parse_schbench(SCHBENCH).unwrap();
+        assert_eq!(stat.percentiles, [(50.0, 45.0), (75.0, 62.0), (99.0, 122.0)]);
+    }
+}
@@ -0,0 +1,198 @@
+//! Parsers and plotters for the collected raw data.
+//!
+//! With only the `parsers` cargo feature enabled, this module exposes
+//! the sysstat and procfs parsers ([`sysstat`], [`procfs`] and the
+//! [`Chunks`] splitter) without any of the plotting machinery. That
+//! subset is the crate's public parsing API: the types and functions it
+//! exports follow semver, so other tools can depend on them. Everything
+//! behind the `plotter` feature remains internal to the report pipeline
+//! and may change between minor versions.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::common::millis_to_naive;
+
+#[cfg(feature = "plotter")]
+pub mod compare;
+#[cfg(feature = "plotter")]
+pub mod correlate;
+#[cfg(feature = "plotter")]
+pub mod cachestat;
+#[cfg(feature = "plotter")]
+pub mod conntrack;
+#[cfg(feature = "plotter")]
+pub mod dbstat;
+#[cfg(feature = "plotter")]
+pub mod ethtool;
+#[cfg(feature = "plotter")]
+pub mod filter;
+#[cfg(feature = "plotter")]
+pub mod fio;
+#[cfg(feature = "plotter")]
+pub mod flame;
+#[cfg(feature = "plotter")]
+pub mod jvm;
+#[cfg(feature = "plotter")]
+pub mod latency;
+#[cfg(feature = "plotter")]
+pub mod nfs;
+pub mod procfs;
+#[cfg(feature = "plotter")]
+pub mod quality;
+#[cfg(feature = "plotter")]
+pub mod registry;
+#[cfg(feature = "plotter")]
+pub mod report;
+#[cfg(feature = "plotter")]
+pub mod sar;
+#[cfg(feature = "plotter")]
+pub mod smart;
+#[cfg(feature = "plotter")]
+pub mod summary;
+pub mod sysstat;
+#[cfg(feature = "plotter")]
+pub mod timeline;
+#[cfg(feature = "plotter")]
+pub mod trend;
+#[cfg(feature = "plotter")]
+pub mod vmstat;
+
+/// Abort parsing on the first malformed chunk instead of skipping it.
+static STRICT: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Make the parsers fail on malformed chunks instead of skipping and
+/// counting them. Truncated final chunks from killed monitors are
+/// expected, so tolerant is the default.
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, std::sync::atomic::Ordering::Relaxed);
+}
+
+pub(crate) fn strict() -> bool {
+    STRICT.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Iterator over blank-line separated chunks of sysstat-style output.
+///
+/// Reads incrementally from the underlying source, so multi-hundred-MB
+/// captures never have to be fully resident: only one chunk is held at a
+/// time. Consecutive blank lines are collapsed.
+pub struct Chunks<R> {
+    reader: R,
+    done: bool,
+}
+
+impl<R: BufRead> Chunks<R> {
+    pub fn new(reader: R) -> Self {
+        Chunks {
+            reader,
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Chunks<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut chunk = String::new();
+        loop {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Err(e) => return Some(Err(e)),
+                Ok(0) => {
+                    self.done = true;
+                    break;
+                }
+                Ok(_) if line.trim().is_empty() => {
+                    if !chunk.is_empty() {
+                        break;
+                    }
+                }
+                Ok(_) => chunk.push_str(&line),
+            }
+        }
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(Ok(chunk))
+        }
+    }
+}
+
+/// Benchmark intervals from the agent journal: activity label with start
+/// and stop times.
+///
+/// Only command activities (fio, launch) are returned — the monitors run
+/// wall-to-wall and shading them would cover the whole chart. A missing
+/// journal yields no intervals rather than an error.
+pub fn read_journal(dir: &Path) -> Vec<(String, NaiveDateTime, NaiveDateTime)> {
+    let Ok(text) = crate::common::readfile(&dir.join("journal.log")) else {
+        return Vec::new();
+    };
+    let mut started: HashMap<&str, (&str, u64)> = HashMap::new();
+    let mut spans = Vec::new();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some(Ok(millis)) = fields.first().map(|f| f.parse::<u64>()) else {
+            continue;
+        };
+        match fields[1..] {
+            ["start", id, name] => {
+                started.insert(id, (name, millis));
+            }
+            ["stop", id, _status] => {
+                if let Some((name, from)) = started.remove(id) {
+                    if matches!(name, "fio" | "launch") {
+                        spans.push((
+                            format!("{id} {name}"),
+                            millis_to_naive(from),
+                            millis_to_naive(millis),
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    spans
+}
+
+/// Load the measurement window markers recorded by the controller,
+/// looking for `marks.json` next to the agent directory or inside it.
+pub fn read_marks(dir: &Path) -> Vec<(String, NaiveDateTime)> {
+    let candidates = [
+        dir.join("marks.json"),
+        dir.parent().map(|p| p.join("marks.json")).unwrap_or_default(),
+    ];
+    for path in candidates {
+        let Ok(text) = crate::common::readfile(&path) else { continue };
+        let Ok(raw) = serde_json::from_str::<std::collections::BTreeMap<String, u64>>(&text) else {
+            continue;
+        };
+        return raw
+            .into_iter()
+            .map(|(name, millis)| (name, millis_to_naive(millis)))
+            .collect();
+    }
+    Vec::new()
+}
+
+/// Read the activity id to name mapping from `out.map` in an agent
+/// output directory.
+pub fn read_mapping(dir: &Path) -> io::Result<Vec<(String, String)>> {
+    let text = crate::common::readfile(&dir.join("out.map"))?;
+    let mut mapping = Vec::new();
+    for line in text.lines() {
+        if let Some((id, name)) = line.split_once(' ') {
+            mapping.push((id.to_string(), name.to_string()));
+        }
+    }
+    Ok(mapping)
+}
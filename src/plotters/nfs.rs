@@ -0,0 +1,139 @@
+//! NFS client/server RPC statistics from `/proc/net/rpc/nfs{,d}`.
+//!
+//! The activity polls both files (plus the raw per-mount `mountstats`,
+//! which stays in the log for manual inspection) with the ordinary file
+//! poller; this module reads the cumulative RPC counters out of the
+//! poll log.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::common::millis_to_naive;
+use crate::plot::{plotly_time, Page, Scatter};
+use crate::plotters::procfs::PollSamples;
+
+/// Parsed NFS sampling: cumulative counters named `client ...` (from
+/// `nfs`) and `server ...` (from `nfsd`).
+#[derive(Debug, Default)]
+pub struct Nfs {
+    pub times: Vec<NaiveDateTime>,
+    pub counters: BTreeMap<String, Vec<f64>>,
+}
+
+/// Parse an NFS sampling log.
+pub fn parse(text: &str) -> Result<Nfs, String> {
+    parse_reader(text.as_bytes())
+}
+
+/// Parse an NFS sampling log incrementally from a reader.
+pub fn parse_reader<R: BufRead>(reader: R) -> Result<Nfs, String> {
+    let mut stat = Nfs::default();
+    for sample in PollSamples::new(reader) {
+        let sample = sample?;
+        stat.times.push(millis_to_naive(sample.millis));
+        for (section, content) in &sample.files {
+            let side = match section.as_str() {
+                "/proc/net/rpc/nfs" => "client",
+                "/proc/net/rpc/nfsd" => "server",
+                _ => continue, // mountstats stays unparsed
+            };
+            for line in content.lines() {
+                let mut fields = line.split_whitespace();
+                let Some(tag) = fields.next() else { continue };
+                let values: Vec<f64> = fields.filter_map(|f| f.parse().ok()).collect();
+                let mut push = |name: &str, value: f64| {
+                    stat.counters
+                        .entry(format!("{side} {name}"))
+                        .or_default()
+                        .push(value);
+                };
+                match (tag, values.as_slice()) {
+                    ("rpc", [calls, retrans, ..]) => {
+                        push("rpc calls", *calls);
+                        push("rpc retrans", *retrans);
+                    }
+                    ("rc", [hits, misses, ..]) => {
+                        push("reply cache hits", *hits);
+                        push("reply cache misses", *misses);
+                    }
+                    ("io", [read, write, ..]) => {
+                        push("read bytes", *read);
+                        push("write bytes", *write);
+                    }
+                    // proc3/proc4/proc4ops rows: a count of fields
+                    // followed by per-operation counters.
+                    (tag, [_nops, ops @ ..]) if tag.starts_with("proc") => {
+                        push(&format!("{tag} total"), ops.iter().sum());
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(stat)
+}
+
+/// Render RPC and NFSD activity into `nfs.html`.
+pub fn plot(stat: &Nfs, outdir: &Path, marks: &[(String, NaiveDateTime)]) -> std::io::Result<()> {
+    let rates = |suffix: &str, scale: f64| -> Vec<serde_json::Value> {
+        stat.counters
+            .iter()
+            .filter(|(name, _)| name.ends_with(suffix))
+            .map(|(name, counter)| rate_trace(&stat.times, name, counter, scale))
+            .collect()
+    };
+
+    let mut page = Page::new("nfs");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    page.add_plot("RPC calls/s", rates("rpc calls", 1.0));
+    page.add_plot("RPC retransmissions/s", rates("rpc retrans", 1.0));
+    page.add_plot("Operations/s", rates("total", 1.0));
+    page.add_plot("Server I/O, MB/s", rates("bytes", 1e-6));
+    page.add_plot("Server reply cache/s", rates("hits", 1.0));
+    page.write(&outdir.join("nfs.html"))
+}
+
+/// Turn a monotonic counter into a per-second rate trace.
+fn rate_trace(times: &[NaiveDateTime], name: &str, counter: &[f64], scale: f64) -> serde_json::Value {
+    let mut trace = Scatter::new(name);
+    for i in 1..counter.len().min(times.len()) {
+        let dt = (times[i] - times[i - 1]).num_milliseconds() as f64 / 1000.0;
+        if dt <= 0.0 {
+            continue;
+        }
+        trace.push(plotly_time(&times[i]), (counter[i] - counter[i - 1]) / dt * scale);
+    }
+    trace.to_trace()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+=== 1724690000000
+--- /proc/net/rpc/nfs
+net 0 0 0 0
+rpc 1000 5 0
+proc3 22 0 10 20 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+--- /proc/net/rpc/nfsd
+rc 7 3 0
+io 4096 8192
+rpc 2000 0 0 0 0
+";
+
+    #[test]
+    fn rpc_counters_parse() {
+        let stat = parse(SAMPLE).unwrap();
+        assert_eq!(stat.times.len(), 1);
+        assert_eq!(stat.counters["client rpc calls"], [1000.0]);
+        assert_eq!(stat.counters["client rpc retrans"], [5.0]);
+        assert_eq!(stat.counters["client proc3 total"], [30.0]);
+        assert_eq!(stat.counters["server reply cache hits"], [7.0]);
+        assert_eq!(stat.counters["server write bytes"], [8192.0]);
+    }
+}
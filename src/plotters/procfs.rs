@@ -0,0 +1,716 @@
+//! Parsers and plotters for polled `/proc` files.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+#[cfg(feature = "plotter")]
+use std::path::Path;
+#[cfg(feature = "plotter")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "plotter")]
+use std::sync::Mutex;
+
+use chrono::NaiveDateTime;
+
+use crate::common::millis_to_naive;
+#[cfg(feature = "plotter")]
+use crate::plot::{self, Page, Scatter};
+
+/// One sample of a poll log: timestamp plus the polled file contents.
+#[derive(Debug, Default)]
+pub struct PollSample {
+    pub millis: u64,
+    pub files: Vec<(String, String)>,
+}
+
+/// Iterator over the samples of an agent poll log.
+///
+/// A text log consists of `=== <millis>` sample markers each followed by
+/// one `--- <path>` section per polled file; the compact framed format
+/// of the [`polllog`](crate::polllog) module is recognized by its magic
+/// and read transparently. Samples are produced one at a time from the
+/// underlying reader, so memory stays bounded by one poll period even
+/// for multi-hundred-MB logs.
+pub struct PollSamples<R> {
+    reader: R,
+    current: Option<PollSample>,
+    done: bool,
+    /// Sniffed from the first bytes on the first sample.
+    binary: Option<bool>,
+}
+
+impl<R: BufRead> PollSamples<R> {
+    pub fn new(reader: R) -> Self {
+        PollSamples {
+            reader,
+            current: None,
+            done: false,
+            binary: None,
+        }
+    }
+
+    /// Check the log for the binary magic, consuming it when present.
+    fn sniff(&mut self) -> Result<bool, String> {
+        let head = self.reader.fill_buf().map_err(|e| e.to_string())?;
+        let binary = head.starts_with(crate::polllog::MAGIC);
+        if binary {
+            self.reader.consume(crate::polllog::MAGIC.len());
+        }
+        Ok(binary)
+    }
+}
+
+impl<R: BufRead> Iterator for PollSamples<R> {
+    type Item = Result<PollSample, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let binary = match self.binary {
+            Some(binary) => binary,
+            None => match self.sniff() {
+                Ok(binary) => *self.binary.insert(binary),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            },
+        };
+        if binary {
+            return match crate::polllog::read_sample(&mut self.reader) {
+                Ok(Some((millis, files))) => Some(Ok(PollSample { millis, files })),
+                Ok(None) => {
+                    self.done = true;
+                    None
+                }
+                Err(e) => {
+                    self.done = true;
+                    Some(Err(e))
+                }
+            };
+        }
+        loop {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Err(e) => return Some(Err(e.to_string())),
+                Ok(0) => {
+                    self.done = true;
+                    return self.current.take().map(Ok);
+                }
+                Ok(_) => {}
+            }
+            let line = line.trim_end_matches('\n');
+
+            if let Some(millis) = line.strip_prefix("=== ") {
+                let millis = match millis.trim().parse() {
+                    Ok(millis) => millis,
+                    Err(e) => return Some(Err(format!("bad poll timestamp '{millis}': {e}"))),
+                };
+                let next = PollSample {
+                    millis,
+                    files: Vec::new(),
+                };
+                if let Some(done) = self.current.replace(next) {
+                    return Some(Ok(done));
+                }
+            } else if let Some(path) = line.strip_prefix("--- ") {
+                let Some(sample) = self.current.as_mut() else {
+                    return Some(Err("poll section before timestamp".to_string()));
+                };
+                sample.files.push((path.trim().to_string(), String::new()));
+            } else {
+                let Some(sample) = self.current.as_mut() else {
+                    return Some(Err("poll data before timestamp".to_string()));
+                };
+                let Some((_, content)) = sample.files.last_mut() else {
+                    return Some(Err("poll data before section".to_string()));
+                };
+                content.push_str(line);
+                content.push('\n');
+            }
+        }
+    }
+}
+
+/// Split an agent poll log into samples.
+pub fn parse_poll_log(text: &str) -> Result<Vec<PollSample>, String> {
+    PollSamples::new(text.as_bytes()).collect()
+}
+
+/// Parsed `/proc/meminfo` poll: `fields[name]` in kB.
+#[derive(Debug, Default)]
+pub struct Meminfo {
+    pub times: Vec<NaiveDateTime>,
+    pub fields: BTreeMap<String, Vec<f64>>,
+    /// Malformed poll samples skipped in tolerant mode.
+    pub dropped_chunks: usize,
+}
+
+/// Parse a poll log of `/proc/meminfo`.
+pub fn parse_meminfo(text: &str) -> Result<Meminfo, String> {
+    parse_meminfo_reader(text.as_bytes())
+}
+
+/// Parse a `/proc/meminfo` poll log incrementally from a reader.
+pub fn parse_meminfo_reader<R: BufRead>(reader: R) -> Result<Meminfo, String> {
+    let mut stat = Meminfo::default();
+    for sample in PollSamples::new(reader) {
+        // A malformed sample (typically the last one, truncated when the
+        // poller was stopped) is skipped and counted unless in strict
+        // mode; the capture is only committed to per whole sample.
+        match sample.and_then(|sample| parse_meminfo_sample(&sample)) {
+            Ok((time, fields)) => {
+                stat.times.push(time);
+                for (name, value) in fields {
+                    stat.fields.entry(name).or_default().push(value);
+                }
+            }
+            Err(error) if crate::plotters::strict() => return Err(error),
+            Err(_) => stat.dropped_chunks += 1,
+        }
+    }
+    Ok(stat)
+}
+
+/// Validate one meminfo poll sample into its timestamp and fields.
+fn parse_meminfo_sample(
+    sample: &PollSample,
+) -> Result<(NaiveDateTime, Vec<(String, f64)>), String> {
+    let (_, content) = sample
+        .files
+        .iter()
+        .find(|(path, _)| path.ends_with("meminfo"))
+        .ok_or("no meminfo section in poll sample")?;
+    let mut fields = Vec::new();
+    for line in content.lines() {
+        let mut tokens = line.split_whitespace();
+        let name = tokens.next().ok_or("empty meminfo line")?.trim_end_matches(':');
+        let value: f64 = tokens
+            .next()
+            .ok_or_else(|| format!("no value in meminfo line: {line}"))?
+            .parse()
+            .map_err(|e| format!("bad meminfo value in '{line}': {e}"))?;
+        fields.push((name.to_string(), value));
+    }
+    Ok((millis_to_naive(sample.millis), fields))
+}
+
+/// Fields plotted even when they never change; the auto-filter otherwise
+/// drops MemTotal and friends.
+#[cfg(feature = "plotter")]
+static KEEP_FIELDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Plot deltas from the first sample in MiB instead of absolute GiB;
+/// slow leaks are invisible on an absolute GiB axis.
+#[cfg(feature = "plotter")]
+static MEMINFO_DELTA: AtomicBool = AtomicBool::new(false);
+
+/// Always plot the given meminfo fields, changing or not.
+#[cfg(feature = "plotter")]
+pub fn set_meminfo_fields(fields: Vec<String>) {
+    *KEEP_FIELDS.lock().unwrap() = fields;
+}
+
+/// Switch the meminfo plot to delta-from-start mode.
+#[cfg(feature = "plotter")]
+pub fn set_meminfo_delta(delta: bool) {
+    MEMINFO_DELTA.store(delta, Ordering::Relaxed);
+}
+
+/// Render the changing meminfo fields into `meminfo.html`.
+#[cfg(feature = "plotter")]
+pub fn plot_meminfo(
+    stat: &Meminfo,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+) -> std::io::Result<()> {
+    let x: Vec<String> = stat.times.iter().map(plot::plotly_time).collect();
+    let keep = KEEP_FIELDS.lock().unwrap().clone();
+    let delta = MEMINFO_DELTA.load(Ordering::Relaxed);
+
+    let mut traces = Vec::new();
+    for (name, values) in &stat.fields {
+        // Nonchanging fields (MemTotal and friends) only clutter the plot.
+        let changing = values.windows(2).any(|w| w[0] != w[1]);
+        if !changing && !keep.iter().any(|f| f == name) {
+            continue;
+        }
+        let base = if delta { values.first().copied().unwrap_or(0.0) } else { 0.0 };
+        let scale = if delta { 1024.0 } else { 1024.0 * 1024.0 };
+        let mut trace = Scatter::new(name);
+        for (time, value) in x.iter().zip(values) {
+            trace.push(time.clone(), (value - base) / scale);
+        }
+        traces.push(trace.to_trace());
+    }
+
+    let mut page = Page::new("meminfo");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    let title = if delta { "Memory delta, MiB" } else { "Memory, GiB" };
+    page.add_plot(title, traces);
+    page.write(&outdir.join("meminfo.html"))
+}
+
+/// Parsed `/proc/interrupts` poll: cumulative `counts[irq][cpu][sample]`.
+#[derive(Debug, Default)]
+pub struct Interrupts {
+    pub times: Vec<NaiveDateTime>,
+    pub cpus: Vec<String>,
+    /// Keyed by IRQ number/label plus its description, e.g.
+    /// `25 IR-PCI-MSI nvme0q1`.
+    pub irqs: BTreeMap<String, Vec<Vec<f64>>>,
+}
+
+/// Parse a poll log of `/proc/interrupts`.
+pub fn parse_interrupts(text: &str) -> Result<Interrupts, String> {
+    parse_interrupts_reader(text.as_bytes())
+}
+
+/// Parse a `/proc/interrupts` poll log incrementally from a reader.
+pub fn parse_interrupts_reader<R: BufRead>(reader: R) -> Result<Interrupts, String> {
+    let mut stat = Interrupts::default();
+    for sample in PollSamples::new(reader) {
+        let sample = sample?;
+        let (_, content) = sample
+            .files
+            .iter()
+            .find(|(path, _)| path.ends_with("interrupts"))
+            .ok_or("no interrupts section in poll sample")?;
+        stat.times.push(millis_to_naive(sample.millis));
+
+        let mut lines = content.lines();
+        let header = lines.next().ok_or("empty interrupts sample")?;
+        if stat.cpus.is_empty() {
+            stat.cpus = header.split_whitespace().map(str::to_string).collect();
+        }
+        for line in lines {
+            let Some((label, rest)) = line.split_once(':') else {
+                continue;
+            };
+            let tokens: Vec<&str> = rest.split_whitespace().collect();
+            let counts: Vec<f64> = tokens
+                .iter()
+                .take(stat.cpus.len())
+                .map_while(|t| t.parse().ok())
+                .collect();
+            // Rows like ERR/MIS carry a single total; skip them rather
+            // than guess a per-CPU split.
+            if counts.len() != stat.cpus.len() {
+                continue;
+            }
+            let description = tokens[counts.len()..].join(" ");
+            let key = format!("{} {description}", label.trim());
+            let per_cpu = stat
+                .irqs
+                .entry(key.trim().to_string())
+                .or_insert_with(|| vec![Vec::new(); counts.len()]);
+            for (cpu, count) in per_cpu.iter_mut().zip(counts) {
+                cpu.push(count);
+            }
+        }
+    }
+    Ok(stat)
+}
+
+/// Render the per-CPU interrupt rate heatmap and the top-N IRQ rate
+/// chart into `interrupts.html`.
+#[cfg(feature = "plotter")]
+pub fn plot_interrupts(
+    stat: &Interrupts,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+) -> std::io::Result<()> {
+    let samples = stat.times.len();
+    if samples < 2 {
+        return Ok(());
+    }
+
+    // Per-CPU total interrupt rate across all IRQ lines.
+    let mut z = vec![vec![0.0; samples - 1]; stat.cpus.len()];
+    for per_cpu in stat.irqs.values() {
+        for (cpu, counts) in per_cpu.iter().enumerate() {
+            for i in 1..counts.len().min(samples) {
+                let dt = (stat.times[i] - stat.times[i - 1]).num_milliseconds() as f64 / 1000.0;
+                if dt > 0.0 {
+                    z[cpu][i - 1] += (counts[i] - counts[i - 1]) / dt;
+                }
+            }
+        }
+    }
+    let x: Vec<String> = stat.times[1..].iter().map(plot::plotly_time).collect();
+
+    // The busiest IRQ lines by total delta over the run.
+    const TOP_IRQS: usize = 10;
+    let mut totals: Vec<(&String, f64)> = stat
+        .irqs
+        .iter()
+        .map(|(name, per_cpu)| {
+            let total = per_cpu
+                .iter()
+                .map(|c| c.last().unwrap_or(&0.0) - c.first().unwrap_or(&0.0))
+                .sum();
+            (name, total)
+        })
+        .collect();
+    totals.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut top = Vec::new();
+    for (name, _) in totals.into_iter().take(TOP_IRQS) {
+        let per_cpu = &stat.irqs[name];
+        let summed: Vec<f64> = (0..samples)
+            .map(|i| per_cpu.iter().filter_map(|c| c.get(i)).sum())
+            .collect();
+        top.push(rate_trace(&stat.times, name, &summed, 1.0));
+    }
+
+    let mut page = Page::new("interrupts");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    let map = plot::HeatMap::new(x, stat.cpus.clone(), z);
+    page.add_plot("Interrupt rate per CPU, irq/s", vec![map.to_trace()]);
+    page.add_plot("Busiest IRQ lines, irq/s", top);
+    page.write(&outdir.join("interrupts.html"))
+}
+
+/// One PSI series: `cpu some`, `io full` and so on.
+#[derive(Debug, Default)]
+pub struct PsiStats {
+    /// `avg10` percentages.
+    pub avg10: Vec<f64>,
+    /// Cumulative stall time in microseconds (`total`).
+    pub total_us: Vec<f64>,
+}
+
+/// Parsed `/proc/pressure/{cpu,io,memory}` poll.
+#[derive(Debug, Default)]
+pub struct Psi {
+    pub times: Vec<NaiveDateTime>,
+    /// Keyed by `<resource> <kind>`, e.g. `memory full`.
+    pub series: BTreeMap<String, PsiStats>,
+}
+
+/// Parse a poll log of the `/proc/pressure` files.
+pub fn parse_psi(text: &str) -> Result<Psi, String> {
+    parse_psi_reader(text.as_bytes())
+}
+
+/// Parse a `/proc/pressure` poll log incrementally from a reader.
+pub fn parse_psi_reader<R: BufRead>(reader: R) -> Result<Psi, String> {
+    let mut stat = Psi::default();
+    for sample in PollSamples::new(reader) {
+        let sample = sample?;
+        stat.times.push(millis_to_naive(sample.millis));
+        for (path, content) in &sample.files {
+            let Some(resource) = path.rsplit('/').next() else {
+                continue;
+            };
+            // `some avg10=0.00 avg60=0.00 avg300=0.00 total=12345`; the
+            // `full` line is absent for cpu on older kernels.
+            for line in content.lines() {
+                let mut tokens = line.split_whitespace();
+                let kind = tokens.next().ok_or("empty pressure line")?;
+                let mut avg10 = None;
+                let mut total = None;
+                for token in tokens {
+                    if let Some(value) = token.strip_prefix("avg10=") {
+                        avg10 = value.parse().ok();
+                    } else if let Some(value) = token.strip_prefix("total=") {
+                        total = value.parse().ok();
+                    }
+                }
+                let (Some(avg10), Some(total)) = (avg10, total) else {
+                    return Err(format!("malformed pressure line: {line}"));
+                };
+                let series = stat.series.entry(format!("{resource} {kind}")).or_default();
+                series.avg10.push(avg10);
+                series.total_us.push(total);
+            }
+        }
+    }
+    Ok(stat)
+}
+
+/// Render the PSI averages and stall time rates into `pressure.html`.
+#[cfg(feature = "plotter")]
+pub fn plot_psi(
+    stat: &Psi,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+) -> std::io::Result<()> {
+    let x: Vec<String> = stat.times.iter().map(plot::plotly_time).collect();
+
+    let mut averages = Vec::new();
+    let mut stalls = Vec::new();
+    for (name, series) in &stat.series {
+        let mut trace = Scatter::new(name);
+        for (time, value) in x.iter().zip(&series.avg10) {
+            trace.push(time.clone(), *value);
+        }
+        averages.push(trace.to_trace());
+        // total is cumulative stall microseconds: the delta rate gives
+        // stalled milliseconds per second of wall time.
+        stalls.push(rate_trace(&stat.times, name, &series.total_us, 1e-3));
+    }
+
+    let mut page = Page::new("pressure");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    page.add_plot("Pressure avg10, %", averages);
+    page.add_plot("Stall time, ms/s", stalls);
+    page.write(&outdir.join("pressure.html"))
+}
+
+/// Per-interface counters from `/proc/net/dev`.
+#[derive(Debug, Default)]
+pub struct IfaceStats {
+    pub rx_bytes: Vec<f64>,
+    pub tx_bytes: Vec<f64>,
+    pub rx_packets: Vec<f64>,
+    pub tx_packets: Vec<f64>,
+}
+
+/// Parsed `/proc/net/dev` poll.
+#[derive(Debug, Default)]
+pub struct NetDev {
+    pub times: Vec<NaiveDateTime>,
+    pub ifaces: BTreeMap<String, IfaceStats>,
+    /// Malformed poll samples skipped in tolerant mode.
+    pub dropped_chunks: usize,
+}
+
+/// Parse a poll log of `/proc/net/dev`.
+pub fn parse_net_dev(text: &str) -> Result<NetDev, String> {
+    parse_net_dev_reader(text.as_bytes())
+}
+
+/// Network namespace label of a polled `net/dev` path: the path
+/// component before `net/dev` unless it is the plain `/proc/net/dev` of
+/// the root namespace, e.g. `/proc/1234/net/dev` labels `1234`.
+fn netns_label(path: &str) -> Option<String> {
+    let parent = path.trim_end_matches("net/dev").trim_end_matches('/');
+    match parent.rsplit('/').next() {
+        Some("proc") | Some("") | None => None,
+        Some(netns) => Some(netns.to_string()),
+    }
+}
+
+/// Parse a `/proc/net/dev` poll log incrementally from a reader.
+///
+/// A sample may carry several `net/dev` sections, e.g. the root
+/// namespace plus `/proc/<pid>/net/dev` of containers: interfaces of
+/// non-root namespaces get a `<netns>:` label prefix.
+pub fn parse_net_dev_reader<R: BufRead>(reader: R) -> Result<NetDev, String> {
+    let mut stat = NetDev::default();
+    for sample in PollSamples::new(reader) {
+        // As with meminfo: whole samples are validated before committing,
+        // and malformed ones are counted rather than fatal by default.
+        match sample.and_then(|sample| parse_net_dev_sample(&sample)) {
+            Ok((time, rows)) => {
+                stat.times.push(time);
+                for (name, values) in rows {
+                    let iface = stat.ifaces.entry(name).or_default();
+                    iface.rx_bytes.push(values[0]);
+                    iface.rx_packets.push(values[1]);
+                    iface.tx_bytes.push(values[8]);
+                    iface.tx_packets.push(values[9]);
+                }
+            }
+            Err(error) if crate::plotters::strict() => return Err(error),
+            Err(_) => stat.dropped_chunks += 1,
+        }
+    }
+    Ok(stat)
+}
+
+/// Raw counter row of one (namespace-prefixed) interface.
+type IfaceRow = (String, Vec<f64>);
+
+/// Validate one net/dev poll sample into its timestamp and the raw
+/// counter rows per interface.
+fn parse_net_dev_sample(sample: &PollSample) -> Result<(NaiveDateTime, Vec<IfaceRow>), String> {
+    let sections: Vec<&(String, String)> = sample
+        .files
+        .iter()
+        .filter(|(path, _)| path.ends_with("net/dev"))
+        .collect();
+    if sections.is_empty() {
+        return Err("no net/dev section in poll sample".to_string());
+    }
+    let mut rows = Vec::new();
+    for (path, content) in sections {
+        let prefix = netns_label(path).map(|ns| format!("{ns}:")).unwrap_or_default();
+        for line in content.lines() {
+            let (name, counters) = match line.split_once(':') {
+                Some(split) => split,
+                None => continue, // header lines
+            };
+            let values: Vec<f64> = counters
+                .split_whitespace()
+                .map(|t| t.parse().map_err(|e| format!("bad net/dev value '{t}': {e}")))
+                .collect::<Result<_, _>>()?;
+            if values.len() < 16 {
+                return Err(format!("short net/dev line: {line}"));
+            }
+            rows.push((format!("{prefix}{}", name.trim()), values));
+        }
+    }
+    Ok((millis_to_naive(sample.millis), rows))
+}
+
+/// Render per-interface traffic rates into `netdev.html`.
+#[cfg(feature = "plotter")]
+pub fn plot_net_dev(
+    stat: &NetDev,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+) -> std::io::Result<()> {
+    let mut bits = Vec::new();
+    let mut packets = Vec::new();
+    for (name, iface) in &stat.ifaces {
+        if !crate::plotters::filter::iface_shown(name) {
+            continue;
+        }
+        bits.push(rate_trace(&stat.times, &format!("{name} rx"), &iface.rx_bytes, 8e-6));
+        bits.push(rate_trace(&stat.times, &format!("{name} tx"), &iface.tx_bytes, 8e-6));
+        packets.push(rate_trace(&stat.times, &format!("{name} rx"), &iface.rx_packets, 1.0));
+        packets.push(rate_trace(&stat.times, &format!("{name} tx"), &iface.tx_packets, 1.0));
+    }
+
+    let mut page = Page::new("netdev");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    page.add_plot("Traffic, Mbit/s", bits);
+    page.add_plot("Packets/s", packets);
+    page.write(&outdir.join("netdev.html"))
+}
+
+/// Turn a monotonic counter into a per-second rate trace.
+#[cfg(feature = "plotter")]
+fn rate_trace(times: &[NaiveDateTime], name: &str, counter: &[f64], scale: f64) -> serde_json::Value {
+    let mut trace = Scatter::new(name);
+    for i in 1..counter.len().min(times.len()) {
+        let dt = (times[i] - times[i - 1]).num_milliseconds() as f64 / 1000.0;
+        if dt <= 0.0 {
+            continue;
+        }
+        let rate = (counter[i] - counter[i - 1]) / dt;
+        trace.push(plot::plotly_time(&times[i]), rate * scale);
+    }
+    trace.to_trace()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+=== 1724690000000
+--- /proc/meminfo
+MemTotal:       16384000 kB
+MemFree:         8192000 kB
+=== 1724690001000
+--- /proc/meminfo
+MemTotal:       16384000 kB
+MemFree:         4096000 kB
+";
+
+    #[test]
+    fn poll_log_splits_into_samples() {
+        let samples = parse_poll_log(SAMPLE).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].millis, 1724690000000);
+        assert_eq!(samples[0].files[0].0, "/proc/meminfo");
+    }
+
+    /// The same log written in the binary format parses identically: the
+    /// reader sniffs the magic and switches decoders.
+    #[test]
+    fn binary_poll_log_parses_like_text() {
+        let text = parse_poll_log(SAMPLE).unwrap();
+        let mut log = Vec::new();
+        let mut writer = crate::polllog::Writer::new(&mut log).unwrap();
+        for sample in &text {
+            writer.write_sample(sample.millis, &sample.files).unwrap();
+        }
+
+        let binary: Vec<PollSample> = PollSamples::new(log.as_slice())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(binary.len(), text.len());
+        for (a, b) in binary.iter().zip(&text) {
+            assert_eq!(a.millis, b.millis);
+            assert_eq!(a.files, b.files);
+        }
+    }
+
+    const SAMPLE_IRQ: &str = "\
+=== 1724690000000
+--- /proc/interrupts
+            CPU0       CPU1
+   0:         36          0   IO-APIC    2-edge      timer
+  25:       1000       2000   IR-PCI-MSI nvme0q1
+ ERR:          0
+=== 1724690001000
+--- /proc/interrupts
+            CPU0       CPU1
+   0:         36          0   IO-APIC    2-edge      timer
+  25:       1500       2600   IR-PCI-MSI nvme0q1
+ ERR:          0
+";
+
+    #[test]
+    fn interrupts_poll_parses() {
+        let stat = parse_interrupts(SAMPLE_IRQ).unwrap();
+        assert_eq!(stat.cpus, ["CPU0", "CPU1"]);
+        assert_eq!(stat.times.len(), 2);
+        let nvme = &stat.irqs["25 IR-PCI-MSI nvme0q1"];
+        assert_eq!(nvme[0], [1000.0, 1500.0]);
+        assert_eq!(nvme[1], [2000.0, 2600.0]);
+        // Single-total rows (ERR/MIS) have no per-CPU split.
+        assert!(!stat.irqs.keys().any(|k| k.starts_with("ERR")));
+    }
+
+    const SAMPLE_PSI: &str = "\
+=== 1724690000000
+--- /proc/pressure/cpu
+some avg10=1.50 avg60=0.80 avg300=0.20 total=1000000
+--- /proc/pressure/memory
+some avg10=0.00 avg60=0.00 avg300=0.00 total=0
+full avg10=0.00 avg60=0.00 avg300=0.00 total=0
+=== 1724690001000
+--- /proc/pressure/cpu
+some avg10=2.50 avg60=0.90 avg300=0.25 total=1250000
+--- /proc/pressure/memory
+some avg10=0.00 avg60=0.00 avg300=0.00 total=0
+full avg10=0.00 avg60=0.00 avg300=0.00 total=0
+";
+
+    #[test]
+    fn psi_poll_parses() {
+        let stat = parse_psi(SAMPLE_PSI).unwrap();
+        assert_eq!(stat.times.len(), 2);
+        assert_eq!(stat.series["cpu some"].avg10, [1.5, 2.5]);
+        assert_eq!(stat.series["cpu some"].total_us, [1000000.0, 1250000.0]);
+        assert_eq!(stat.series["memory full"].avg10, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn netns_sections_get_prefixed() {
+        let counters = "1 2 0 0 0 0 0 0 9 10 0 0 0 0 0 0";
+        let sample = format!(
+            "=== 1724690000000\n--- /proc/net/dev\neth0: {counters}\n\
+             --- /proc/4242/net/dev\neth0: {counters}\n"
+        );
+        let stat = parse_net_dev(&sample).unwrap();
+        assert_eq!(stat.ifaces["eth0"].rx_bytes, [1.0]);
+        assert_eq!(stat.ifaces["4242:eth0"].tx_bytes, [9.0]);
+    }
+
+    #[test]
+    fn meminfo_fields_are_captured() {
+        let stat = parse_meminfo(SAMPLE).unwrap();
+        assert_eq!(stat.times.len(), 2);
+        assert_eq!(stat.fields["MemFree"], [8192000.0, 4096000.0]);
+        assert_eq!(stat.fields["MemTotal"], [16384000.0, 16384000.0]);
+    }
+}
@@ -0,0 +1,92 @@
+//! Data-quality assessment of parsed sources.
+//!
+//! Sampling tools get killed, clocks step, disks fill up: the quality
+//! page states how trustworthy each parsed series is instead of leaving
+//! users to wonder why a chart looks sparse.
+
+use std::io;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::plot::Page;
+
+/// Quality figures of one parsed source.
+#[derive(Debug)]
+pub struct SourceQuality {
+    pub source: String,
+    pub samples: usize,
+    /// Sampling gaps larger than twice the typical interval.
+    pub gaps: usize,
+    /// Samples where time went backwards or stood still.
+    pub clock_anomalies: usize,
+    /// Chunks the parser had to skip as unparsable.
+    pub dropped_chunks: usize,
+}
+
+/// Assess one source from its sample timestamps. `dropped_chunks` comes
+/// from the parser when it runs in tolerant mode.
+pub fn assess(source: &str, times: &[NaiveDateTime], dropped_chunks: usize) -> SourceQuality {
+    let mut intervals: Vec<i64> = times
+        .windows(2)
+        .map(|w| (w[1] - w[0]).num_milliseconds())
+        .collect();
+    let clock_anomalies = intervals.iter().filter(|dt| **dt <= 0).count();
+
+    intervals.sort_unstable();
+    let typical = intervals.get(intervals.len() / 2).copied().unwrap_or(0);
+    let gaps = if typical > 0 {
+        intervals.iter().filter(|dt| **dt > 2 * typical).count()
+    } else {
+        0
+    };
+
+    SourceQuality {
+        source: source.to_string(),
+        samples: times.len(),
+        gaps,
+        clock_anomalies,
+        dropped_chunks,
+    }
+}
+
+/// Render the per-source quality table into `quality.html`.
+pub fn plot(rows: &[SourceQuality], outdir: &Path) -> io::Result<()> {
+    let mut page = Page::new("data quality");
+    let header = ["source", "samples", "gaps", "clock anomalies", "dropped chunks"]
+        .map(str::to_string)
+        .to_vec();
+    let table = rows
+        .iter()
+        .map(|q| {
+            vec![
+                q.source.clone(),
+                q.samples.to_string(),
+                q.gaps.to_string(),
+                q.clock_anomalies.to_string(),
+                q.dropped_chunks.to_string(),
+            ]
+        })
+        .collect();
+    page.add_table("Per-source data quality", header, table);
+    page.write(&outdir.join("quality.html"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::millis_to_naive;
+
+    #[test]
+    fn gaps_and_anomalies_are_counted() {
+        let times: Vec<NaiveDateTime> = [0u64, 1000, 2000, 7000, 8000, 8000]
+            .iter()
+            .map(|ms| millis_to_naive(*ms))
+            .collect();
+        let quality = assess("mpstat", &times, 1);
+        assert_eq!(quality.samples, 6);
+        assert_eq!(quality.gaps, 1);
+        assert_eq!(quality.clock_anomalies, 1);
+        assert_eq!(quality.dropped_chunks, 1);
+    }
+}
@@ -0,0 +1,339 @@
+//! Activity-name to plotter dispatch.
+//!
+//! Maps the activity names recorded in `out.map` to handlers that parse
+//! and plot the activity's output files. The built-in data sources are
+//! resolved here too, and downstream crates can [`register`] handlers for
+//! their custom activities instead of having them skipped.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use chrono::NaiveDateTime;
+
+use crate::common::readfile;
+use crate::export::{self, Format};
+use crate::plotters::quality::SourceQuality;
+use crate::plotters::sysstat::mpstat::HeatScale;
+use crate::plotters::{
+    cachestat, conntrack, dbstat, ethtool, fio, jvm, latency, nfs, procfs, quality, sar, smart,
+    sysstat, vmstat,
+};
+
+/// Everything a handler gets to process one activity of an agent
+/// directory.
+pub struct Context<'a> {
+    /// The agent output directory.
+    pub dir: &'a Path,
+    /// The activity id, as used in the output file names.
+    pub id: &'a str,
+    /// The activity name from `out.map`.
+    pub name: &'a str,
+    /// Measurement window markers recorded by the controller.
+    pub marks: &'a [(String, NaiveDateTime)],
+    /// Export the parsed series in this format, when requested.
+    pub export_to: Option<Format>,
+    pub scale: HeatScale,
+}
+
+/// A handler parses and plots the output files of one activity,
+/// optionally returning a data quality assessment for `quality.html`.
+///
+/// Handlers run in parallel over the activities of a directory, so they
+/// must be `Send + Sync`.
+pub type Handler = Arc<dyn Fn(&Context) -> io::Result<Option<SourceQuality>> + Send + Sync>;
+
+/// Handlers registered at runtime, taking precedence over the built-ins.
+static REGISTERED: Mutex<BTreeMap<String, Handler>> = Mutex::new(BTreeMap::new());
+
+/// Register a handler for the given activity name process-wide. An
+/// existing handler for the name, built-in or registered, is shadowed.
+pub fn register(name: &str, handler: Handler) {
+    REGISTERED.lock().unwrap().insert(name.to_string(), handler);
+}
+
+/// Find the handler for an activity name: registered ones first, then the
+/// built-in data sources.
+pub fn lookup(name: &str) -> Option<Handler> {
+    if let Some(handler) = REGISTERED.lock().unwrap().get(name) {
+        return Some(handler.clone());
+    }
+    let builtin: fn(&Context) -> io::Result<Option<SourceQuality>> = match name {
+        "mpstat" => mpstat,
+        "iostat" => iostat,
+        "sar" => sar,
+        "pidstat" => pidstat,
+        "vmstat" => vmstat,
+        "meminfo" => meminfo,
+        "netdev" => netdev,
+        "ethtool" => ethtool,
+        "interrupts" => interrupts,
+        "pressure" => pressure,
+        "fio" => fio,
+        "pgstat" | "mysqlstat" => dbstat,
+        "redis" | "memcached" => cachestat,
+        "jvm" => jvm,
+        "smart" => smart,
+        "nfs" => nfs,
+        "conntrack" => conntrack,
+        "cyclictest" => cyclictest,
+        "schbench" => schbench,
+        "snapshot" => snapshot,
+        // The closing snapshot is part of the smart activity above.
+        "smart-after" => |_| Ok(None),
+        // Launched commands have no structured output to plot; failures
+        // surface through the report problems section.
+        "launch" => |_| Ok(None),
+        _ => return None,
+    };
+    Some(Arc::new(builtin))
+}
+
+/// Fall back to the pre-exported table when the agent converted and
+/// removed a raw log (`pmppt_agent --pre-export`). Only CSV can be read
+/// back; other formats leave the activity unplotted.
+fn exported_fallback(ctx: &Context, err: io::Error) -> io::Result<Option<SourceQuality>> {
+    let path = ctx.dir.join(format!("{}.csv", ctx.name));
+    if err.kind() != io::ErrorKind::NotFound || !path.exists() {
+        return Err(err);
+    }
+    let table = export::read_csv(&path)?;
+    let times = plot_exported(&table, ctx)?;
+    Ok(Some(quality::assess(ctx.name, &times, 0)))
+}
+
+/// Render a tidy table generically: one plot per metric, one trace per
+/// key column combination. Loses the source-specific chart layouts but
+/// keeps huge runs plottable without the raw data.
+fn plot_exported(table: &export::Table, ctx: &Context) -> io::Result<Vec<NaiveDateTime>> {
+    use crate::plot::{Page, Scatter};
+
+    let metric = table.columns.iter().position(|c| c == "metric");
+    let mut plots: BTreeMap<String, BTreeMap<String, Scatter>> = BTreeMap::new();
+    let mut times: std::collections::BTreeSet<NaiveDateTime> = std::collections::BTreeSet::new();
+    for row in &table.rows {
+        if row.len() != table.columns.len() || row.len() < 2 {
+            continue;
+        }
+        let Ok(time) = NaiveDateTime::parse_from_str(&row[0], "%Y-%m-%d %H:%M:%S%.3f") else {
+            continue;
+        };
+        let Ok(value) = row[row.len() - 1].parse::<f64>() else {
+            continue;
+        };
+        times.insert(time);
+        let plot = match metric {
+            Some(i) => row[i].clone(),
+            None => table.columns[table.columns.len() - 1].clone(),
+        };
+        let keys: Vec<&str> = row[1..row.len() - 1]
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| Some(i + 1) != metric)
+            .map(|(_, v)| v.as_str())
+            .collect();
+        let trace = if keys.is_empty() {
+            plot.clone()
+        } else {
+            keys.join(" ")
+        };
+        plots
+            .entry(plot)
+            .or_default()
+            .entry(trace.clone())
+            .or_insert_with(|| Scatter::new(&trace))
+            .push(row[0].clone(), value);
+    }
+
+    let mut page = Page::new(&format!("{} (pre-exported)", ctx.name));
+    page.set_marks(ctx.marks);
+    for (title, traces) in plots {
+        page.add_plot(&title, traces.into_values().map(|t| t.to_trace()).collect());
+    }
+    page.write(&ctx.dir.join(format!("{}.html", ctx.name)))?;
+    Ok(times.into_iter().collect())
+}
+
+fn out_log(ctx: &Context) -> io::Result<BufReader<File>> {
+    Ok(BufReader::new(File::open(
+        ctx.dir.join(format!("{}-out.log", ctx.id)),
+    )?))
+}
+
+fn poll_log(ctx: &Context) -> io::Result<BufReader<File>> {
+    Ok(BufReader::new(File::open(
+        ctx.dir.join(format!("{}-poll.log", ctx.id)),
+    )?))
+}
+
+fn mpstat(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let log = match out_log(ctx) {
+        Ok(log) => log,
+        Err(err) => return exported_fallback(ctx, err),
+    };
+    let stat = sysstat::mpstat::parse_reader(log).map_err(io::Error::other)?;
+    sysstat::mpstat::plot(&stat, ctx.dir, ctx.marks, ctx.scale)?;
+    if let Some(format) = ctx.export_to {
+        export::mpstat(&stat).write(ctx.dir, format)?;
+    }
+    Ok(Some(quality::assess(ctx.name, &stat.times, stat.dropped_chunks)))
+}
+
+fn iostat(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let log = match out_log(ctx) {
+        Ok(log) => log,
+        Err(err) => return exported_fallback(ctx, err),
+    };
+    let stat = sysstat::iostat::parse_reader(log).map_err(io::Error::other)?;
+    sysstat::iostat::plot(&stat, ctx.dir, ctx.marks)?;
+    if let Some(format) = ctx.export_to {
+        export::iostat(&stat).write(ctx.dir, format)?;
+    }
+    Ok(Some(quality::assess(ctx.name, &stat.times, stat.dropped_chunks)))
+}
+
+fn sar(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let text = readfile(&ctx.dir.join(format!("{}-out.log", ctx.id)))?;
+    let stat = sar::parse(&text).map_err(io::Error::other)?;
+    sar::plot(&stat, ctx.dir, ctx.marks)?;
+    Ok(None)
+}
+
+fn pidstat(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let text = readfile(&ctx.dir.join(format!("{}-out.log", ctx.id)))?;
+    let stat = sysstat::pidstat::parse(&text).map_err(io::Error::other)?;
+    sysstat::pidstat::plot(&stat, ctx.dir, ctx.marks)?;
+    Ok(None)
+}
+
+fn vmstat(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let text = match readfile(&ctx.dir.join(format!("{}-out.log", ctx.id))) {
+        Ok(text) => text,
+        Err(err) => return exported_fallback(ctx, err),
+    };
+    let stat = vmstat::parse(&text).map_err(io::Error::other)?;
+    vmstat::plot(&stat, ctx.dir, ctx.marks)?;
+    if let Some(format) = ctx.export_to {
+        export::vmstat(&stat).write(ctx.dir, format)?;
+    }
+    Ok(Some(quality::assess(ctx.name, &stat.times, 0)))
+}
+
+fn meminfo(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let log = match poll_log(ctx) {
+        Ok(log) => log,
+        Err(err) => return exported_fallback(ctx, err),
+    };
+    let stat = procfs::parse_meminfo_reader(log).map_err(io::Error::other)?;
+    procfs::plot_meminfo(&stat, ctx.dir, ctx.marks)?;
+    if let Some(format) = ctx.export_to {
+        export::meminfo(&stat).write(ctx.dir, format)?;
+    }
+    Ok(Some(quality::assess(ctx.name, &stat.times, stat.dropped_chunks)))
+}
+
+fn netdev(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let log = match poll_log(ctx) {
+        Ok(log) => log,
+        Err(err) => return exported_fallback(ctx, err),
+    };
+    let stat = procfs::parse_net_dev_reader(log).map_err(io::Error::other)?;
+    procfs::plot_net_dev(&stat, ctx.dir, ctx.marks)?;
+    if let Some(format) = ctx.export_to {
+        export::net_dev(&stat).write(ctx.dir, format)?;
+    }
+    Ok(Some(quality::assess(ctx.name, &stat.times, stat.dropped_chunks)))
+}
+
+fn ethtool(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let stat = ethtool::parse_reader(out_log(ctx)?).map_err(io::Error::other)?;
+    ethtool::plot(&stat, ctx.dir, ctx.marks)?;
+    Ok(Some(quality::assess(ctx.name, &stat.times, 0)))
+}
+
+fn interrupts(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let stat = procfs::parse_interrupts_reader(poll_log(ctx)?).map_err(io::Error::other)?;
+    procfs::plot_interrupts(&stat, ctx.dir, ctx.marks)?;
+    Ok(Some(quality::assess(ctx.name, &stat.times, 0)))
+}
+
+fn pressure(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let stat = procfs::parse_psi_reader(poll_log(ctx)?).map_err(io::Error::other)?;
+    procfs::plot_psi(&stat, ctx.dir, ctx.marks)?;
+    Ok(Some(quality::assess(ctx.name, &stat.times, 0)))
+}
+
+fn dbstat(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let stat = dbstat::parse_reader(out_log(ctx)?).map_err(io::Error::other)?;
+    dbstat::plot(&stat, ctx.dir, ctx.marks, ctx.name)?;
+    Ok(Some(quality::assess(ctx.name, &stat.times, 0)))
+}
+
+fn conntrack(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    // Plain polling writes a poll log; the nftables variant is a shell
+    // loop writing the same format to stdout.
+    let reader = poll_log(ctx).or_else(|_| out_log(ctx))?;
+    let stat = conntrack::parse_reader(reader).map_err(io::Error::other)?;
+    conntrack::plot(&stat, ctx.dir, ctx.marks)?;
+    Ok(Some(quality::assess(ctx.name, &stat.times, 0)))
+}
+
+fn nfs(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let stat = nfs::parse_reader(poll_log(ctx)?).map_err(io::Error::other)?;
+    nfs::plot(&stat, ctx.dir, ctx.marks)?;
+    Ok(Some(quality::assess(ctx.name, &stat.times, 0)))
+}
+
+fn cyclictest(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let text = readfile(&ctx.dir.join(format!("{}-out.log", ctx.id)))?;
+    let stat = latency::parse_cyclictest(&text).map_err(io::Error::other)?;
+    latency::plot_cyclictest(&stat, ctx.dir, ctx.marks)?;
+    Ok(None)
+}
+
+fn schbench(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    // schbench prints its report to stderr.
+    let text = readfile(&ctx.dir.join(format!("{}-err.log", ctx.id)))?;
+    let stat = latency::parse_schbench(&text).map_err(io::Error::other)?;
+    latency::plot_schbench(&stat, ctx.dir, ctx.marks)?;
+    Ok(None)
+}
+
+fn snapshot(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    // The command output is freeform, so only the sampling cadence is
+    // assessed; the raw log stays in the archive for manual inspection.
+    let times: Vec<NaiveDateTime> = procfs::PollSamples::new(out_log(ctx)?)
+        .map(|sample| sample.map(|s| crate::common::millis_to_naive(s.millis)))
+        .collect::<Result<_, _>>()
+        .map_err(io::Error::other)?;
+    Ok(Some(quality::assess(ctx.name, &times, 0)))
+}
+
+fn smart(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let before = smart::parse(&readfile(&ctx.dir.join("smart-before"))?);
+    let after = smart::parse(&readfile(&ctx.dir.join("smart-after"))?);
+    smart::plot(&before, &after, ctx.dir)?;
+    Ok(None)
+}
+
+fn jvm(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let stat = jvm::parse_reader(out_log(ctx)?).map_err(io::Error::other)?;
+    jvm::plot(&stat, ctx.dir, ctx.marks)?;
+    Ok(Some(quality::assess(ctx.name, &stat.times, 0)))
+}
+
+fn cachestat(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    let stat = cachestat::parse_reader(out_log(ctx)?).map_err(io::Error::other)?;
+    cachestat::plot(&stat, ctx.dir, ctx.marks, ctx.name)?;
+    Ok(Some(quality::assess(ctx.name, &stat.times, 0)))
+}
+
+fn fio(ctx: &Context) -> io::Result<Option<SourceQuality>> {
+    fio::plot(ctx.dir, "fio")?;
+    if let Some(format) = ctx.export_to {
+        fio::export(ctx.dir, "fio", format)?;
+    }
+    Ok(None)
+}
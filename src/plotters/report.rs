@@ -0,0 +1,241 @@
+//! Unified run report tying the per-source pages together.
+//!
+//! The per-source plotters keep writing their own pages; `write` generates
+//! one `report.html` in the run directory with run metadata and navigation
+//! tabs per agent and per data source, so sharing a result means sharing a
+//! single directory with one entry point.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::common::readfile;
+use crate::plotters::read_mapping;
+
+/// Collect what went wrong in one agent directory: non-empty stderr logs
+/// and non-zero exit statuses from the journal.
+///
+/// Background monitors are killed at stage end and report status -1;
+/// only genuine failures (positive statuses) are surfaced.
+pub fn collect_problems(dir: &Path) -> io::Result<Vec<String>> {
+    let mut problems = Vec::new();
+    let mapping = read_mapping(dir)?;
+    let name_of = |id: &str| {
+        mapping
+            .iter()
+            .find(|(i, _)| i == id)
+            .map(|(_, name)| name.as_str())
+            .unwrap_or("unknown")
+    };
+
+    for (id, name) in &mapping {
+        let Ok(stderr) = readfile(&dir.join(format!("{id}-err.log"))) else {
+            continue;
+        };
+        let mut lines = stderr.lines().filter(|l| !l.trim().is_empty());
+        if let Some(first) = lines.next() {
+            let more = lines.count();
+            let suffix = if more > 0 { format!(" (+{more} more lines)") } else { String::new() };
+            problems.push(format!("{name} ({id}): stderr: {first}{suffix}"));
+        }
+    }
+
+    if let Ok(journal) = readfile(&dir.join("journal.log")) {
+        for line in journal.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if let [_millis, "stop", id, status] = fields.as_slice() {
+                if status.parse::<i32>().is_ok_and(|s| s > 0) {
+                    problems
+                        .push(format!("{} ({id}): exited with status {status}", name_of(id)));
+                }
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// Escape text destined for HTML element content.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Render the run metadata recorded by the controller in `run.json`: the
+/// agents with their hardware, a stage timeline and the config used.
+/// Reports of runs collected without the metadata render nothing.
+fn write_meta(out: &mut impl Write, run_dir: &Path) -> io::Result<()> {
+    let Ok(text) = readfile(&run_dir.join("run.json")) else {
+        return Ok(());
+    };
+    let Ok(meta) = serde_json::from_str::<serde_json::Value>(&text) else {
+        return Ok(());
+    };
+
+    if let Some(agents) = meta["agents"].as_array() {
+        writeln!(out, "<h2>Agents</h2>")?;
+        writeln!(out, "<table border=\"1\" cellpadding=\"4\">")?;
+        writeln!(
+            out,
+            "<tr><th>agent</th><th>addr</th><th>uname</th><th>cpus</th><th>memory</th></tr>"
+        )?;
+        for agent in agents {
+            let field = |name: &str| agent[name].as_str().unwrap_or("-").to_string();
+            let addr = match agent["local"].as_bool() {
+                Some(true) => "local".to_string(),
+                _ => field("addr"),
+            };
+            let cpus = agent["cpus"]
+                .as_u64()
+                .map_or_else(|| "-".to_string(), |n| n.to_string());
+            let memory = agent["mem_total_kb"].as_f64().map_or_else(
+                || "-".to_string(),
+                |kb| format!("{:.1} GiB", kb / (1024.0 * 1024.0)),
+            );
+            writeln!(
+                out,
+                "<tr><td>{}</td><td>{addr}</td><td>{}</td><td>{cpus}</td><td>{memory}</td></tr>",
+                field("name"),
+                field("uname"),
+            )?;
+        }
+        writeln!(out, "</table>")?;
+
+        // The long environment facts gathered at pre-flight, folded away:
+        // they matter when two runs disagree, not on every read.
+        writeln!(out, "<details><summary>System details</summary>")?;
+        writeln!(out, "<table border=\"1\" cellpadding=\"4\">")?;
+        writeln!(
+            out,
+            "<tr><th>agent</th><th>governor</th><th>smt</th><th>cmdline</th></tr>"
+        )?;
+        for agent in agents {
+            let field = |name: &str| escape(agent[name].as_str().unwrap_or("-"));
+            writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                field("name"),
+                field("governor"),
+                field("smt"),
+                field("cmdline"),
+            )?;
+        }
+        writeln!(out, "</table></details>")?;
+    }
+
+    if let Some(stages) = meta["stages"].as_array() {
+        let span = |name: &str| stages.iter().filter_map(|s| s[name].as_u64()).collect::<Vec<_>>();
+        let from = span("start_ms").into_iter().min().unwrap_or(0);
+        let to = span("stop_ms").into_iter().max().unwrap_or(from + 1).max(from + 1);
+        let total = (to - from) as f64;
+        writeln!(out, "<h2>Stages</h2>")?;
+        for stage in stages {
+            let (Some(start), Some(stop)) = (stage["start_ms"].as_u64(), stage["stop_ms"].as_u64())
+            else {
+                continue;
+            };
+            let left = (start - from) as f64 / total * 100.0;
+            let width = (stop - start) as f64 / total * 100.0;
+            let label = stage["name"].as_str().unwrap_or("?");
+            let seconds = (stop - start) as f64 / 1000.0;
+            writeln!(
+                out,
+                "<div style=\"position: relative; width: 60%; height: 1.5em; \
+                 background: #eee; margin: 2px 0;\">\
+                 <div style=\"position: absolute; left: {left:.1}%; width: {width:.1}%; \
+                 min-width: 2px; height: 100%; background: #68a;\"></div>\
+                 <span style=\"position: absolute; left: 4px;\">{label} ({seconds:.0} s)</span>\
+                 </div>"
+            )?;
+        }
+    }
+
+    if let Some(config) = meta["config"].as_str() {
+        writeln!(out, "<details><summary>Config</summary>")?;
+        writeln!(out, "<pre>{}</pre></details>", escape(config))?;
+    }
+    Ok(())
+}
+
+/// Collect the HTML pages generated in one agent directory, sorted.
+pub fn collect_pages(dir: &Path) -> io::Result<Vec<String>> {
+    let mut pages = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".html") {
+            pages.push(name);
+        }
+    }
+    pages.sort();
+    Ok(pages)
+}
+
+/// Write `report.html` into the run directory.
+///
+/// `agents` maps agent names to their generated pages, relative to the
+/// agent subdirectory of the same name.
+pub fn write(run_dir: &Path, agents: &[(String, Vec<String>)]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(run_dir.join("report.html"))?);
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>pmppt report</title>")?;
+    writeln!(out, "<style>")?;
+    writeln!(out, ".tab {{ margin: 2px; }}")?;
+    writeln!(out, ".tab.active {{ font-weight: bold; }}")?;
+    writeln!(
+        out,
+        "iframe {{ width: 100%; height: 85vh; border: 1px solid gray; }}"
+    )?;
+    writeln!(out, "</style></head><body>")?;
+    writeln!(out, "<h1>pmppt report</h1>")?;
+
+    write_meta(&mut out, run_dir)?;
+
+    // Artifact manifest recorded by the controller, when present.
+    if let Ok(manifest) = readfile(&run_dir.join("manifest.json")) {
+        writeln!(out, "<details><summary>Run manifest</summary>")?;
+        writeln!(out, "<pre>{manifest}</pre></details>")?;
+    }
+
+    // Failed monitors otherwise just manifest as missing charts; surface
+    // their stderr and exit statuses up front.
+    let mut problems = Vec::new();
+    for (agent, _) in agents {
+        for problem in collect_problems(&run_dir.join(agent))? {
+            problems.push(format!("{agent}: {problem}"));
+        }
+    }
+    if !problems.is_empty() {
+        writeln!(out, "<h2 style=\"color: #b00\">Problems</h2><ul>")?;
+        for problem in &problems {
+            writeln!(out, "<li>{problem}</li>")?;
+        }
+        writeln!(out, "</ul>")?;
+    }
+
+    for (agent, pages) in agents {
+        writeln!(out, "<h2>{agent}</h2>")?;
+        for page in pages {
+            writeln!(
+                out,
+                "<button class=\"tab\" onclick=\"show(this, '{agent}/{page}')\">{}</button>",
+                page.trim_end_matches(".html")
+            )?;
+        }
+    }
+
+    writeln!(out, "<div><iframe id=\"view\" src=\"about:blank\"></iframe></div>")?;
+    writeln!(out, "<script>")?;
+    writeln!(out, "function show(button, page) {{")?;
+    writeln!(
+        out,
+        "  for (const tab of document.querySelectorAll('.tab')) \
+         tab.classList.remove('active');"
+    )?;
+    writeln!(out, "  button.classList.add('active');")?;
+    writeln!(out, "  document.getElementById('view').src = page;")?;
+    writeln!(out, "}}")?;
+    writeln!(out, "const first = document.querySelector('.tab');")?;
+    writeln!(out, "if (first) first.click();")?;
+    writeln!(out, "</script>")?;
+    writeln!(out, "</body></html>")?;
+    Ok(())
+}
@@ -0,0 +1,224 @@
+//! `sar -A` text output parsing and plotting.
+//!
+//! Many production hosts already run sadc; the sar activity simply runs
+//! `sar -A <period>` and this module picks the interesting sections out
+//! of the combined report: overall CPU, memory, paging and per-interface
+//! network rates.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::plot::{self, Page, Scatter};
+
+/// Parsed `sar -A` capture, one series per recognized section.
+#[derive(Debug, Default)]
+pub struct Sar {
+    /// Overall CPU busy (100 - %idle of the "all" row), percent.
+    pub cpu_busy: Vec<(NaiveDateTime, f64)>,
+    /// Used memory, GiB.
+    pub mem_used_gib: Vec<(NaiveDateTime, f64)>,
+    /// Paged in/out, kB/s.
+    pub paging: Vec<(NaiveDateTime, f64, f64)>,
+    /// Per-interface (rx, tx) rates, kB/s.
+    pub net: BTreeMap<String, Vec<(NaiveDateTime, f64, f64)>>,
+}
+
+fn parse_time(token: &str, rest: &[&str], date: NaiveDate) -> Option<NaiveDateTime> {
+    // sar prints either 24-hour times or 12-hour ones followed by AM/PM.
+    if let Some(meridian) = rest.first().filter(|t| **t == "AM" || **t == "PM") {
+        let stamp = format!("{token} {meridian}");
+        return NaiveTime::parse_from_str(&stamp, "%I:%M:%S %p")
+            .ok()
+            .map(|time| NaiveDateTime::new(date, time));
+    }
+    NaiveTime::parse_from_str(token, "%H:%M:%S")
+        .ok()
+        .map(|time| NaiveDateTime::new(date, time))
+}
+
+fn banner_date(banner: &str) -> Option<NaiveDate> {
+    for token in banner.split_whitespace() {
+        for format in ["%m/%d/%Y", "%m/%d/%y", "%Y-%m-%d"] {
+            if let Ok(date) = NaiveDate::parse_from_str(token, format) {
+                return Some(date);
+            }
+        }
+    }
+    None
+}
+
+/// Parse raw `sar -A <interval>` output.
+pub fn parse(text: &str) -> Result<Sar, String> {
+    let mut chunks = text.split("\n\n");
+    let banner = chunks.next().ok_or("empty sar file")?;
+    let date = banner_date(banner).ok_or("no date in sar banner")?;
+
+    let mut stat = Sar::default();
+    for chunk in chunks {
+        let mut lines = chunk.lines().filter(|l| !l.trim().is_empty());
+        let Some(header) = lines.next() else { continue };
+        if header.starts_with("Average:") {
+            continue;
+        }
+        let columns: Vec<&str> = header.split_whitespace().collect();
+        // Skip the time (and possible AM/PM) prefix of the header.
+        let names: Vec<&str> = columns
+            .iter()
+            .skip_while(|t| !t.starts_with('%') && !t.chars().next().is_some_and(char::is_alphabetic))
+            .copied()
+            .collect();
+
+        let col = |name: &str| names.iter().position(|t| *t == name);
+
+        if let (Some(cpu), Some(idle)) = (col("CPU"), col("%idle")) {
+            for line in lines {
+                let (Some(time), fields) = split_data_line(line, date) else {
+                    continue;
+                };
+                if fields.get(cpu).copied() == Some("all") {
+                    if let Some(Ok(idle)) = fields.get(idle).map(|v| v.parse::<f64>()) {
+                        stat.cpu_busy.push((time, 100.0 - idle));
+                    }
+                }
+            }
+        } else if let Some(used) = col("kbmemused") {
+            for line in lines {
+                let (Some(time), fields) = split_data_line(line, date) else {
+                    continue;
+                };
+                if let Some(Ok(kb)) = fields.get(used).map(|v| v.parse::<f64>()) {
+                    stat.mem_used_gib.push((time, kb / (1024.0 * 1024.0)));
+                }
+            }
+        } else if let (Some(pgin), Some(pgout)) = (col("pgpgin/s"), col("pgpgout/s")) {
+            for line in lines {
+                let (Some(time), fields) = split_data_line(line, date) else {
+                    continue;
+                };
+                let pgin = fields.get(pgin).and_then(|v| v.parse().ok());
+                let pgout = fields.get(pgout).and_then(|v| v.parse().ok());
+                if let (Some(pgin), Some(pgout)) = (pgin, pgout) {
+                    stat.paging.push((time, pgin, pgout));
+                }
+            }
+        } else if let (Some(iface), Some(rx), Some(tx)) = (col("IFACE"), col("rxkB/s"), col("txkB/s")) {
+            for line in lines {
+                let (Some(time), fields) = split_data_line(line, date) else {
+                    continue;
+                };
+                let Some(name) = fields.get(iface) else { continue };
+                let rx = fields.get(rx).and_then(|v| v.parse().ok());
+                let tx = fields.get(tx).and_then(|v| v.parse().ok());
+                if let (Some(rx), Some(tx)) = (rx, tx) {
+                    stat.net
+                        .entry(name.to_string())
+                        .or_default()
+                        .push((time, rx, tx));
+                }
+            }
+        }
+    }
+    Ok(stat)
+}
+
+/// Split a sar data line into its timestamp and the remaining fields.
+fn split_data_line(line: &str, date: NaiveDate) -> (Option<NaiveDateTime>, Vec<&str>) {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let Some((first, rest)) = tokens.split_first() else {
+        return (None, Vec::new());
+    };
+    let time = parse_time(first, rest, date);
+    let fields = if rest.first().is_some_and(|t| *t == "AM" || *t == "PM") {
+        rest[1..].to_vec()
+    } else {
+        rest.to_vec()
+    };
+    (time, fields)
+}
+
+/// Render the recognized sections into `sar.html`.
+pub fn plot(stat: &Sar, outdir: &Path, marks: &[(String, NaiveDateTime)]) -> std::io::Result<()> {
+    let mut page = Page::new("sar");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+
+    if !stat.cpu_busy.is_empty() {
+        let mut trace = Scatter::new("busy");
+        for (time, busy) in &stat.cpu_busy {
+            trace.push(plot::plotly_time(time), *busy);
+        }
+        page.add_plot("CPU busy, %", vec![trace.to_trace()]);
+    }
+    if !stat.mem_used_gib.is_empty() {
+        let mut trace = Scatter::new("used");
+        for (time, gib) in &stat.mem_used_gib {
+            trace.push(plot::plotly_time(time), *gib);
+        }
+        page.add_plot("Memory used, GiB", vec![trace.to_trace()]);
+    }
+    if !stat.paging.is_empty() {
+        let mut pgin = Scatter::new("pgpgin/s");
+        let mut pgout = Scatter::new("pgpgout/s");
+        for (time, in_kb, out_kb) in &stat.paging {
+            pgin.push(plot::plotly_time(time), *in_kb);
+            pgout.push(plot::plotly_time(time), *out_kb);
+        }
+        page.add_plot("Paging, kB/s", vec![pgin.to_trace(), pgout.to_trace()]);
+    }
+    if !stat.net.is_empty() {
+        let mut traces = Vec::new();
+        for (iface, samples) in &stat.net {
+            if !crate::plotters::filter::iface_shown(iface) {
+                continue;
+            }
+            let mut rx = Scatter::new(&format!("{iface} rx"));
+            let mut tx = Scatter::new(&format!("{iface} tx"));
+            for (time, rx_kb, tx_kb) in samples {
+                rx.push(plot::plotly_time(time), *rx_kb);
+                tx.push(plot::plotly_time(time), *tx_kb);
+            }
+            traces.push(rx.to_trace());
+            traces.push(tx.to_trace());
+        }
+        page.add_plot("Network, kB/s", traces);
+    }
+
+    if page.is_empty() {
+        return Ok(());
+    }
+    page.write(&outdir.join("sar.html"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+Linux 6.5.0 (host) \t08/26/2026 \t_x86_64_\t(2 CPU)
+
+17:04:02        CPU     %user     %nice   %system   %iowait    %steal     %idle
+17:04:03        all      1.00      0.00      0.50      0.00      0.00     98.50
+17:04:03          0      2.00      0.00      1.00      0.00      0.00     97.00
+
+17:04:02    kbmemfree   kbavail kbmemused  %memused kbbuffers  kbcached
+17:04:03      8192000   9000000   2097152     20.00    100000    500000
+
+17:04:02     pgpgin/s pgpgout/s   fault/s  majflt/s
+17:04:03        12.00     34.00    100.00      0.00
+
+17:04:02        IFACE   rxpck/s   txpck/s    rxkB/s    txkB/s   rxcmp/s   txcmp/s  rxmcst/s
+17:04:03         eth0     10.00      5.00    100.00     50.00      0.00      0.00      0.00
+";
+
+    #[test]
+    fn sections_are_recognized() {
+        let stat = parse(SAMPLE).unwrap();
+        assert_eq!(stat.cpu_busy.len(), 1);
+        assert!((stat.cpu_busy[0].1 - 1.5).abs() < 1e-9);
+        assert_eq!(stat.mem_used_gib.len(), 1);
+        assert_eq!(stat.paging, [(stat.paging[0].0, 12.0, 34.0)]);
+        assert_eq!(stat.net["eth0"][0].1, 100.0);
+    }
+}
@@ -0,0 +1,208 @@
+//! Device health snapshots via `nvme smart-log` / `smartctl -a`.
+//!
+//! The smart activity writes `smart-before` at stage start and
+//! `smart-after` when the stage ends (both with `--- smart:<device>`
+//! section markers); this module diffs the two and renders `smart.html`
+//! flagging devices that errored or throttled during the run.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Numeric health fields per device.
+pub type Snapshot = BTreeMap<String, BTreeMap<String, f64>>;
+
+/// One changed field between the before and after snapshots.
+#[derive(Debug)]
+pub struct Delta {
+    pub device: String,
+    pub field: String,
+    pub before: f64,
+    pub after: f64,
+    /// Set when the change indicates errors or throttling rather than
+    /// plain usage counters ticking up.
+    pub flagged: bool,
+}
+
+/// Parse a snapshot file: `--- smart:<device>` sections with either
+/// `name : value` lines (nvme smart-log, smartctl info fields) or
+/// smartctl attribute table rows. Non-numeric values are skipped.
+pub fn parse(text: &str) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    let mut device = String::new();
+    for line in text.lines() {
+        if let Some(dev) = line.strip_prefix("--- smart:") {
+            device = dev.trim().to_string();
+            snapshot.entry(device.clone()).or_default();
+            continue;
+        }
+        if device.is_empty() {
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if let Some(value) = leading_number(value) {
+                snapshot
+                    .get_mut(&device)
+                    .expect("section inserted above")
+                    .insert(name.trim().to_string(), value);
+                continue;
+            }
+        }
+        // smartctl attribute table: ID# ATTRIBUTE_NAME ... RAW_VALUE.
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() >= 10 && fields[0].chars().all(|c| c.is_ascii_digit()) {
+            if let Some(value) = leading_number(fields[9]) {
+                snapshot
+                    .get_mut(&device)
+                    .expect("section inserted above")
+                    .insert(fields[1].to_string(), value);
+            }
+        }
+    }
+    snapshot
+}
+
+/// The first number in a value, tolerating thousands separators and
+/// units (`1,234`, `36 C`, `1%`).
+fn leading_number(value: &str) -> Option<f64> {
+    let cleaned: String = value
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | ','))
+        .filter(|c| *c != ',')
+        .collect();
+    cleaned.parse().ok()
+}
+
+/// Fields whose growth means trouble rather than usage.
+fn alarming(field: &str) -> bool {
+    let field = field.to_ascii_lowercase();
+    ["err", "critical", "warning", "throttle", "realloc", "pending"]
+        .iter()
+        .any(|bad| field.contains(bad))
+}
+
+/// All fields that changed between the snapshots.
+pub fn deltas(before: &Snapshot, after: &Snapshot) -> Vec<Delta> {
+    let mut deltas = Vec::new();
+    for (device, fields) in before {
+        let Some(after_fields) = after.get(device) else {
+            continue;
+        };
+        for (field, old) in fields {
+            let Some(new) = after_fields.get(field) else {
+                continue;
+            };
+            if new != old {
+                deltas.push(Delta {
+                    device: device.clone(),
+                    field: field.clone(),
+                    before: *old,
+                    after: *new,
+                    flagged: alarming(field) && new > old,
+                });
+            }
+        }
+    }
+    deltas
+}
+
+/// Render the snapshot diff into `smart.html`.
+pub fn plot(before: &Snapshot, after: &Snapshot, outdir: &Path) -> io::Result<()> {
+    let deltas = deltas(before, after);
+    let mut out = BufWriter::new(File::create(outdir.join("smart.html"))?);
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>device health</title></head><body>")?;
+    writeln!(out, "<h1>Device health over the run</h1>")?;
+
+    let flagged: Vec<&Delta> = deltas.iter().filter(|d| d.flagged).collect();
+    if !flagged.is_empty() {
+        writeln!(out, "<h2 style=\"color: #b00\">Flagged</h2><ul>")?;
+        for delta in &flagged {
+            writeln!(
+                out,
+                "<li>{}: {} went {} -> {}</li>",
+                delta.device, delta.field, delta.before, delta.after
+            )?;
+        }
+        writeln!(out, "</ul>")?;
+    }
+
+    if deltas.is_empty() {
+        writeln!(out, "<p>No health counters changed.</p>")?;
+    } else {
+        writeln!(out, "<table border=\"1\" cellpadding=\"4\">")?;
+        writeln!(
+            out,
+            "<tr><th>device</th><th>field</th><th>before</th><th>after</th></tr>"
+        )?;
+        for delta in &deltas {
+            let style = if delta.flagged {
+                " style=\"background: #fdd\""
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "<tr{style}><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                delta.device, delta.field, delta.before, delta.after
+            )?;
+        }
+        writeln!(out, "</table>")?;
+    }
+    writeln!(out, "</body></html>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEFORE: &str = "\
+--- smart:/dev/nvme0n1
+critical_warning : 0
+temperature : 36 C
+media_errors : 0
+data_units_read : 1,234
+--- smart:/dev/sda
+  5 Reallocated_Sector_Ct   0x0033   100   100   010    Pre-fail  Always       -       0
+";
+
+    const AFTER: &str = "\
+--- smart:/dev/nvme0n1
+critical_warning : 0
+temperature : 51 C
+media_errors : 2
+data_units_read : 5,678
+--- smart:/dev/sda
+  5 Reallocated_Sector_Ct   0x0033   100   100   010    Pre-fail  Always       -       3
+";
+
+    #[test]
+    fn snapshots_parse_and_diff() {
+        let before = parse(BEFORE);
+        let after = parse(AFTER);
+        assert_eq!(before["/dev/nvme0n1"]["data_units_read"], 1234.0);
+        assert_eq!(before["/dev/sda"]["Reallocated_Sector_Ct"], 0.0);
+
+        let deltas = deltas(&before, &after);
+        let media = deltas
+            .iter()
+            .find(|d| d.field == "media_errors")
+            .expect("media_errors changed");
+        assert!(media.flagged);
+        let realloc = deltas
+            .iter()
+            .find(|d| d.field == "Reallocated_Sector_Ct")
+            .expect("reallocations changed");
+        assert!(realloc.flagged);
+        // Usage counters change without raising a flag.
+        let read = deltas
+            .iter()
+            .find(|d| d.field == "data_units_read")
+            .expect("reads changed");
+        assert!(!read.flagged);
+    }
+}
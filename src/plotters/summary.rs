@@ -0,0 +1,262 @@
+//! Headline run statistics computed from the parsed data sources.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::common::readfile;
+use crate::plotters::sysstat::mpstat::MpstatColumn;
+use crate::plotters::{fio, procfs, read_mapping, sysstat};
+
+/// The few numbers most users want without opening the HTML pages.
+/// Sources that were not captured stay `None`.
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub cpu_busy_avg_pct: Option<f64>,
+    pub cpu_busy_peak_pct: Option<f64>,
+    pub iops_avg: Option<f64>,
+    pub iops_max: Option<f64>,
+    pub disk_mibps_avg: Option<f64>,
+    pub disk_mibps_max: Option<f64>,
+    pub mem_free_min_gib: Option<f64>,
+    pub net_peak_mbps: Option<f64>,
+    pub fio_iops: Option<f64>,
+    pub fio_mibps: Option<f64>,
+    pub fio_clat_p99_ms: Option<f64>,
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn peak(values: &[f64]) -> f64 {
+    values.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+}
+
+/// The measurement window delimited by the run marks: `load_start` to
+/// `load_end` when both are recorded, otherwise the span from the first
+/// mark to the last. Fewer than two marks delimit nothing.
+pub fn mark_window(marks: &[(String, NaiveDateTime)]) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    let named = |name: &str| marks.iter().find(|(n, _)| n == name).map(|(_, time)| *time);
+    if let (Some(start), Some(end)) = (named("load_start"), named("load_end")) {
+        return Some((start, end));
+    }
+    let start = marks.iter().map(|(_, time)| time).min()?;
+    let end = marks.iter().map(|(_, time)| time).max()?;
+    (start < end).then_some((*start, *end))
+}
+
+/// Keep the samples whose timestamps fall inside the window. A window
+/// that excludes every sample belongs to another part of the run than
+/// this capture and is ignored.
+fn clip(
+    times: &[NaiveDateTime],
+    values: Vec<f64>,
+    window: Option<(NaiveDateTime, NaiveDateTime)>,
+) -> Vec<f64> {
+    let Some((start, end)) = window else {
+        return values;
+    };
+    let clipped: Vec<f64> = times
+        .iter()
+        .zip(&values)
+        .filter(|(time, _)| (start..=end).contains(*time))
+        .map(|(_, value)| *value)
+        .collect();
+    if clipped.is_empty() {
+        values
+    } else {
+        clipped
+    }
+}
+
+/// Compute the summary for one agent output directory. With a mark
+/// window only the samples inside it feed the aggregations, keeping
+/// ramp-up and teardown noise out of the averages.
+pub fn compute(dir: &Path, window: Option<(NaiveDateTime, NaiveDateTime)>) -> io::Result<Summary> {
+    let mut summary = Summary::default();
+
+    for (id, name) in read_mapping(dir)? {
+        match name.as_str() {
+            "mpstat" => {
+                let log = BufReader::new(File::open(dir.join(format!("{id}-out.log")))?);
+                let stat = sysstat::mpstat::parse_reader(log).map_err(io::Error::other)?;
+                // Idle is only keyed once a per-CPU row was parsed; a
+                // banner-only capture carries no keys at all.
+                let Some(idle) = stat.data.get(&MpstatColumn::Idle) else {
+                    continue;
+                };
+                if idle.is_empty() || idle[0].is_empty() {
+                    continue;
+                }
+                // Per-sample busy averaged across CPUs.
+                let samples = idle[0].len();
+                let busy: Vec<f64> = (0..samples)
+                    .map(|s| {
+                        let total: f64 = idle.iter().map(|cpu| 100.0 - cpu[s]).sum();
+                        total / idle.len() as f64
+                    })
+                    .collect();
+                let busy = clip(&stat.times, busy, window);
+                summary.cpu_busy_avg_pct = Some(mean(&busy));
+                summary.cpu_busy_peak_pct = Some(peak(&busy));
+            }
+            "iostat" => {
+                let log = BufReader::new(File::open(dir.join(format!("{id}-out.log")))?);
+                let stat = sysstat::iostat::parse_reader(log).map_err(io::Error::other)?;
+                let samples = stat
+                    .devices
+                    .values()
+                    .map(|d| d.rps.len())
+                    .min()
+                    .unwrap_or(0);
+                if samples == 0 {
+                    continue;
+                }
+                let iops: Vec<f64> = (0..samples)
+                    .map(|s| stat.devices.values().map(|d| d.rps[s] + d.wps[s]).sum())
+                    .collect();
+                let mibps: Vec<f64> = (0..samples)
+                    .map(|s| {
+                        stat.devices
+                            .values()
+                            .map(|d| (d.rkbps[s] + d.wkbps[s]) / 1024.0)
+                            .sum()
+                    })
+                    .collect();
+                let iops = clip(&stat.times, iops, window);
+                let mibps = clip(&stat.times, mibps, window);
+                summary.iops_avg = Some(mean(&iops));
+                summary.iops_max = Some(peak(&iops));
+                summary.disk_mibps_avg = Some(mean(&mibps));
+                summary.disk_mibps_max = Some(peak(&mibps));
+            }
+            "meminfo" => {
+                let log = BufReader::new(File::open(dir.join(format!("{id}-poll.log")))?);
+                let stat = procfs::parse_meminfo_reader(log).map_err(io::Error::other)?;
+                if let Some(free) = stat.fields.get("MemFree") {
+                    if !free.is_empty() {
+                        let free = clip(&stat.times, free.clone(), window);
+                        let min = free.iter().copied().fold(f64::INFINITY, f64::min);
+                        summary.mem_free_min_gib = Some(min / (1024.0 * 1024.0));
+                    }
+                }
+            }
+            "netdev" => {
+                let log = BufReader::new(File::open(dir.join(format!("{id}-poll.log")))?);
+                let stat = procfs::parse_net_dev_reader(log).map_err(io::Error::other)?;
+                let mut peak_mbps: f64 = 0.0;
+                for iface in stat.ifaces.values() {
+                    for i in 1..stat.times.len().min(iface.rx_bytes.len()) {
+                        if let Some((start, end)) = window {
+                            if !(start..=end).contains(&stat.times[i]) {
+                                continue;
+                            }
+                        }
+                        let dt =
+                            (stat.times[i] - stat.times[i - 1]).num_milliseconds() as f64 / 1000.0;
+                        if dt <= 0.0 {
+                            continue;
+                        }
+                        let bytes = (iface.rx_bytes[i] - iface.rx_bytes[i - 1])
+                            + (iface.tx_bytes[i] - iface.tx_bytes[i - 1]);
+                        peak_mbps = peak_mbps.max(bytes / dt * 8e-6);
+                    }
+                }
+                if peak_mbps > 0.0 {
+                    summary.net_peak_mbps = Some(peak_mbps);
+                }
+            }
+            "fio" => {
+                let Ok(text) = readfile(&dir.join("fio.json")) else {
+                    continue;
+                };
+                let jobs = fio::parse_json(&text).map_err(io::Error::other)?;
+                if jobs.is_empty() {
+                    continue;
+                }
+                let dirs = jobs.iter().flat_map(|j| [&j.read, &j.write]);
+                summary.fio_iops = Some(dirs.clone().map(|d| d.iops).sum());
+                summary.fio_mibps = Some(dirs.clone().map(|d| d.bw_mibps).sum());
+                summary.fio_clat_p99_ms = dirs
+                    .filter_map(|d| d.clat_p99_ms)
+                    .fold(None, |acc, v| Some(acc.map_or(v, |a: f64| a.max(v))));
+            }
+            _ => {}
+        }
+    }
+    Ok(summary)
+}
+
+/// Write `summary.json` and `summary.md` into the agent directory.
+pub fn write(summary: &Summary, dir: &Path) -> io::Result<()> {
+    fs::write(
+        dir.join("summary.json"),
+        serde_json::to_string_pretty(summary).expect("serializable"),
+    )?;
+
+    let mut md = String::from("# Run summary\n\n| metric | value |\n| --- | --- |\n");
+    let mut row = |name: &str, value: Option<f64>, unit: &str| {
+        if let Some(value) = value {
+            md.push_str(&format!("| {name} | {value:.2} {unit} |\n"));
+        }
+    };
+    row("CPU busy avg", summary.cpu_busy_avg_pct, "%");
+    row("CPU busy peak", summary.cpu_busy_peak_pct, "%");
+    row("IOPS avg", summary.iops_avg, "");
+    row("IOPS max", summary.iops_max, "");
+    row("Disk throughput avg", summary.disk_mibps_avg, "MiB/s");
+    row("Disk throughput max", summary.disk_mibps_max, "MiB/s");
+    row("Min free memory", summary.mem_free_min_gib, "GiB");
+    row("Network peak", summary.net_peak_mbps, "Mbit/s");
+    row("fio IOPS", summary.fio_iops, "");
+    row("fio throughput", summary.fio_mibps, "MiB/s");
+    row("fio clat p99", summary.fio_clat_p99_ms, "ms");
+    fs::write(dir.join("summary.md"), md)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn mark_window_prefers_the_named_pair() {
+        let marks = vec![
+            ("setup_done".to_string(), t("2026-08-26 10:00:00")),
+            ("load_start".to_string(), t("2026-08-26 10:01:00")),
+            ("load_end".to_string(), t("2026-08-26 10:05:00")),
+        ];
+        assert_eq!(
+            mark_window(&marks),
+            Some((t("2026-08-26 10:01:00"), t("2026-08-26 10:05:00")))
+        );
+
+        let unnamed = vec![
+            ("a".to_string(), t("2026-08-26 10:00:00")),
+            ("b".to_string(), t("2026-08-26 10:09:00")),
+        ];
+        assert_eq!(
+            mark_window(&unnamed),
+            Some((t("2026-08-26 10:00:00"), t("2026-08-26 10:09:00")))
+        );
+        assert_eq!(mark_window(&unnamed[..1]), None);
+    }
+
+    #[test]
+    fn clip_keeps_only_window_samples() {
+        let times: Vec<NaiveDateTime> =
+            (0..4).map(|m| t(&format!("2026-08-26 10:0{m}:00"))).collect();
+        let window = Some((t("2026-08-26 10:01:00"), t("2026-08-26 10:02:00")));
+        assert_eq!(clip(&times, vec![1.0, 2.0, 3.0, 4.0], window), [2.0, 3.0]);
+        // A window that misses the capture entirely is ignored.
+        let off = Some((t("2026-08-27 10:00:00"), t("2026-08-27 11:00:00")));
+        assert_eq!(clip(&times, vec![1.0, 2.0, 3.0, 4.0], off), [1.0, 2.0, 3.0, 4.0]);
+    }
+}
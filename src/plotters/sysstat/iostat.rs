@@ -0,0 +1,308 @@
+//! `iostat -x -t` output parsing and per-device plotting.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "plotter")]
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDateTime};
+
+#[cfg(feature = "plotter")]
+use crate::plot::{self, Page, Scatter};
+
+/// Time series of one block device. The latency and discard series stay
+/// empty when the sysstat version does not print those columns.
+#[derive(Debug, Default)]
+pub struct DeviceStats {
+    pub rps: Vec<f64>,
+    pub wps: Vec<f64>,
+    pub rkbps: Vec<f64>,
+    pub wkbps: Vec<f64>,
+    pub util: Vec<f64>,
+    pub r_await: Vec<f64>,
+    pub w_await: Vec<f64>,
+    pub dps: Vec<f64>,
+}
+
+/// Parsed iostat capture.
+#[derive(Debug, Default)]
+pub struct Iostat {
+    pub times: Vec<NaiveDateTime>,
+    pub devices: BTreeMap<String, DeviceStats>,
+    /// Malformed lines skipped in tolerant mode.
+    pub dropped_chunks: usize,
+}
+
+/// Columns every supported sysstat version prints.
+const REQUIRED: [&str; 5] = ["r/s", "w/s", "rkB/s", "wkB/s", "%util"];
+/// Columns that appeared in later sysstat versions.
+const OPTIONAL: [&str; 3] = ["r_await", "w_await", "d/s"];
+
+/// Parse an iostat `-t` timestamp line. The format depends on the agent
+/// locale and `S_TIME_FORMAT`; all styles seen in the wild are tried.
+fn parse_timestamp(token: &str) -> Option<NaiveDateTime> {
+    const FORMATS: [&str; 6] = [
+        "%m/%d/%Y %I:%M:%S %p",
+        "%m/%d/%y %I:%M:%S %p",
+        "%Y-%m-%d %H:%M:%S",
+        "%m/%d/%Y %H:%M:%S",
+        "%d/%m/%Y %H:%M:%S",
+        "%d.%m.%Y %H:%M:%S",
+    ];
+    for format in FORMATS {
+        if let Ok(time) = NaiveDateTime::parse_from_str(token, format) {
+            return Some(time);
+        }
+    }
+    // `S_TIME_FORMAT=ISO` prints ISO 8601 with a timezone offset.
+    DateTime::parse_from_str(token, "%Y-%m-%dT%H:%M:%S%z")
+        .ok()
+        .map(|time| time.naive_local())
+}
+
+#[derive(Debug)]
+struct Columns {
+    required: Vec<usize>,
+    optional: Vec<Option<usize>>,
+}
+
+impl Columns {
+    /// Locate the captured columns in a Device header line. The layout
+    /// varies across sysstat versions, so the positions are recomputed
+    /// from every header rather than hard-coded.
+    fn from_header(header: &str) -> Result<Columns, String> {
+        let tokens: Vec<&str> = header.split_whitespace().collect();
+        let find = |name: &str| tokens.iter().position(|t| *t == name);
+        let required = REQUIRED
+            .iter()
+            .map(|name| find(name).ok_or_else(|| format!("no {name} column in iostat header")))
+            .collect::<Result<_, _>>()?;
+        let optional = OPTIONAL.iter().map(|name| find(name)).collect();
+        Ok(Columns { required, optional })
+    }
+}
+
+/// Parse raw `iostat -x -t -y <interval>` output.
+pub fn parse(text: &str) -> Result<Iostat, String> {
+    parse_reader(text.as_bytes())
+}
+
+/// Parse `iostat -x -t -y <interval>` output incrementally from a reader,
+/// holding only one line in memory at a time.
+pub fn parse_reader<R: std::io::BufRead>(reader: R) -> Result<Iostat, String> {
+    let mut stat = Iostat::default();
+    let mut columns: Option<Columns> = None;
+    let mut in_devices = false;
+
+    for line in reader.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            in_devices = false;
+            continue;
+        }
+
+        if let Some(time) = parse_timestamp(line.trim()) {
+            stat.times.push(time);
+            continue;
+        }
+
+        if line.starts_with("Device") {
+            match Columns::from_header(line) {
+                Ok(parsed) => {
+                    columns = Some(parsed);
+                    in_devices = true;
+                }
+                // A broken header drops the whole section: without the
+                // column positions its lines cannot be trusted.
+                Err(error) if crate::plotters::strict() => return Err(error),
+                Err(_) => {
+                    in_devices = false;
+                    stat.dropped_chunks += 1;
+                }
+            }
+            continue;
+        }
+
+        if !in_devices {
+            continue; // banner or avg-cpu block
+        }
+
+        let columns = columns.as_ref().expect("set when entering devices");
+        if let Err(error) = parse_device_line(line, columns, &mut stat.devices) {
+            if crate::plotters::strict() {
+                return Err(error);
+            }
+            stat.dropped_chunks += 1;
+        }
+    }
+    Ok(stat)
+}
+
+/// Fold one device line into the capture; a failure commits nothing.
+fn parse_device_line(
+    line: &str,
+    columns: &Columns,
+    devices: &mut BTreeMap<String, DeviceStats>,
+) -> Result<(), String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let value_at = |pos: usize| -> Result<f64, String> {
+        tokens
+            .get(pos)
+            .ok_or_else(|| format!("short iostat line: {line}"))?
+            .parse()
+            .map_err(|e| format!("bad iostat value in '{line}': {e}"))
+    };
+
+    let mut values = Vec::new();
+    for pos in &columns.required {
+        values.push(value_at(*pos)?);
+    }
+    let mut optional = Vec::new();
+    for pos in &columns.optional {
+        optional.push(match pos {
+            Some(pos) => Some(value_at(*pos)?),
+            None => None,
+        });
+    }
+
+    let device = devices.entry(tokens[0].to_string()).or_default();
+    device.rps.push(values[0]);
+    device.wps.push(values[1]);
+    device.rkbps.push(values[2]);
+    device.wkbps.push(values[3]);
+    device.util.push(values[4]);
+    for (value, series) in optional.into_iter().zip([
+        &mut device.r_await,
+        &mut device.w_await,
+        &mut device.dps,
+    ]) {
+        if let Some(value) = value {
+            series.push(value);
+        }
+    }
+    Ok(())
+}
+
+/// Render IOPS, throughput and utilization plots into `iostat.html`.
+#[cfg(feature = "plotter")]
+pub fn plot(
+    stat: &Iostat,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+) -> std::io::Result<()> {
+    let x: Vec<String> = stat.times.iter().map(plot::plotly_time).collect();
+
+    let mut iops = Vec::new();
+    let mut throughput = Vec::new();
+    let mut latency = Vec::new();
+    let mut util = Vec::new();
+    for (name, device) in &stat.devices {
+        if !crate::plotters::filter::device_shown(name) {
+            continue;
+        }
+        iops.push(series(&x, &format!("{name} r/s"), &device.rps, 1.0));
+        iops.push(series(&x, &format!("{name} w/s"), &device.wps, 1.0));
+        if !device.dps.is_empty() {
+            iops.push(series(&x, &format!("{name} d/s"), &device.dps, 1.0));
+        }
+        throughput.push(series(&x, &format!("{name} read"), &device.rkbps, 1.0 / 1024.0));
+        throughput.push(series(&x, &format!("{name} write"), &device.wkbps, 1.0 / 1024.0));
+        if !device.r_await.is_empty() {
+            latency.push(series(&x, &format!("{name} r_await"), &device.r_await, 1.0));
+        }
+        if !device.w_await.is_empty() {
+            latency.push(series(&x, &format!("{name} w_await"), &device.w_await, 1.0));
+        }
+        util.push(series(&x, name, &device.util, 1.0));
+    }
+
+    let mut page = Page::new("iostat");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    page.add_plot("IOPS", iops);
+    page.add_plot("Throughput, MiB/s", throughput);
+    if !latency.is_empty() {
+        page.add_plot("Latency, ms", latency);
+    }
+    page.add_plot("Utilization, %", util);
+    page.write(&outdir.join("iostat.html"))
+}
+
+#[cfg(feature = "plotter")]
+fn series(x: &[String], name: &str, values: &[f64], scale: f64) -> serde_json::Value {
+    let mut trace = Scatter::new(name);
+    for (time, value) in x.iter().zip(values) {
+        trace.push(time.clone(), value * scale);
+    }
+    trace.to_trace()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+Linux 6.5.0 (host) \t08/26/2026 \t_x86_64_\t(2 CPU)
+
+08/26/2026 05:04:01 PM
+avg-cpu:  %user   %nice %system %iowait  %steal   %idle
+           0.50    0.00    0.25    0.00    0.00   99.25
+
+Device            r/s     w/s     rkB/s     wkB/s   rrqm/s   wrqm/s  %rrqm  %wrqm r_await w_await aqu-sz rareq-sz wareq-sz  svctm  %util
+nvme0n1          1.00    3.00      4.00     24.00     0.00     1.00   0.00  25.00    0.00    0.67   0.00     0.00     8.00   0.33   0.10
+
+08/26/2026 05:04:02 PM
+avg-cpu:  %user   %nice %system %iowait  %steal   %idle
+           0.50    0.00    0.25    0.00    0.00   99.25
+
+Device            r/s     w/s     rkB/s     wkB/s   rrqm/s   wrqm/s  %rrqm  %wrqm r_await w_await aqu-sz rareq-sz wareq-sz  svctm  %util
+nvme0n1        100.00    3.00    400.00     24.00     0.00     1.00   0.00  25.00    0.00    0.67   0.00     0.00     8.00   0.33  95.00
+";
+
+    #[test]
+    fn sample_capture_parses() {
+        let stat = parse(SAMPLE).unwrap();
+        assert_eq!(stat.times.len(), 2);
+        let device = &stat.devices["nvme0n1"];
+        assert_eq!(device.rps, [1.0, 100.0]);
+        assert_eq!(device.util, [0.1, 95.0]);
+        assert_eq!(device.w_await, [0.67, 0.67]);
+        assert!(device.dps.is_empty());
+    }
+
+    const SAMPLE_DISCARD: &str = "\
+Linux 6.5.0 (host) \t08/26/2026 \t_x86_64_\t(2 CPU)
+
+08/26/2026 05:04:01 PM
+Device            r/s     rkB/s   r_await  rareq-sz     w/s     wkB/s   w_await  wareq-sz     d/s     dkB/s   d_await  dareq-sz  aqu-sz  %util
+sda              1.00      4.00      0.10      4.00    3.00     24.00      0.67      8.00    2.00    128.00      0.05     64.00    0.00   0.10
+";
+
+    #[test]
+    fn discard_layout_parses() {
+        // Newer sysstat groups the columns per direction and adds
+        // discards; the parser must not assume fixed positions.
+        let stat = parse(SAMPLE_DISCARD).unwrap();
+        let device = &stat.devices["sda"];
+        assert_eq!(device.rps, [1.0]);
+        assert_eq!(device.wkbps, [24.0]);
+        assert_eq!(device.r_await, [0.1]);
+        assert_eq!(device.dps, [2.0]);
+    }
+
+    #[test]
+    fn timestamp_formats() {
+        let expected = "2026-08-26 17:04:01";
+        for stamp in [
+            "08/26/2026 05:04:01 PM",
+            "2026-08-26 17:04:01",
+            "26/08/2026 17:04:01",
+            "26.08.2026 17:04:01",
+            "2026-08-26T17:04:01+0300",
+        ] {
+            let time = parse_timestamp(stamp).unwrap_or_else(|| panic!("unparsed: {stamp}"));
+            assert_eq!(time.format("%Y-%m-%d %H:%M:%S").to_string(), expected);
+        }
+        assert!(parse_timestamp("avg-cpu:  %user").is_none());
+    }
+}
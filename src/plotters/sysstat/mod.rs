@@ -0,0 +1,5 @@
+//! Parsers and plotters for sysstat tools output.
+
+pub mod iostat;
+pub mod mpstat;
+pub mod pidstat;
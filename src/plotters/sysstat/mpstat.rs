@@ -0,0 +1,437 @@
+//! `mpstat -P ALL` output parsing and per-CPU heatmap plotting.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "plotter")]
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+#[cfg(feature = "plotter")]
+use crate::plot::{self, HeatMap, Page, Scatter};
+
+/// The mpstat columns we capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MpstatColumn {
+    Usr,
+    Sys,
+    Iowait,
+    Irq,
+    Soft,
+    Steal,
+    Guest,
+    Gnice,
+    Idle,
+}
+
+impl MpstatColumn {
+    pub const ALL: [MpstatColumn; 9] = [
+        MpstatColumn::Usr,
+        MpstatColumn::Sys,
+        MpstatColumn::Iowait,
+        MpstatColumn::Irq,
+        MpstatColumn::Soft,
+        MpstatColumn::Steal,
+        MpstatColumn::Guest,
+        MpstatColumn::Gnice,
+        MpstatColumn::Idle,
+    ];
+
+    /// The column title as printed by mpstat.
+    pub fn header(&self) -> &'static str {
+        match self {
+            MpstatColumn::Usr => "%usr",
+            MpstatColumn::Sys => "%sys",
+            MpstatColumn::Iowait => "%iowait",
+            MpstatColumn::Irq => "%irq",
+            MpstatColumn::Soft => "%soft",
+            MpstatColumn::Steal => "%steal",
+            MpstatColumn::Guest => "%guest",
+            MpstatColumn::Gnice => "%gnice",
+            MpstatColumn::Idle => "%idle",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            MpstatColumn::Usr => "usr",
+            MpstatColumn::Sys => "sys",
+            MpstatColumn::Iowait => "iowait",
+            MpstatColumn::Irq => "irq",
+            MpstatColumn::Soft => "soft",
+            MpstatColumn::Steal => "steal",
+            MpstatColumn::Guest => "guest",
+            MpstatColumn::Gnice => "gnice",
+            MpstatColumn::Idle => "idle",
+        }
+    }
+}
+
+/// Parsed mpstat capture: `data[column][cpu][sample]`.
+#[derive(Debug, Default)]
+pub struct Mpstat {
+    pub times: Vec<NaiveDateTime>,
+    pub cpus: Vec<String>,
+    pub data: BTreeMap<MpstatColumn, Vec<Vec<f64>>>,
+    /// The machine-wide "all" row: `all[column][sample]`.
+    pub all: BTreeMap<MpstatColumn, Vec<f64>>,
+    /// Malformed chunks skipped in tolerant mode.
+    pub dropped_chunks: usize,
+}
+
+fn parse_time(token: &str, next: Option<&str>) -> Option<NaiveTime> {
+    // mpstat prints either 24-hour times or 12-hour ones followed by
+    // AM/PM, depending on the locale.
+    if let Some(meridian) = next.filter(|t| *t == "AM" || *t == "PM") {
+        let stamp = format!("{token} {meridian}");
+        return NaiveTime::parse_from_str(&stamp, "%I:%M:%S %p").ok();
+    }
+    NaiveTime::parse_from_str(token, "%H:%M:%S").ok()
+}
+
+/// Parse raw `mpstat -P ALL <interval>` output.
+pub fn parse(text: &str) -> Result<Mpstat, String> {
+    parse_reader(text.as_bytes())
+}
+
+/// Parse `mpstat -P ALL <interval>` output incrementally from a reader,
+/// holding only one sample chunk in memory at a time.
+pub fn parse_reader<R: std::io::BufRead>(reader: R) -> Result<Mpstat, String> {
+    let mut chunks = crate::plotters::Chunks::new(reader);
+
+    // The first chunk is the sysstat banner carrying the date. The banner
+    // only gives the starting day: runs crossing midnight are handled by
+    // bumping the day whenever the clock time jumps backwards.
+    let banner = chunks
+        .next()
+        .ok_or("empty mpstat file")?
+        .map_err(|e| e.to_string())?;
+    let mut day = banner_date(&banner).ok_or("no date in mpstat banner")?;
+
+    let mut stat = Mpstat::default();
+    for chunk in chunks {
+        // I/O errors stay fatal; a malformed chunk (e.g. truncated by a
+        // killed mpstat) is skipped and counted unless in strict mode.
+        if let Err(error) = parse_chunk(&chunk.map_err(|e| e.to_string())?, &mut stat, &mut day) {
+            if crate::plotters::strict() {
+                return Err(error);
+            }
+            stat.dropped_chunks += 1;
+        }
+    }
+    Ok(stat)
+}
+
+/// Fold one sample chunk into the capture. A failure leaves the capture
+/// untouched: the chunk is fully validated before anything is committed.
+fn parse_chunk(chunk: &str, stat: &mut Mpstat, day: &mut NaiveDate) -> Result<(), String> {
+    let mut lines = chunk.lines().filter(|l| !l.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return Ok(());
+    };
+    // Skip the "Average:" trailer and anything unexpected.
+    if !header.contains("CPU") || !header.contains("%usr") {
+        return Ok(());
+    }
+    let columns: Vec<&str> = header.split_whitespace().collect();
+    let cpu_pos = columns
+        .iter()
+        .position(|t| *t == "CPU")
+        .ok_or("no CPU column")?;
+
+    let mut chunk_time = None;
+    let mut rows: Vec<(&str, Vec<f64>)> = Vec::new();
+    let mut all_row: Option<Vec<f64>> = None;
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() != columns.len() {
+            return Err(format!("malformed mpstat line: {line}"));
+        }
+        let cpu = tokens[cpu_pos];
+        if chunk_time.is_none() {
+            chunk_time = Some(
+                parse_time(tokens[0], tokens.get(1).copied())
+                    .ok_or_else(|| format!("bad mpstat time '{}'", tokens[0]))?,
+            );
+        }
+
+        let mut values = Vec::with_capacity(MpstatColumn::ALL.len());
+        for column in MpstatColumn::ALL {
+            let pos = columns
+                .iter()
+                .position(|t| *t == column.header())
+                .ok_or_else(|| format!("no {} column", column.header()))?;
+            values.push(
+                tokens[pos]
+                    .parse()
+                    .map_err(|e| format!("bad mpstat value '{}': {e}", tokens[pos]))?,
+            );
+        }
+        if cpu == "all" {
+            all_row = Some(values);
+        } else {
+            rows.push((cpu, values));
+        }
+    }
+
+    let Some(time) = chunk_time else {
+        return Ok(());
+    };
+    let mut stamp = NaiveDateTime::new(*day, time);
+    if stat.times.last().is_some_and(|prev| stamp < *prev) {
+        *day = day.succ_opt().ok_or("mpstat date overflow")?;
+        stamp = NaiveDateTime::new(*day, time);
+    }
+    stat.times.push(stamp);
+    if let Some(values) = all_row {
+        for (column, value) in MpstatColumn::ALL.into_iter().zip(values) {
+            stat.all.entry(column).or_default().push(value);
+        }
+    }
+    for (cpu, values) in rows {
+        let row = cpu_index(stat, cpu);
+        for (column, value) in MpstatColumn::ALL.into_iter().zip(values) {
+            stat.data.get_mut(&column).unwrap()[row].push(value);
+        }
+    }
+    Ok(())
+}
+
+fn banner_date(banner: &str) -> Option<NaiveDate> {
+    for token in banner.split_whitespace() {
+        for format in ["%m/%d/%Y", "%m/%d/%y", "%Y-%m-%d"] {
+            if let Ok(date) = NaiveDate::parse_from_str(token, format) {
+                return Some(date);
+            }
+        }
+    }
+    None
+}
+
+fn cpu_index(stat: &mut Mpstat, cpu: &str) -> usize {
+    if let Some(pos) = stat.cpus.iter().position(|c| c == cpu) {
+        return pos;
+    }
+    stat.cpus.push(cpu.to_string());
+    for column in MpstatColumn::ALL {
+        stat.data.entry(column).or_default().push(Vec::new());
+    }
+    stat.cpus.len() - 1
+}
+
+/// Derive the heatmap matrices to plot: overall busy plus every captured
+/// column.
+#[cfg(feature = "plotter")]
+fn process_chunks(stat: &Mpstat) -> Vec<(String, Vec<Vec<f64>>)> {
+    let busy: Vec<Vec<f64>> = stat.data[&MpstatColumn::Idle]
+        .iter()
+        .map(|row| row.iter().map(|idle| 100.0 - idle).collect())
+        .collect();
+
+    let mut matrices = vec![("busy".to_string(), busy)];
+    for column in MpstatColumn::ALL {
+        if column == MpstatColumn::Idle {
+            continue;
+        }
+        matrices.push((column.title().to_string(), stat.data[&column].clone()));
+    }
+    matrices
+}
+
+/// Colorbar scaling of the percentage heatmaps.
+#[cfg(feature = "plotter")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeatScale {
+    /// Pin the colorbar to 0–100% so mostly idle runs do not look
+    /// alarming.
+    #[default]
+    Fixed,
+    /// Let plotly scale the colorbar to the data.
+    Auto,
+}
+
+#[cfg(feature = "plotter")]
+impl std::str::FromStr for HeatScale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed" => Ok(HeatScale::Fixed),
+            "auto" => Ok(HeatScale::Auto),
+            other => Err(format!("unknown heatmap scale '{other}'")),
+        }
+    }
+}
+
+/// CPUs additionally rendered as per-CPU usage line charts, empty
+/// meaning heatmaps only.
+#[cfg(feature = "plotter")]
+static CPU_LINES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+/// Also render a usage line chart for each of the given CPUs (mpstat row
+/// labels, e.g. "0") below the heatmap, where individual cores can be
+/// read off more precisely than from a color scale.
+#[cfg(feature = "plotter")]
+pub fn set_cpu_lines(cpus: Vec<String>) {
+    *CPU_LINES.lock().unwrap() = cpus;
+}
+
+/// Render `mpstat.html` in `outdir`: the aggregate usage chart, then the
+/// per-CPU heatmap panel with a metric selector switching between busy
+/// and the captured columns, plus optional line charts for chosen CPUs.
+#[cfg(feature = "plotter")]
+pub fn plot(
+    stat: &Mpstat,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+    scale: HeatScale,
+) -> std::io::Result<()> {
+    // mpstat killed before its first interval leaves a banner-only log:
+    // it parses fine but carries nothing to draw.
+    if stat.times.is_empty() || stat.data.is_empty() {
+        return Ok(());
+    }
+    let x: Vec<String> = stat.times.iter().map(plot::plotly_time).collect();
+
+    let mut page = Page::new("mpstat");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+
+    // The one-line summary first: machine-wide usage from the "all" row
+    // with the non-idle columns stacked, so the total busy share reads
+    // off the top edge before drilling into per-CPU detail.
+    if !stat.all.is_empty() {
+        let traces = MpstatColumn::ALL
+            .into_iter()
+            .filter(|column| *column != MpstatColumn::Idle)
+            .map(|column| {
+                let mut line = Scatter::new(column.title());
+                for (time, value) in x.iter().zip(&stat.all[&column]) {
+                    line.push(time.clone(), *value);
+                }
+                let mut trace = line.to_trace();
+                trace["stackgroup"] = serde_json::json!("cpu");
+                trace
+            })
+            .collect();
+        page.add_plot("CPU usage % (all CPUs)", traces);
+    }
+
+    let matrices = process_chunks(stat);
+    let options = matrices
+        .iter()
+        .map(|(title, z)| {
+            let mut map = HeatMap::new(x.clone(), stat.cpus.clone(), z.clone());
+            if scale == HeatScale::Fixed {
+                map.set_zrange(0.0, 100.0);
+            }
+            (title.clone(), vec![map.to_trace()])
+        })
+        .collect();
+    page.add_selector_plot("CPU usage % per CPU", options);
+
+    for cpu in CPU_LINES.lock().unwrap().iter() {
+        let Some(row) = stat.cpus.iter().position(|c| c == cpu) else {
+            eprintln!("plotter: mpstat: no CPU '{cpu}' in capture");
+            continue;
+        };
+        let traces = matrices
+            .iter()
+            .map(|(title, z)| {
+                let mut line = Scatter::new(title);
+                for (time, value) in x.iter().zip(&z[row]) {
+                    line.push(time.clone(), *value);
+                }
+                line.to_trace()
+            })
+            .collect();
+        page.add_plot(&format!("CPU {cpu} usage %"), traces);
+    }
+    page.write(&outdir.join("mpstat.html"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+Linux 6.5.0 (host) \t08/26/2026 \t_x86_64_\t(2 CPU)
+
+17:04:02     CPU    %usr   %nice    %sys %iowait    %irq   %soft  %steal  %guest  %gnice   %idle
+17:04:02     all    1.00    0.00    0.50    0.00    0.00    0.00    0.00    0.00    0.00   98.50
+17:04:02       0    2.00    0.00    1.00    0.00    0.00    0.00    0.00    0.00    0.00   97.00
+17:04:02       1    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00  100.00
+
+17:04:03     CPU    %usr   %nice    %sys %iowait    %irq   %soft  %steal  %guest  %gnice   %idle
+17:04:03     all   50.00    0.00    0.50    0.00    0.00    0.00    0.00    0.00    0.00   49.50
+17:04:03       0   99.00    0.00    1.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00
+17:04:03       1    1.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00   99.00
+";
+
+    #[test]
+    fn sample_capture_parses() {
+        let stat = parse(SAMPLE).unwrap();
+        assert_eq!(stat.cpus, ["0", "1"]);
+        assert_eq!(stat.times.len(), 2);
+        assert_eq!(stat.times[0].format("%H:%M:%S").to_string(), "17:04:02");
+        assert_eq!(stat.data[&MpstatColumn::Usr][0], [2.0, 99.0]);
+        assert_eq!(stat.data[&MpstatColumn::Idle][1], [100.0, 99.0]);
+        assert_eq!(stat.data[&MpstatColumn::Steal][0], [0.0, 0.0]);
+        assert_eq!(stat.data[&MpstatColumn::Guest][1], [0.0, 0.0]);
+        assert_eq!(stat.all[&MpstatColumn::Usr], [1.0, 50.0]);
+        assert_eq!(stat.all[&MpstatColumn::Idle], [98.50, 49.50]);
+    }
+
+    #[test]
+    fn truncated_chunk_is_dropped() {
+        // A chunk cut short by killing mpstat loses columns mid-line.
+        let truncated = format!(
+            "{SAMPLE}\n\
+             17:04:04     CPU    %usr   %nice    %sys %iowait    %irq   %soft  %steal  %guest  %gnice   %idle\n\
+             17:04:04       0    2.00    0.0"
+        );
+        let stat = parse(&truncated).unwrap();
+        assert_eq!(stat.times.len(), 2);
+        assert_eq!(stat.dropped_chunks, 1);
+        assert_eq!(stat.data[&MpstatColumn::Usr][0].len(), 2);
+    }
+
+    #[cfg(feature = "plotter")]
+    #[test]
+    fn banner_only_capture_plots_nothing() {
+        let stat = parse("Linux 6.5.0 (host) \t08/26/2026 \t_x86_64_\t(2 CPU)\n").unwrap();
+        assert!(stat.times.is_empty());
+        assert!(stat.data.is_empty());
+
+        let dir = std::env::temp_dir().join(format!("pmppt-mpstat-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        plot(&stat, &dir, &[], HeatScale::Fixed).unwrap();
+        assert!(!dir.join("mpstat.html").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    const SAMPLE_12H: &str = "\
+Linux 6.5.0 (host) \t08/26/2026 \t_x86_64_\t(1 CPU)
+
+11:59:59 PM  CPU    %usr   %nice    %sys %iowait    %irq   %soft  %steal  %guest  %gnice   %idle
+11:59:59 PM    0    2.00    0.00    1.00    0.00    0.00    0.00    0.00    0.00    0.00   97.00
+
+12:00:01 AM  CPU    %usr   %nice    %sys %iowait    %irq   %soft  %steal  %guest  %gnice   %idle
+12:00:01 AM    0    3.00    0.00    1.00    0.00    0.00    0.00    0.00    0.00    0.00   96.00
+";
+
+    #[test]
+    fn twelve_hour_clock_and_midnight_rollover() {
+        let stat = parse(SAMPLE_12H).unwrap();
+        assert_eq!(stat.times.len(), 2);
+        assert_eq!(
+            stat.times[0].format("%Y-%m-%d %H:%M:%S").to_string(),
+            "2026-08-26 23:59:59"
+        );
+        // The second sample is past midnight and must land on the next day.
+        assert_eq!(
+            stat.times[1].format("%Y-%m-%d %H:%M:%S").to_string(),
+            "2026-08-27 00:00:01"
+        );
+        assert!(stat.times[0] < stat.times[1]);
+    }
+}
@@ -0,0 +1,119 @@
+//! Combined multi-agent timeline.
+//!
+//! Renders the headline metrics of every agent of a run on one shared
+//! time axis so cause and effect across hosts line up visually. Agent
+//! timestamps are corrected by the clock offsets measured at pre-flight
+//! and recorded in the run storage.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::common::readfile;
+use crate::plot::{plotly_time, Page, Scatter};
+use crate::plotters::sysstat::mpstat::MpstatColumn;
+use crate::plotters::{read_mapping, sysstat};
+use crate::storage::{Key, Storage};
+
+/// Clock offsets per agent, from `storage.json` in the run directory.
+/// Agents without a recorded offset are assumed to be in sync.
+pub fn agent_offsets(run_dir: &Path, agents: &[String]) -> BTreeMap<String, i64> {
+    let Ok(storage) = Storage::load(&run_dir.join("storage.json")) else {
+        return BTreeMap::new();
+    };
+    agents
+        .iter()
+        .filter_map(|agent| {
+            let offset = storage
+                .get_as::<i64>(&Key::agent(agent, "clock_offset_ms"))
+                .ok()?;
+            Some((agent.clone(), offset))
+        })
+        .collect()
+}
+
+/// Shift an agent-local timestamp onto the controller clock.
+fn corrected(time: &NaiveDateTime, offset_ms: i64) -> String {
+    plotly_time(&(*time - Duration::milliseconds(offset_ms)))
+}
+
+/// Per-sample CPU busy percentage averaged over all CPUs.
+pub fn cpu_busy(stat: &sysstat::mpstat::Mpstat) -> Vec<f64> {
+    // A banner-only capture has no per-CPU rows, so Idle is not keyed.
+    let Some(idle) = stat.data.get(&MpstatColumn::Idle) else {
+        return Vec::new();
+    };
+    if idle.is_empty() {
+        return Vec::new();
+    }
+    (0..stat.times.len())
+        .map(|s| {
+            let total: f64 = idle.iter().filter_map(|cpu| cpu.get(s)).map(|v| 100.0 - v).sum();
+            total / idle.len() as f64
+        })
+        .collect()
+}
+
+/// Render `timeline.html` in the run directory from all unpacked agent
+/// subdirectories.
+pub fn plot(run_dir: &Path, marks: &[(String, NaiveDateTime)]) -> io::Result<()> {
+    let mut agents = Vec::new();
+    for entry in std::fs::read_dir(run_dir)? {
+        let entry = entry?;
+        if entry.path().join("out.map").exists() {
+            agents.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    agents.sort();
+    let offsets = agent_offsets(run_dir, &agents);
+
+    let mut cpu_traces = Vec::new();
+    let mut disk_traces = Vec::new();
+    for agent in &agents {
+        let dir = run_dir.join(agent);
+        let offset = offsets.get(agent).copied().unwrap_or(0);
+        for (id, name) in read_mapping(&dir)? {
+            match name.as_str() {
+                "mpstat" => {
+                    let text = readfile(&dir.join(format!("{id}-out.log")))?;
+                    let stat = sysstat::mpstat::parse(&text).map_err(io::Error::other)?;
+                    let mut trace = Scatter::new(&format!("{agent}: cpu busy"));
+                    for (time, busy) in stat.times.iter().zip(cpu_busy(&stat)) {
+                        trace.push(corrected(time, offset), busy);
+                    }
+                    cpu_traces.push(trace.to_trace());
+                }
+                "iostat" => {
+                    let text = readfile(&dir.join(format!("{id}-out.log")))?;
+                    let stat = sysstat::iostat::parse(&text).map_err(io::Error::other)?;
+                    for (device, stats) in &stat.devices {
+                        if !crate::plotters::filter::device_shown(device) {
+                            continue;
+                        }
+                        let mut trace = Scatter::new(&format!("{agent}: {device} util"));
+                        for (time, util) in stat.times.iter().zip(&stats.util) {
+                            trace.push(corrected(time, offset), *util);
+                        }
+                        disk_traces.push(trace.to_trace());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut page = Page::new("combined timeline");
+    page.set_marks(marks);
+    if !cpu_traces.is_empty() {
+        page.add_plot("CPU busy, %", cpu_traces);
+    }
+    if !disk_traces.is_empty() {
+        page.add_plot("Disk utilization, %", disk_traces);
+    }
+    if page.is_empty() {
+        return Err(io::Error::other("no mpstat/iostat data found in the run"));
+    }
+    page.write(&run_dir.join("timeline.html"))
+}
@@ -0,0 +1,109 @@
+//! Long-term performance trends across many runs.
+//!
+//! Scans a directory of collected run directories — the root a nightly
+//! benchmarking setup accumulates — computes the headline summary of
+//! every agent in every run and plots each metric across runs over time
+//! in `trend.html`, so slow regressions show up without diffing runs by
+//! hand.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::common::{millis_to_naive, readfile};
+use crate::plot::{self, Page, Scatter};
+use crate::plotters::{compare, read_marks, summary};
+
+/// One run eligible for the trend: its directory name, start time and
+/// the per-agent headline summaries.
+struct Run {
+    name: String,
+    start: NaiveDateTime,
+    summaries: Vec<(String, serde_json::Value)>,
+}
+
+/// The start of a run as recorded in its `run.json`: the first stage
+/// start, which exists for every run that got past pre-flight.
+fn run_start(run_dir: &Path) -> Option<NaiveDateTime> {
+    let text = readfile(&run_dir.join("run.json")).ok()?;
+    let meta: serde_json::Value = serde_json::from_str(&text).ok()?;
+    Some(millis_to_naive(meta["stages"][0]["start_ms"].as_u64()?))
+}
+
+/// Collect the summaries of every run directory under the root, sorted
+/// by start time. A run whose data cannot be summarized is skipped with
+/// a note: one broken nightly run must not take the dashboard down.
+fn scan(runs_root: &Path) -> io::Result<Vec<Run>> {
+    let mut runs = Vec::new();
+    for entry in std::fs::read_dir(runs_root)? {
+        let dir = entry?.path();
+        if !dir.is_dir() {
+            continue;
+        }
+        let Some(start) = run_start(&dir) else {
+            continue;
+        };
+        let name = dir
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| dir.display().to_string());
+        let mut summaries = Vec::new();
+        for (agent, agent_dir) in compare::agent_dirs(&dir)? {
+            let window = summary::mark_window(&read_marks(&agent_dir));
+            match summary::compute(&agent_dir, window) {
+                Ok(computed) => summaries
+                    .push((agent, serde_json::to_value(computed).expect("serializable"))),
+                Err(e) => eprintln!("plotter: trend: skipping {name}/{agent}: {e}"),
+            }
+        }
+        runs.push(Run {
+            name,
+            start,
+            summaries,
+        });
+    }
+    runs.sort_by_key(|run| run.start);
+    Ok(runs)
+}
+
+/// Render `trend.html` into the runs root: one plot per headline metric
+/// with a trace per agent, plus the list of covered runs.
+pub fn plot(runs_root: &Path) -> io::Result<()> {
+    let runs = scan(runs_root)?;
+    if runs.is_empty() {
+        return Err(io::Error::other("no run directories with run.json found"));
+    }
+
+    // metric -> agent -> trace across the runs.
+    let mut metrics: BTreeMap<String, BTreeMap<String, Scatter>> = BTreeMap::new();
+    for run in &runs {
+        let x = plot::plotly_time(&run.start);
+        for (agent, computed) in &run.summaries {
+            for (metric, value) in computed.as_object().expect("summary is an object") {
+                let Some(value) = value.as_f64() else {
+                    continue;
+                };
+                metrics
+                    .entry(metric.clone())
+                    .or_default()
+                    .entry(agent.clone())
+                    .or_insert_with(|| Scatter::new(agent))
+                    .push(x.clone(), value);
+            }
+        }
+    }
+
+    let mut page = Page::new("trend");
+    let rows = runs
+        .iter()
+        .map(|run| vec![run.name.clone(), run.start.format("%Y-%m-%d %H:%M:%S").to_string()])
+        .collect();
+    page.add_table("Runs", vec!["run".to_string(), "start".to_string()], rows);
+    for (metric, agents) in metrics {
+        let traces = agents.values().map(Scatter::to_trace).collect();
+        page.add_plot(&metric, traces);
+    }
+    page.write(&runs_root.join("trend.html"))
+}
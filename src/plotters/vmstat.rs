@@ -0,0 +1,142 @@
+//! `vmstat -t` output parsing and plotting.
+//!
+//! Covers the cheap scheduler/VM signals not present in the mpstat and
+//! meminfo sources: run queue length, blocked tasks, swap traffic and
+//! context switches.
+
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::plot::{self, Page, Scatter};
+
+/// Parsed `vmstat -t` capture.
+#[derive(Debug, Default)]
+pub struct Vmstat {
+    pub times: Vec<NaiveDateTime>,
+    /// Runnable tasks (`r`).
+    pub running: Vec<f64>,
+    /// Tasks in uninterruptible sleep (`b`).
+    pub blocked: Vec<f64>,
+    /// Swapped in/out, kB/s (`si`/`so`).
+    pub swap_in: Vec<f64>,
+    pub swap_out: Vec<f64>,
+    /// Interrupts and context switches per second (`in`/`cs`).
+    pub interrupts: Vec<f64>,
+    pub ctx_switches: Vec<f64>,
+}
+
+const CAPTURED: [&str; 6] = ["r", "b", "si", "so", "in", "cs"];
+
+/// Parse raw `vmstat -t -n <interval>` output.
+pub fn parse(text: &str) -> Result<Vmstat, String> {
+    let mut stat = Vmstat::default();
+    let mut positions: Option<Vec<usize>> = None;
+
+    for line in text.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() || line.starts_with("procs") {
+            continue;
+        }
+        if tokens[0] == "r" {
+            let mut found = Vec::new();
+            for name in CAPTURED {
+                let pos = tokens
+                    .iter()
+                    .position(|t| *t == name)
+                    .ok_or_else(|| format!("no {name} column in vmstat header"))?;
+                found.push(pos);
+            }
+            positions = Some(found);
+            continue;
+        }
+        let Some(positions) = positions.as_ref() else {
+            continue;
+        };
+
+        // With -t the timestamp is the trailing "date time" pair.
+        if tokens.len() < 2 {
+            continue;
+        }
+        let stamp = format!("{} {}", tokens[tokens.len() - 2], tokens[tokens.len() - 1]);
+        let Ok(time) = NaiveDateTime::parse_from_str(&stamp, "%Y-%m-%d %H:%M:%S") else {
+            continue;
+        };
+
+        let mut values = Vec::new();
+        for pos in positions {
+            let value: f64 = tokens
+                .get(*pos)
+                .ok_or_else(|| format!("short vmstat line: {line}"))?
+                .parse()
+                .map_err(|e| format!("bad vmstat value in '{line}': {e}"))?;
+            values.push(value);
+        }
+
+        stat.times.push(time);
+        stat.running.push(values[0]);
+        stat.blocked.push(values[1]);
+        stat.swap_in.push(values[2]);
+        stat.swap_out.push(values[3]);
+        stat.interrupts.push(values[4]);
+        stat.ctx_switches.push(values[5]);
+    }
+    Ok(stat)
+}
+
+/// Render the vmstat series into `vmstat.html`.
+pub fn plot(
+    stat: &Vmstat,
+    outdir: &Path,
+    marks: &[(String, NaiveDateTime)],
+) -> std::io::Result<()> {
+    let series = |name: &str, values: &[f64]| {
+        let mut trace = Scatter::new(name);
+        for (time, value) in stat.times.iter().zip(values) {
+            trace.push(plot::plotly_time(time), *value);
+        }
+        trace.to_trace()
+    };
+
+    let mut page = Page::new("vmstat");
+    page.set_marks(marks);
+    page.set_spans(&crate::plotters::read_journal(outdir));
+    page.add_plot(
+        "Tasks",
+        vec![series("running", &stat.running), series("blocked", &stat.blocked)],
+    );
+    page.add_plot(
+        "Swap, kB/s",
+        vec![series("in", &stat.swap_in), series("out", &stat.swap_out)],
+    );
+    page.add_plot(
+        "System events/s",
+        vec![
+            series("interrupts", &stat.interrupts),
+            series("context switches", &stat.ctx_switches),
+        ],
+    );
+    page.write(&outdir.join("vmstat.html"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+procs -----------memory---------- ---swap-- -----io---- -system-- ------cpu----- -----timestamp-----
+ r  b   swpd   free   buff  cache   si   so    bi    bo   in   cs us sy id wa st                 UTC
+ 2  0      0 819200 10000 500000    0    0     5    10  300  500  1  1 98  0  0 2026-08-26 17:04:02
+ 5  1      0 409600 10000 500000    8   16     5    10  400  900  9  1 90  0  0 2026-08-26 17:04:03
+";
+
+    #[test]
+    fn sample_capture_parses() {
+        let stat = parse(SAMPLE).unwrap();
+        assert_eq!(stat.times.len(), 2);
+        assert_eq!(stat.running, [2.0, 5.0]);
+        assert_eq!(stat.blocked, [0.0, 1.0]);
+        assert_eq!(stat.swap_out, [0.0, 16.0]);
+        assert_eq!(stat.ctx_switches, [500.0, 900.0]);
+    }
+}
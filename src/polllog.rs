@@ -0,0 +1,137 @@
+//! Compact binary format for agent poll logs.
+//!
+//! The default text poll log (`=== <millis>` / `--- <path>` sections) is
+//! easy to eyeball but wasteful at high frequencies: every sample repeats
+//! the section framing and the reader has to scan for marker lines. The
+//! binary format keeps the same model — a monotonic timestamp plus one
+//! section per polled file — in length-prefixed frames, so logs shrink
+//! and the reader skips straight from frame to frame.
+//!
+//! Layout: the file starts with [`MAGIC`]; each sample is a 4-byte
+//! little-endian payload length followed by the payload, which is an
+//! 8-byte little-endian millisecond timestamp and then, per file, a
+//! 2-byte path length + path and a 4-byte content length + content (all
+//! little-endian, paths and contents UTF-8). A log truncated mid-frame
+//! (poller killed while writing) loses only that last sample.
+//!
+//! The agent writes the format when [`Request::Poll`] asks for it; the
+//! poll-log readers in `plotters` sniff the magic and handle both
+//! formats transparently.
+//!
+//! [`Request::Poll`]: crate::proto::Request::Poll
+
+use std::io::{self, Read, Write};
+
+/// First bytes of every binary poll log, sniffed by the readers. The
+/// trailing digit versions the format.
+pub const MAGIC: &[u8; 8] = b"PMPPTPB1";
+
+/// Writer of a binary poll log, one frame per sample.
+pub struct Writer<W> {
+    out: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Start a binary poll log by writing the magic.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        Ok(Writer { out })
+    }
+
+    /// Append one sample frame.
+    pub fn write_sample(&mut self, millis: u64, files: &[(String, String)]) -> io::Result<()> {
+        let payload: usize = 8 + files.iter().map(|(p, c)| 2 + p.len() + 4 + c.len()).sum::<usize>();
+        let mut frame = Vec::with_capacity(4 + payload);
+        frame.extend_from_slice(&(payload as u32).to_le_bytes());
+        frame.extend_from_slice(&millis.to_le_bytes());
+        for (path, content) in files {
+            frame.extend_from_slice(&(path.len() as u16).to_le_bytes());
+            frame.extend_from_slice(path.as_bytes());
+            frame.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            frame.extend_from_slice(content.as_bytes());
+        }
+        self.out.write_all(&frame)
+    }
+}
+
+/// Read one sample frame, `Ok(None)` at a clean end of the log. The
+/// magic must already be consumed.
+#[allow(clippy::type_complexity)]
+pub fn read_sample<R: Read>(
+    reader: &mut R,
+) -> Result<Option<(u64, Vec<(String, String)>)>, String> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    }
+    let mut frame = vec![0u8; u32::from_le_bytes(len) as usize];
+    reader
+        .read_exact(&mut frame)
+        .map_err(|e| format!("truncated poll frame: {e}"))?;
+
+    let mut frame = frame.as_slice();
+    let millis = u64::from_le_bytes(take(&mut frame, 8)?.try_into().unwrap());
+    let mut files = Vec::new();
+    while !frame.is_empty() {
+        let len = u16::from_le_bytes(take(&mut frame, 2)?.try_into().unwrap());
+        let path = utf8(take(&mut frame, len as usize)?)?;
+        let len = u32::from_le_bytes(take(&mut frame, 4)?.try_into().unwrap());
+        let content = utf8(take(&mut frame, len as usize)?)?;
+        files.push((path, content));
+    }
+    Ok(Some((millis, files)))
+}
+
+fn take<'a>(frame: &mut &'a [u8], n: usize) -> Result<&'a [u8], String> {
+    if frame.len() < n {
+        return Err("malformed poll frame".to_string());
+    }
+    let (head, tail) = frame.split_at(n);
+    *frame = tail;
+    Ok(head)
+}
+
+fn utf8(bytes: &[u8]) -> Result<String, String> {
+    String::from_utf8(bytes.to_vec()).map_err(|e| format!("poll frame is not UTF-8: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_round_trip() {
+        let mut log = Vec::new();
+        let files = vec![
+            ("/proc/meminfo".to_string(), "MemTotal: 1 kB\n".to_string()),
+            ("/proc/net/dev".to_string(), String::new()),
+        ];
+        let mut writer = Writer::new(&mut log).unwrap();
+        writer.write_sample(1724690000000, &files).unwrap();
+        writer.write_sample(1724690000100, &[]).unwrap();
+
+        let mut reader = &log[MAGIC.len()..];
+        let (millis, read) = read_sample(&mut reader).unwrap().unwrap();
+        assert_eq!(millis, 1724690000000);
+        assert_eq!(read, files);
+        let (millis, read) = read_sample(&mut reader).unwrap().unwrap();
+        assert_eq!(millis, 1724690000100);
+        assert!(read.is_empty());
+        assert!(read_sample(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_frame_is_an_error() {
+        let mut log = Vec::new();
+        let mut writer = Writer::new(&mut log).unwrap();
+        writer
+            .write_sample(1, &[("/proc/meminfo".to_string(), "x\n".to_string())])
+            .unwrap();
+        log.truncate(log.len() - 1);
+
+        let mut reader = &log[MAGIC.len()..];
+        assert!(read_sample(&mut reader).unwrap_err().contains("truncated"));
+    }
+}
@@ -0,0 +1,278 @@
+//! Wire protocol between the controller and the agents.
+//!
+//! Every message is a 4-byte little-endian length prefix followed by a
+//! msgpack-encoded [`Request`] or [`Response`].
+
+use std::fmt;
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+/// Bumped on every incompatible protocol change.
+pub const PROTO_VERSION: u32 = 8;
+
+/// Identifier of a started activity, used to name its output files.
+///
+/// The controller proposes the id and the agent validates it (safe
+/// characters only, unique within the session), so output files keep
+/// consistent names across config edits instead of shifting with
+/// agent-side sequence numbers.
+pub type ActivityId = String;
+
+/// Controller-to-agent messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Request {
+    /// Protocol version handshake, must be the first request of a session.
+    Version,
+    /// Pre-flight health check: report which of the given tools are
+    /// missing, whether the outdir is writable, and the agent clock.
+    Check { tools: Vec<String> },
+    /// Start polling the given files with the given period.
+    Poll {
+        id: ActivityId,
+        name: String,
+        period_ms: u64,
+        paths: Vec<String>,
+        /// Write the log in the compact binary format (see the
+        /// [`polllog`](crate::polllog) module) instead of text.
+        binary: bool,
+    },
+    /// Spawn a command in background, redirecting its output to log files.
+    SpawnBg {
+        id: ActivityId,
+        name: String,
+        cmd: Vec<String>,
+    },
+    /// Spawn a command and wait for its completion.
+    SpawnFg {
+        id: ActivityId,
+        name: String,
+        cmd: Vec<String>,
+    },
+    /// Stop a single background activity.
+    Stop { id: ActivityId },
+    /// Stop all running background activities.
+    StopAll,
+    /// Sleep until the given agent-local timestamp (milliseconds since
+    /// the Unix epoch), then reply. The controller sends this ahead of
+    /// pipelined start requests to resume a barrier at one absolute
+    /// instant: the agent sleeps locally and then runs the spawns
+    /// already sitting in its socket buffer, so no round trip adds to
+    /// the start skew.
+    WaitUntil { millis: u64 },
+    /// Read one file from the agent host and send it back as a
+    /// [`Response::Chunk`] stream. Meant for selective fetches (live
+    /// samples, single artifacts) where a full [`Request::Collect`]
+    /// would be wasteful.
+    Fetch { path: String },
+    /// Pack the session output directory and send it back as a
+    /// [`Response::Chunk`] stream.
+    Collect,
+}
+
+/// Agent-to-controller messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Response {
+    Version { version: u32 },
+    Checked {
+        missing_tools: Vec<String>,
+        outdir_writable: bool,
+        agent_millis: u64,
+        /// Kernel and architecture (`uname -srm`), empty if unavailable.
+        uname: String,
+        cpus: usize,
+        mem_total_kb: u64,
+    },
+    Started { id: ActivityId },
+    Finished {
+        status: i32,
+        // Byte fields use the msgpack bin format instead of the integer
+        // array serde would pick on its own, so payloads go over the
+        // wire at their actual size.
+        #[serde(with = "serde_bytes")]
+        stdout: Vec<u8>,
+        #[serde(with = "serde_bytes")]
+        stderr: Vec<u8>,
+    },
+    Stopped { id: ActivityId },
+    AllStopped,
+    /// A [`Request::WaitUntil`] deadline has passed.
+    Resumed,
+    /// One piece of a streamed Fetch/Collect reply; `last` closes the
+    /// transfer and carries no data. Chunks are bounded, so the agent
+    /// never holds a whole perf.data or pcap in memory while sending it.
+    Chunk {
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+        last: bool,
+    },
+    Error { message: String },
+}
+
+/// Borrowed mirror of [`Response`] for decoding straight out of a
+/// receive buffer: the byte payloads reference the frame instead of
+/// being copied into fresh allocations, which matters for the chunk
+/// streams of large transfers. Variants must stay in [`Response`]
+/// order — msgpack identifies them by index.
+#[derive(Debug, Deserialize)]
+pub enum ResponseRef<'a> {
+    Version { version: u32 },
+    Checked {
+        missing_tools: Vec<String>,
+        outdir_writable: bool,
+        agent_millis: u64,
+        uname: String,
+        cpus: usize,
+        mem_total_kb: u64,
+    },
+    Started { id: ActivityId },
+    Finished {
+        status: i32,
+        #[serde(borrow, with = "serde_bytes")]
+        stdout: &'a [u8],
+        #[serde(borrow, with = "serde_bytes")]
+        stderr: &'a [u8],
+    },
+    Stopped { id: ActivityId },
+    AllStopped,
+    Resumed,
+    Chunk {
+        #[serde(borrow, with = "serde_bytes")]
+        data: &'a [u8],
+        last: bool,
+    },
+    Error { message: String },
+}
+
+#[derive(Debug)]
+pub enum ProtoError {
+    Io(std::io::Error),
+    Encode(rmp_serde::encode::Error),
+    Decode(rmp_serde::decode::Error),
+}
+
+impl fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtoError::Io(e) => write!(f, "protocol I/O error: {e}"),
+            ProtoError::Encode(e) => write!(f, "message encode error: {e}"),
+            ProtoError::Decode(e) => write!(f, "message decode error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ProtoError {}
+
+impl From<std::io::Error> for ProtoError {
+    fn from(e: std::io::Error) -> Self {
+        ProtoError::Io(e)
+    }
+}
+
+impl From<rmp_serde::encode::Error> for ProtoError {
+    fn from(e: rmp_serde::encode::Error) -> Self {
+        ProtoError::Encode(e)
+    }
+}
+
+impl From<rmp_serde::decode::Error> for ProtoError {
+    fn from(e: rmp_serde::decode::Error) -> Self {
+        ProtoError::Decode(e)
+    }
+}
+
+/// Upper bound on an accepted frame. Requests are tiny, chunk streams
+/// are bounded by the agent chunk size, and the only sizeable payloads
+/// are foreground command outputs, which fit with a wide margin. The
+/// cap keeps a hostile peer from making the receiver allocate gigabytes
+/// with a four-byte length prefix.
+pub const MAX_FRAME_LEN: usize = 64 << 20;
+
+/// Serialize and send one length-prefixed message, returning the number
+/// of bytes put on the wire (payload plus prefix).
+pub fn send_msg<T: Serialize>(stream: &mut impl Write, msg: &T) -> Result<usize, ProtoError> {
+    let data = rmp_serde::to_vec(msg)?;
+    stream.write_all(&(data.len() as u32).to_le_bytes())?;
+    stream.write_all(&data)?;
+    stream.flush()?;
+    Ok(data.len() + 4)
+}
+
+/// Receive one length-prefixed frame into `buf`, reusing its capacity.
+/// Decode separately with [`decode`], which may borrow from the buffer.
+pub fn recv_frame(stream: &mut impl Read, buf: &mut Vec<u8>) -> Result<(), ProtoError> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(ProtoError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("frame length {len} exceeds the {MAX_FRAME_LEN} byte limit"),
+        )));
+    }
+    buf.resize(len, 0);
+    stream.read_exact(buf)?;
+    Ok(())
+}
+
+/// Deserialize one received frame, borrowing byte payloads from it when
+/// the target type asks to.
+pub fn decode<'a, T: Deserialize<'a>>(buf: &'a [u8]) -> Result<T, ProtoError> {
+    Ok(rmp_serde::from_slice(buf)?)
+}
+
+/// Receive and deserialize one length-prefixed message.
+pub fn recv_msg<T: for<'de> Deserialize<'de>>(stream: &mut impl Read) -> Result<T, ProtoError> {
+    let mut buf = Vec::new();
+    recv_frame(stream, &mut buf)?;
+    decode(&buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The mirror identifies variants by index, so drifting out of sync
+    /// with [`Response`] would silently decode the wrong variant.
+    #[test]
+    fn borrowed_mirror_decodes_owned_encoding() {
+        let frame = rmp_serde::to_vec(&Response::Chunk {
+            data: vec![1, 2, 3],
+            last: false,
+        })
+        .unwrap();
+        match rmp_serde::from_slice::<ResponseRef>(&frame).unwrap() {
+            ResponseRef::Chunk { data, last } => {
+                assert_eq!(data, [1, 2, 3]);
+                assert!(!last);
+            }
+            other => panic!("decoded {other:?}"),
+        }
+
+        let frame = rmp_serde::to_vec(&Response::Error {
+            message: "boom".to_string(),
+        })
+        .unwrap();
+        assert!(matches!(
+            rmp_serde::from_slice::<ResponseRef>(&frame).unwrap(),
+            ResponseRef::Error { message } if message == "boom"
+        ));
+
+        let frame = rmp_serde::to_vec(&Response::Resumed).unwrap();
+        assert!(matches!(
+            rmp_serde::from_slice::<ResponseRef>(&frame).unwrap(),
+            ResponseRef::Resumed
+        ));
+    }
+
+    /// A length prefix alone must not be able to trigger a huge
+    /// allocation before any policy looks at the message.
+    #[test]
+    fn oversized_frame_is_rejected() {
+        let prefix = (u32::MAX).to_le_bytes();
+        let mut buf = Vec::new();
+        let err = recv_frame(&mut &prefix[..], &mut buf).unwrap_err();
+        assert!(err.to_string().contains("exceeds"));
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,246 @@
+//! Controller API server mode.
+//!
+//! `pmppt serve` turns the controller into a long-lived service that CI
+//! systems can drive programmatically: submit a config, watch the run
+//! progress, download the results. The protocol is a deliberately small
+//! slice of HTTP/1.1 over the standard library — enough for `curl` and
+//! any HTTP client, without growing a web framework dependency:
+//!
+//! - `POST /runs` — submit a YAML config, returns the new run name
+//! - `GET /runs` — list runs with their statuses
+//! - `GET /runs/<name>` — status of one run
+//! - `GET /runs/<name>/log` — lifecycle event log of one run
+//! - `GET /runs/<name>/archive` — download the run directory as .tgz
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use crate::common::{create_next_numeric_dir_in, now_millis};
+use crate::{cfgparse, controller};
+
+#[derive(Clone, PartialEq)]
+enum Status {
+    Running,
+    Completed,
+    Failed(String),
+}
+
+impl Status {
+    fn as_str(&self) -> &str {
+        match self {
+            Status::Running => "running",
+            Status::Completed => "completed",
+            Status::Failed(_) => "failed",
+        }
+    }
+}
+
+struct RunState {
+    dir: PathBuf,
+    status: Status,
+    /// Timestamped lifecycle events, served under `/log`.
+    log: Vec<String>,
+}
+
+impl RunState {
+    fn log(&mut self, event: &str) {
+        self.log.push(format!("{} {event}", now_millis()));
+    }
+}
+
+type Runs = Arc<Mutex<BTreeMap<String, RunState>>>;
+
+/// Accept and serve API connections forever, one thread per connection.
+pub fn serve(addr: &str, output_root: &Path) -> io::Result<()> {
+    std::fs::create_dir_all(output_root)?;
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("serve: listening on {addr}");
+
+    let runs: Runs = Arc::new(Mutex::new(BTreeMap::new()));
+    loop {
+        let (stream, _peer) = listener.accept()?;
+        let runs = runs.clone();
+        let output_root = output_root.to_path_buf();
+        std::thread::spawn(move || {
+            if let Err(e) = handle(stream, &runs, &output_root) {
+                eprintln!("serve: request failed: {e}");
+            }
+        });
+    }
+}
+
+fn handle(mut stream: TcpStream, runs: &Runs, output_root: &Path) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return respond(&mut stream, 400, "text/plain", b"bad request line\n");
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    // Headers: only Content-Length matters for the config upload.
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method.as_str(), segments.as_slice()) {
+        ("POST", ["runs"]) => submit(&mut stream, runs, output_root, &body),
+        ("GET", ["runs"]) => list(&mut stream, runs),
+        ("GET", ["runs", name]) => status(&mut stream, runs, name),
+        ("GET", ["runs", name, "log"]) => log(&mut stream, runs, name),
+        ("GET", ["runs", name, "archive"]) => archive(&mut stream, runs, name),
+        _ => respond(&mut stream, 404, "text/plain", b"not found\n"),
+    }
+}
+
+/// Parse the submitted config and start the run on its own thread.
+fn submit(stream: &mut TcpStream, runs: &Runs, output_root: &Path, body: &[u8]) -> io::Result<()> {
+    let text = String::from_utf8_lossy(body);
+    let config = match cfgparse::parse(&text) {
+        Ok(config) => config,
+        Err(e) => {
+            let message = format!("cannot parse config: {e}\n");
+            return respond(stream, 400, "text/plain", message.as_bytes());
+        }
+    };
+    let dir = create_next_numeric_dir_in(output_root)?;
+    let name = dir.file_name().expect("numeric dir").to_string_lossy().into_owned();
+
+    let mut state = RunState {
+        dir: dir.clone(),
+        status: Status::Running,
+        log: Vec::new(),
+    };
+    state.log("submitted");
+    runs.lock().expect("not poisoned").insert(name.clone(), state);
+
+    let runs = runs.clone();
+    let thread_name = name.clone();
+    std::thread::spawn(move || {
+        let result = controller::run(&config, &dir);
+        let mut runs = runs.lock().expect("not poisoned");
+        let state = runs.get_mut(&thread_name).expect("submitted above");
+        match result {
+            Ok(()) => {
+                state.status = Status::Completed;
+                state.log("completed");
+            }
+            Err(e) => {
+                let report =
+                    serde_json::to_string_pretty(&e.failure_report()).expect("serializable");
+                let _ = std::fs::write(dir.join("failure.json"), report);
+                state.log(&format!("failed: {e}"));
+                state.status = Status::Failed(e.to_string());
+            }
+        }
+    });
+
+    let reply = serde_json::json!({ "run": name }).to_string() + "\n";
+    respond(stream, 200, "application/json", reply.as_bytes())
+}
+
+fn list(stream: &mut TcpStream, runs: &Runs) -> io::Result<()> {
+    let runs = runs.lock().expect("not poisoned");
+    let listing: Vec<serde_json::Value> = runs
+        .iter()
+        .map(|(name, state)| run_json(name, state))
+        .collect();
+    let reply = serde_json::Value::Array(listing).to_string() + "\n";
+    respond(stream, 200, "application/json", reply.as_bytes())
+}
+
+fn status(stream: &mut TcpStream, runs: &Runs, name: &str) -> io::Result<()> {
+    let runs = runs.lock().expect("not poisoned");
+    match runs.get(name) {
+        Some(state) => {
+            let reply = run_json(name, state).to_string() + "\n";
+            respond(stream, 200, "application/json", reply.as_bytes())
+        }
+        None => respond(stream, 404, "text/plain", b"no such run\n"),
+    }
+}
+
+fn log(stream: &mut TcpStream, runs: &Runs, name: &str) -> io::Result<()> {
+    let runs = runs.lock().expect("not poisoned");
+    match runs.get(name) {
+        Some(state) => {
+            let reply = state.log.join("\n") + "\n";
+            respond(stream, 200, "text/plain", reply.as_bytes())
+        }
+        None => respond(stream, 404, "text/plain", b"no such run\n"),
+    }
+}
+
+/// Pack the run directory into a tarball and stream it out. Finished
+/// runs only: a tar racing the collection step would ship torn files.
+fn archive(stream: &mut TcpStream, runs: &Runs, name: &str) -> io::Result<()> {
+    let dir = {
+        let runs = runs.lock().expect("not poisoned");
+        match runs.get(name) {
+            Some(state) if state.status == Status::Running => {
+                return respond(stream, 409, "text/plain", b"run still in progress\n");
+            }
+            Some(state) => state.dir.clone(),
+            None => return respond(stream, 404, "text/plain", b"no such run\n"),
+        }
+    };
+
+    let parent = dir.parent().unwrap_or(Path::new("/"));
+    let output = Command::new("tar")
+        .arg("czf")
+        .arg("-")
+        .arg("-C")
+        .arg(parent)
+        .arg(dir.file_name().expect("numeric dir"))
+        .output()?;
+    if !output.status.success() {
+        return respond(stream, 500, "text/plain", b"tar failed\n");
+    }
+    respond(stream, 200, "application/gzip", &output.stdout)
+}
+
+fn run_json(name: &str, state: &RunState) -> serde_json::Value {
+    let mut value = serde_json::json!({
+        "run": name,
+        "status": state.status.as_str(),
+        "dir": state.dir.display().to_string(),
+    });
+    if let Status::Failed(error) = &state.status {
+        value["error"] = serde_json::Value::String(error.clone());
+    }
+    value
+}
+
+fn respond(stream: &mut TcpStream, code: u16, kind: &str, body: &[u8]) -> io::Result<()> {
+    let reason = match code {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {code} {reason}\r\nContent-Type: {kind}\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)
+}
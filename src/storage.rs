@@ -0,0 +1,226 @@
+//! Typed artifact storage shared between the stages of a run.
+//!
+//! Activities publish values (device paths, ports, extracted numbers) that
+//! later stages consume. Keys are namespaced by agent and stage so that
+//! independent chains cannot clash, and every lookup returns a `Result`
+//! instead of taking the whole run down on a missing or duplicate artifact.
+//! After the run all artifacts are dumped into the manifest.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Namespaced artifact key.
+///
+/// Rendered as `name`, `agent/name` or `agent/stage/name` depending on how
+/// much of the namespace is filled in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    agent: Option<String>,
+    stage: Option<String>,
+    name: String,
+}
+
+impl Key {
+    /// A run-global artifact.
+    pub fn run(name: &str) -> Self {
+        Key {
+            agent: None,
+            stage: None,
+            name: name.to_string(),
+        }
+    }
+
+    /// An artifact scoped to one agent.
+    pub fn agent(agent: &str, name: &str) -> Self {
+        Key {
+            agent: Some(agent.to_string()),
+            stage: None,
+            name: name.to_string(),
+        }
+    }
+
+    /// An artifact scoped to one agent and one stage.
+    pub fn stage(agent: &str, stage: &str, name: &str) -> Self {
+        Key {
+            agent: Some(agent.to_string()),
+            stage: Some(stage.to_string()),
+            name: name.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(agent) = &self.agent {
+            write!(f, "{agent}/")?;
+        }
+        if let Some(stage) = &self.stage {
+            write!(f, "{stage}/")?;
+        }
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// The requested artifact does not exist.
+    Missing(String),
+    /// `set_unique` found an artifact already stored under the key.
+    Duplicate(String),
+    /// The stored value cannot be deserialized into the requested type.
+    Type { key: String, error: String },
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Missing(key) => write!(f, "no artifact stored under '{key}'"),
+            StorageError::Duplicate(key) => write!(f, "artifact '{key}' is already stored"),
+            StorageError::Type { key, error } => {
+                write!(f, "artifact '{key}' has unexpected type: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+/// In-memory artifact store for one run.
+#[derive(Debug, Default)]
+pub struct Storage {
+    data: BTreeMap<String, Value>,
+}
+
+impl Storage {
+    pub fn new() -> Self {
+        Storage::default()
+    }
+
+    /// Store an artifact, failing if the key is already taken.
+    pub fn set_unique<T: Serialize>(&mut self, key: &Key, value: &T) -> Result<(), StorageError> {
+        let rendered = key.to_string();
+        if self.data.contains_key(&rendered) {
+            return Err(StorageError::Duplicate(rendered));
+        }
+        self.data
+            .insert(rendered, serde_json::to_value(value).expect("serializable"));
+        Ok(())
+    }
+
+    /// Store an artifact, silently replacing any previous value.
+    pub fn set_or_replace<T: Serialize>(&mut self, key: &Key, value: &T) {
+        self.data.insert(
+            key.to_string(),
+            serde_json::to_value(value).expect("serializable"),
+        );
+    }
+
+    /// Fetch an artifact, deserializing it into the requested type.
+    pub fn get_as<T: DeserializeOwned>(&self, key: &Key) -> Result<T, StorageError> {
+        let rendered = key.to_string();
+        let value = self
+            .data
+            .get(&rendered)
+            .ok_or_else(|| StorageError::Missing(rendered.clone()))?;
+        serde_json::from_value(value.clone()).map_err(|e| StorageError::Type {
+            key: rendered,
+            error: e.to_string(),
+        })
+    }
+
+    /// Dump all artifacts for inclusion in the run manifest.
+    pub fn dump(&self) -> Value {
+        serde_json::to_value(&self.data).expect("serializable")
+    }
+
+    /// Persist all artifacts as JSON, atomically replacing `path`.
+    ///
+    /// The controller saves the storage after every stage so that a crashed
+    /// run can be resumed with its artifact state intact.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("json.tmp");
+        fs::write(
+            &tmp,
+            serde_json::to_string_pretty(&self.data).expect("serializable"),
+        )?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Restore a previously saved storage.
+    pub fn load(path: &Path) -> io::Result<Storage> {
+        let text = fs::read_to_string(path)?;
+        let data = serde_json::from_str(&text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Storage { data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_keys_do_not_clash_across_namespaces() {
+        let mut storage = Storage::new();
+        storage.set_unique(&Key::run("dev"), &"/dev/loop0").unwrap();
+        storage
+            .set_unique(&Key::agent("srv", "dev"), &"/dev/loop1")
+            .unwrap();
+        storage
+            .set_unique(&Key::stage("srv", "load", "dev"), &"/dev/loop2")
+            .unwrap();
+
+        let dev: String = storage.get_as(&Key::agent("srv", "dev")).unwrap();
+        assert_eq!(dev, "/dev/loop1");
+    }
+
+    #[test]
+    fn duplicate_set_unique_is_an_error() {
+        let mut storage = Storage::new();
+        storage.set_unique(&Key::run("port"), &8080).unwrap();
+        assert!(matches!(
+            storage.set_unique(&Key::run("port"), &8081),
+            Err(StorageError::Duplicate(_))
+        ));
+
+        storage.set_or_replace(&Key::run("port"), &8081);
+        let port: u16 = storage.get_as(&Key::run("port")).unwrap();
+        assert_eq!(port, 8081);
+    }
+
+    #[test]
+    fn save_load_round_trip() {
+        let mut storage = Storage::new();
+        storage.set_or_replace(&Key::agent("srv", "port"), &8080);
+        storage.set_or_replace(&Key::run("label"), &"baseline");
+
+        let path = std::env::temp_dir().join(format!("pmppt-storage-{}.json", std::process::id()));
+        storage.save(&path).unwrap();
+        let restored = Storage::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.dump(), storage.dump());
+    }
+
+    #[test]
+    fn missing_and_mistyped_artifacts_are_reported() {
+        let mut storage = Storage::new();
+        assert!(matches!(
+            storage.get_as::<String>(&Key::run("nope")),
+            Err(StorageError::Missing(_))
+        ));
+
+        storage.set_or_replace(&Key::run("label"), &"baseline");
+        assert!(matches!(
+            storage.get_as::<u64>(&Key::run("label")),
+            Err(StorageError::Type { .. })
+        ));
+    }
+}
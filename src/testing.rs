@@ -0,0 +1,314 @@
+//! In-memory test doubles for the controller/agent boundary.
+//!
+//! [`MockConnection`] implements [`ConnectionOps`] without sockets: it
+//! records every request and answers from a script of responses, falling
+//! back to sensible defaults (echoed `Started` ids, successful stops)
+//! when the script runs out. [`MockAgent`] is the same idea on
+//! the [`AgentOps`] side. Both let activity implementations and
+//! controller logic be unit-tested without real sockets or processes.
+
+use std::collections::VecDeque;
+
+use crate::agent::AgentOps;
+use crate::connection::{CheckReport, ConnError, ConnectionOps, FgResult};
+use crate::proto::{ActivityId, Request, Response};
+
+/// The default success response to a request: echoed ids, clean check,
+/// empty outputs.
+fn default_response(req: &Request) -> Response {
+    match req {
+        Request::Version => Response::Version {
+            version: crate::proto::PROTO_VERSION,
+        },
+        Request::Check { .. } => Response::Checked {
+            missing_tools: vec![],
+            outdir_writable: true,
+            agent_millis: crate::common::now_millis(),
+            uname: "Mock 0.0 mock".to_string(),
+            cpus: 1,
+            mem_total_kb: 0,
+        },
+        Request::Poll { id, .. } | Request::SpawnBg { id, .. } => {
+            Response::Started { id: id.clone() }
+        }
+        Request::SpawnFg { .. } => Response::Finished {
+            status: 0,
+            stdout: vec![],
+            stderr: vec![],
+        },
+        Request::Stop { id } => Response::Stopped { id: id.clone() },
+        Request::StopAll => Response::AllStopped,
+        Request::WaitUntil { .. } => Response::Resumed,
+        Request::Fetch { .. } | Request::Collect => Response::Chunk {
+            data: vec![],
+            last: true,
+        },
+    }
+}
+
+/// A scriptable [`ConnectionOps`] double.
+#[derive(Default)]
+pub struct MockConnection {
+    /// Scripted responses, consumed front to back.
+    responses: VecDeque<Response>,
+    /// Every request handled, in order.
+    pub requests: Vec<Request>,
+}
+
+impl MockConnection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue the response for the next request; without a script every
+    /// request succeeds with a default response.
+    pub fn push_response(&mut self, response: Response) {
+        self.responses.push_back(response);
+    }
+
+    /// Record the request and answer it, from the script if one is
+    /// queued and with a default success otherwise.
+    fn transact(&mut self, req: Request) -> Response {
+        let default = self.default_response(&req);
+        self.requests.push(req);
+        self.responses.pop_front().unwrap_or(default)
+    }
+
+    fn default_response(&mut self, req: &Request) -> Response {
+        default_response(req)
+    }
+
+    fn checked(&mut self, req: Request) -> Result<Response, ConnError> {
+        match self.transact(req) {
+            Response::Error { message } => Err(ConnError::Agent(message)),
+            resp => Ok(resp),
+        }
+    }
+
+    /// Assemble a chunk-streamed reply: the scripted responses after the
+    /// first are consumed until one carries `last`.
+    fn chunked(&mut self, req: Request) -> Result<Vec<u8>, ConnError> {
+        let mut resp = self.checked(req.clone())?;
+        let mut data = Vec::new();
+        loop {
+            match resp {
+                Response::Chunk { data: chunk, last } => {
+                    data.extend_from_slice(&chunk);
+                    if last {
+                        return Ok(data);
+                    }
+                }
+                Response::Error { message } => return Err(ConnError::Agent(message)),
+                other => return Err(ConnError::Unexpected(format!("{other:?}"))),
+            }
+            resp = self.responses.pop_front().unwrap_or(Response::Chunk {
+                data: vec![],
+                last: true,
+            });
+        }
+    }
+}
+
+impl ConnectionOps for MockConnection {
+    fn check(&mut self, tools: &[String]) -> Result<CheckReport, ConnError> {
+        let req = Request::Check {
+            tools: tools.to_vec(),
+        };
+        match self.checked(req)? {
+            Response::Checked {
+                missing_tools,
+                outdir_writable,
+                agent_millis: _,
+                uname,
+                cpus,
+                mem_total_kb,
+            } => Ok(CheckReport {
+                missing_tools,
+                outdir_writable,
+                clock_offset_ms: 0,
+                uname,
+                cpus,
+                mem_total_kb,
+            }),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn poll(
+        &mut self,
+        id: &str,
+        name: &str,
+        period_ms: u64,
+        paths: &[String],
+    ) -> Result<ActivityId, ConnError> {
+        let req = Request::Poll {
+            id: id.to_string(),
+            name: name.to_string(),
+            period_ms,
+            paths: paths.to_vec(),
+            binary: false,
+        };
+        match self.checked(req)? {
+            Response::Started { id } => Ok(id),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn spawn_bg(&mut self, id: &str, name: &str, cmd: &[String]) -> Result<ActivityId, ConnError> {
+        let req = Request::SpawnBg {
+            id: id.to_string(),
+            name: name.to_string(),
+            cmd: cmd.to_vec(),
+        };
+        match self.checked(req)? {
+            Response::Started { id } => Ok(id),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn spawn_fg(&mut self, id: &str, name: &str, cmd: &[String]) -> Result<FgResult, ConnError> {
+        let req = Request::SpawnFg {
+            id: id.to_string(),
+            name: name.to_string(),
+            cmd: cmd.to_vec(),
+        };
+        match self.checked(req)? {
+            Response::Finished {
+                status,
+                stdout,
+                stderr,
+            } => Ok(FgResult {
+                status,
+                stdout,
+                stderr,
+            }),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn stop(&mut self, id: &str) -> Result<(), ConnError> {
+        let req = Request::Stop { id: id.to_string() };
+        match self.checked(req)? {
+            Response::Stopped { .. } => Ok(()),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn stop_all(&mut self) -> Result<(), ConnError> {
+        match self.checked(Request::StopAll)? {
+            Response::AllStopped => Ok(()),
+            other => Err(ConnError::Unexpected(format!("{other:?}"))),
+        }
+    }
+
+    fn fetch(&mut self, path: &str) -> Result<Vec<u8>, ConnError> {
+        let req = Request::Fetch {
+            path: path.to_string(),
+        };
+        self.chunked(req)
+    }
+
+    fn collect(&mut self) -> Result<Vec<u8>, ConnError> {
+        self.chunked(Request::Collect)
+    }
+
+    fn transact_many(&mut self, reqs: &[Request]) -> Result<Vec<Response>, ConnError> {
+        let mut resps = Vec::with_capacity(reqs.len());
+        for req in reqs {
+            match self.transact(req.clone()) {
+                Response::Error { message } => return Err(ConnError::Agent(message)),
+                resp => resps.push(resp),
+            }
+        }
+        Ok(resps)
+    }
+}
+
+/// A scriptable [`AgentOps`] double, for exercising transports and
+/// session loops against a fake agent.
+#[derive(Default)]
+pub struct MockAgent {
+    responses: VecDeque<Response>,
+    pub requests: Vec<Request>,
+}
+
+impl MockAgent {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue the response for the next request, overriding the default.
+    pub fn push_response(&mut self, response: Response) {
+        self.responses.push_back(response);
+    }
+}
+
+impl AgentOps for MockAgent {
+    fn handle(&mut self, req: Request) -> Response {
+        let default = default_response(&req);
+        self.requests.push(req);
+        self.responses.pop_front().unwrap_or(default)
+    }
+}
+
+#[cfg(all(test, feature = "controller"))]
+mod tests {
+    use super::*;
+    use crate::activities::{self, Started};
+    use crate::cfgparse::{Activity, PollFormat};
+
+    #[test]
+    fn records_requests_and_echoes_stable_ids() {
+        let mut conn = MockConnection::new();
+        let mut ids = activities::IdAlloc::new("warm up");
+        let activity = Activity::Mpstat { period: 1 };
+        let results = activities::start(&mut conn, &activity, &mut ids, 0, PollFormat::Text).unwrap();
+        assert_eq!(results.len(), 1);
+        // Stage names are sanitized into the id prefix.
+        assert!(matches!(&results[0], Started::Bg(id) if id == "warm-up.mpstat"));
+        assert!(matches!(&conn.requests[0], Request::SpawnBg { name, .. } if name == "mpstat"));
+    }
+
+    #[test]
+    fn cpufreq_pairs_apply_with_restore() {
+        let mut ids = activities::IdAlloc::new("pin");
+        let activity = Activity::Cpufreq {
+            governor: "performance".to_string(),
+            no_turbo: true,
+        };
+        let mut conn = MockConnection::new();
+        let results = activities::start(&mut conn, &activity, &mut ids, 0, PollFormat::Text).unwrap();
+        assert!(matches!(&results[0], Started::Fg(id, r) if id == "pin.cpufreq" && r.status == 0));
+
+        let cleanups = activities::cleanup_requests(&activity, &mut ids);
+        assert_eq!(cleanups.len(), 1);
+        assert!(matches!(
+            &cleanups[0],
+            Request::SpawnFg { id, name, .. }
+                if id == "pin.cpufreq-restore" && name == "cpufreq-restore"
+        ));
+    }
+
+    #[test]
+    fn resume_deadline_rides_ahead_of_the_batch() {
+        let mut conn = MockConnection::new();
+        let mut ids = activities::IdAlloc::new("load");
+        let entries = [&Activity::Mpstat { period: 1 }];
+        let results =
+            activities::start_all(&mut conn, &entries, &mut ids, 0, PollFormat::Text, Some(123))
+                .unwrap();
+        // The WaitUntil response is consumed; only the entry results remain.
+        assert_eq!(results.len(), 1);
+        assert!(matches!(conn.requests[0], Request::WaitUntil { millis: 123 }));
+        assert!(matches!(&conn.requests[1], Request::SpawnBg { name, .. } if name == "mpstat"));
+    }
+
+    #[test]
+    fn scripted_error_surfaces_as_agent_error() {
+        let mut conn = MockConnection::new();
+        conn.push_response(Response::Error {
+            message: "boom".to_string(),
+        });
+        assert!(matches!(conn.stop_all(), Err(ConnError::Agent(m)) if m == "boom"));
+    }
+}
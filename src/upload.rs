@@ -0,0 +1,78 @@
+//! Post-run upload of results to S3-compatible object storage.
+//!
+//! Benchmark farms need results off the controller box automatically.
+//! When the setup section configures a bucket, the collected archives,
+//! the manifest and the HTML report are copied there after the run, and
+//! the destination URLs are recorded in `manifest.json` first so the
+//! uploaded manifest already points at its neighbours. The copying goes
+//! through the `aws` CLI, which handles credentials and custom
+//! endpoints for any S3-compatible store.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::cfgparse::UploadCfg;
+
+/// Upload the run results, returning the destination URLs.
+pub fn run(cfg: &UploadCfg, outdir: &Path) -> io::Result<Vec<String>> {
+    let run_name = outdir
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "run".to_string());
+    let base = match &cfg.prefix {
+        Some(prefix) => format!("s3://{}/{}/{run_name}", cfg.bucket, prefix.trim_matches('/')),
+        None => format!("s3://{}/{run_name}", cfg.bucket),
+    };
+
+    let files = files_to_upload(outdir)?;
+    let urls: Vec<String> = files
+        .iter()
+        .map(|rel| format!("{base}/{}", rel.display()))
+        .collect();
+
+    record_urls(outdir, &urls)?;
+
+    for (rel, url) in files.iter().zip(&urls) {
+        eprintln!("upload: {url}");
+        let mut command = Command::new("aws");
+        command.args(["s3", "cp", "--only-show-errors"]);
+        if let Some(endpoint) = &cfg.endpoint {
+            command.arg("--endpoint-url").arg(endpoint);
+        }
+        let status = command.arg(outdir.join(rel)).arg(url).status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("aws s3 cp failed: {status}")));
+        }
+    }
+    Ok(urls)
+}
+
+/// What leaves the box: the manifest, the report if it was rendered,
+/// and every collected agent archive. The unpacked plotter output stays
+/// local — it can be regenerated from the archives.
+fn files_to_upload(outdir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![PathBuf::from("manifest.json")];
+    if outdir.join("report.html").exists() {
+        files.push(PathBuf::from("report.html"));
+    }
+    for entry in std::fs::read_dir(outdir)? {
+        let entry = entry?;
+        let archive = entry.path().join("out.tgz");
+        if archive.exists() {
+            files.push(PathBuf::from(entry.file_name()).join("out.tgz"));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Record the destination URLs in `manifest.json` before it is uploaded.
+fn record_urls(outdir: &Path, urls: &[String]) -> io::Result<()> {
+    let path = outdir.join("manifest.json");
+    let text = crate::common::readfile(&path)?;
+    let mut manifest: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|e| io::Error::other(format!("bad manifest.json: {e}")))?;
+    manifest["uploads"] = serde_json::json!(urls);
+    std::fs::write(&path, serde_json::to_string_pretty(&manifest).expect("serializable"))
+}